@@ -26,7 +26,11 @@ func _process(_delta):
 
 func _physics_process(_delta):
 	if Engine.is_editor_hint(): return
-	if playing and !paused: GR3D.step(1)
+	if playing and !paused: _step_default_world()
+
+# Other worlds are stepped by whoever created them
+func _step_default_world():
+	GR3D.step(1)
 
 func play():
 	playing = true
@@ -54,12 +54,16 @@ static func get_all_objects(root: Node) -> Array[Node3D]:
 	var pid_chars = root.find_children("*", "RapierPIDCharacter3D", true, false)
 	var rbs = root.find_children("*", "RapierRigidBody3D", true, false)
 	var static_bodies = root.find_children("*", "RapierStaticBody3D", true, false)
+	var joints = []
+	for joint_class in ["RapierFixedJoint3D", "RapierRevoluteJoint3D", "RapierPrismaticJoint3D", "RapierSphericalJoint3D", "RapierRopeJoint3D", "RapierSpringJoint3D"]:
+		joints.append_array(root.find_children("*", joint_class, true, false))
 	var all: Array[Node3D] = []
 	all.append_array(areas)
 	all.append_array(col_shapes)
 	all.append_array(kinematic_chars)
 	all.append_array(rbs)
 	all.append_array(static_bodies)
+	all.append_array(joints)
 	return all
 
 static func new_cuid(node: Node3D) -> String:
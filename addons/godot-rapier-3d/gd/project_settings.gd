@@ -8,7 +8,20 @@ var _project_settings = [
 	{ "name": "debug/rapier_3d/debug_in_editor", "type": TYPE_BOOL, "default": true },
 	{ "name": "debug/rapier_3d/show_debug_outlines", "type": TYPE_BOOL, "default": true },
 	{ "name": "debug/rapier_3d/show_ui", "type": TYPE_BOOL, "default": true },
-	{ "name": "physics/rapier_3d/gravity_vector", "type": TYPE_VECTOR3, "default": Vector3(0, -9.8, 0) },
+	{ "name": "physics/rapier_3d/gravity_vector", "type": TYPE_VECTOR3, "default": Vector3(0, -9.81, 0) },
+	{ "name": "physics/rapier_3d/ticks_per_second", "type": TYPE_INT, "default": 60, "hint": PROPERTY_HINT_RANGE, "hint_string": "1,1000,or_greater" },
+	{ "name": "physics/rapier_3d/buffer_length", "type": TYPE_INT, "default": 1000, "hint": PROPERTY_HINT_RANGE, "hint_string": "1,10000,or_greater" },
+	{ "name": "physics/rapier_3d/solver_iterations", "type": TYPE_INT, "default": 4, "hint": PROPERTY_HINT_RANGE, "hint_string": "1,32,or_greater" },
+	{ "name": "physics/rapier_3d/additional_friction_iterations", "type": TYPE_INT, "default": 0, "hint": PROPERTY_HINT_RANGE, "hint_string": "0,32,or_greater" },
+	{ "name": "physics/rapier_3d/internal_pgs_iterations", "type": TYPE_INT, "default": 1, "hint": PROPERTY_HINT_RANGE, "hint_string": "0,32,or_greater" },
+	{ "name": "physics/rapier_3d/internal_stabilization_iterations", "type": TYPE_INT, "default": 2, "hint": PROPERTY_HINT_RANGE, "hint_string": "0,32,or_greater" },
+	{ "name": "physics/rapier_3d/ccd_substeps", "type": TYPE_INT, "default": 1, "hint": PROPERTY_HINT_RANGE, "hint_string": "1,16,or_greater" },
+	{ "name": "physics/rapier_3d/contact_natural_frequency", "type": TYPE_FLOAT, "default": 30.0 },
+	{ "name": "physics/rapier_3d/contact_damping_ratio", "type": TYPE_FLOAT, "default": 5.0 },
+	{ "name": "physics/rapier_3d/joint_natural_frequency", "type": TYPE_FLOAT, "default": 1.0e6 },
+	{ "name": "physics/rapier_3d/joint_damping_ratio", "type": TYPE_FLOAT, "default": 1.0 },
+	{ "name": "physics/rapier_3d/warmstart_coefficient", "type": TYPE_FLOAT, "default": 1.0, "hint": PROPERTY_HINT_RANGE, "hint_string": "0,1,0.01" },
+	{ "name": "physics/rapier_3d/length_unit", "type": TYPE_FLOAT, "default": 1.0 },
 ]
 
 func _add_project_setting(name: String, type: int, default, hint = null, hint_string = null, restart_if_changed = false) -> void:
@@ -1,7 +1,8 @@
+use crate::utils::is_expensive_shape;
 use crate::World;
 use godot::prelude::*;
 use rapier3d::prelude::{
-    DebugRenderBackend, DebugRenderObject, DebugRenderPipeline, DebugRenderStyle, ShapeType,
+    DebugRenderBackend, DebugRenderObject, DebugRenderPipeline, DebugRenderStyle,
 };
 
 pub struct GR3DDebugger {
@@ -60,21 +61,8 @@ impl DebugRenderBackend for GR3DDebuggerBackend {
 
     fn filter_object(&self, object: DebugRenderObject) -> bool {
         match object {
-            DebugRenderObject::Collider(_, collider) => {
-                if let ShapeType::TriMesh = collider.shape().shape_type() {
-                    let casted = collider.shape().as_trimesh();
-                    if let Some(trimesh) = casted {
-                        let too_many_verts =
-                            trimesh.vertices().len() > crate::config::DEBUG_MAX_VERTEX_COUNT;
-                        return !too_many_verts;
-                    } else {
-                        return true;
-                    }
-                }
-            }
-            _ => return true,
+            DebugRenderObject::Collider(_, collider) => !is_expensive_shape(collider.shape()),
+            _ => true,
         }
-
-        true
     }
 }
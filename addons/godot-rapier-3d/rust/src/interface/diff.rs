@@ -0,0 +1,59 @@
+use godot::prelude::*;
+
+use crate::utils::vector_to_godot;
+use crate::world::diff::{BodyDiff, FieldValue, MissingObjects, SnapshotDiff};
+
+/*
+    Converts a snapshot diff into the dictionary returned by GR3D.diff_snapshots
+*/
+
+pub fn diff_to_dict(diff: &SnapshotDiff) -> Dictionary {
+    let mut dict = Dictionary::new();
+    dict.set("ok", true);
+    dict.set("identical", diff.is_identical());
+    dict.set("only_in_a", missing_to_dict(&diff.only_in_a));
+    dict.set("only_in_b", missing_to_dict(&diff.only_in_b));
+
+    let mut bodies = Array::<Dictionary>::new();
+    for body in &diff.bodies {
+        bodies.push(&body_to_dict(body));
+    }
+    dict.set("bodies", bodies);
+    dict
+}
+
+fn missing_to_dict(missing: &MissingObjects) -> Dictionary {
+    let mut dict = Dictionary::new();
+    dict.set("bodies", to_string_array(&missing.bodies));
+    dict.set("colliders", to_string_array(&missing.colliders));
+    dict.set("joints", to_string_array(&missing.joints));
+    dict
+}
+
+/// { cuid, <field>: { a, b, magnitude } } for every differing field
+fn body_to_dict(body: &BodyDiff) -> Dictionary {
+    let mut dict = Dictionary::new();
+    dict.set("cuid", GString::from(&body.cuid));
+    for field in &body.fields {
+        let mut field_dict = Dictionary::new();
+        field_dict.set("a", value_to_variant(field.a));
+        field_dict.set("b", value_to_variant(field.b));
+        field_dict.set("magnitude", field.magnitude);
+        dict.set(field.name, field_dict);
+    }
+    dict
+}
+
+fn value_to_variant(value: FieldValue) -> Variant {
+    match value {
+        FieldValue::Vector(vector) => vector_to_godot(vector).to_variant(),
+        FieldValue::Rotation(rotation) => {
+            Quaternion::new(rotation.i, rotation.j, rotation.k, rotation.w).to_variant()
+        }
+        FieldValue::Bool(value) => value.to_variant(),
+    }
+}
+
+fn to_string_array(cuids: &[String]) -> PackedStringArray {
+    cuids.iter().map(GString::from).collect()
+}
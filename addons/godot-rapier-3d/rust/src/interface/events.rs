@@ -0,0 +1,176 @@
+use godot::prelude::*;
+use std::collections::HashMap;
+
+use super::registry::{ColliderOwner, NodeRegistry};
+use crate::world::events::{EventCollider, PhysicsEvent};
+
+/*
+    Emits signals on Rapier nodes for the events collected during a world step
+
+    Signals are emitted deferred so that connected callbacks can safely call back into the GR3D singleton
+*/
+
+pub struct EventDispatcher {
+    overlaps: HashMap<(InstanceId, InstanceId), u32>, // (node, other node) -> number of touching collider pairs
+}
+
+impl EventDispatcher {
+    pub fn new() -> Self {
+        Self {
+            overlaps: HashMap::new(),
+        }
+    }
+
+    /// Recounts which bodies are touching from the given collider pairs, without emitting signals.
+    /// Used after the world state was overwritten, e.g. by a snapshot or a correction
+    pub fn resync_overlaps(
+        &mut self,
+        pairs: Vec<(EventCollider, EventCollider)>,
+        registry: &NodeRegistry,
+    ) {
+        self.overlaps.clear();
+        for (collider1, collider2) in pairs {
+            if let (Some(a), Some(b)) = (
+                registry.get_owner(&collider1),
+                registry.get_owner(&collider2),
+            ) {
+                for (own, other) in [(&a, &b), (&b, &a)] {
+                    if tracks_overlap(own, other) {
+                        self.update_overlap(own, other, true);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn dispatch(&mut self, events: Vec<PhysicsEvent>, registry: &NodeRegistry) {
+        for event in events {
+            match event {
+                PhysicsEvent::CollisionStarted {
+                    collider1,
+                    collider2,
+                    ..
+                } => {
+                    if let (Some(a), Some(b)) = (
+                        registry.get_owner(&collider1),
+                        registry.get_owner(&collider2),
+                    ) {
+                        self.on_collision(&a, &b, true);
+                        self.on_collision(&b, &a, true);
+                    }
+                }
+                PhysicsEvent::CollisionStopped {
+                    collider1,
+                    collider2,
+                    ..
+                } => {
+                    if let (Some(a), Some(b)) = (
+                        registry.get_owner(&collider1),
+                        registry.get_owner(&collider2),
+                    ) {
+                        self.on_collision(&a, &b, false);
+                        self.on_collision(&b, &a, false);
+                    }
+                }
+                PhysicsEvent::ContactForce {
+                    collider1,
+                    collider2,
+                    total_force,
+                } => {
+                    if let (Some(a), Some(b)) = (
+                        registry.get_owner(&collider1),
+                        registry.get_owner(&collider2),
+                    ) {
+                        let force = Vector3::new(total_force.x, total_force.y, total_force.z);
+                        if !a.is_area && !b.is_area {
+                            emit(
+                                &a.node,
+                                "contact_force",
+                                &[b.node.to_variant(), force.to_variant()],
+                            );
+                            emit(
+                                &b.node,
+                                "contact_force",
+                                &[a.node.to_variant(), (-force).to_variant()],
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+    /// Emits the signals for one side of a collision
+    fn on_collision(&mut self, own: &ColliderOwner, other: &ColliderOwner, started: bool) {
+        if !tracks_overlap(own, other) {
+            return;
+        }
+        if own.is_area {
+            let prefix = if other.is_area { "area" } else { "body" };
+            if self.update_overlap(own, other, started) {
+                let signal = format!("{}_{}", prefix, if started { "entered" } else { "exited" });
+                emit(&own.node, &signal, &[other.node.to_variant()]);
+            }
+        } else {
+            let signal = if started {
+                "contact_started"
+            } else {
+                "contact_ended"
+            };
+            emit(
+                &own.node,
+                signal,
+                &[other.collider.to_variant(), own.collider.to_variant()],
+            );
+
+            if self.update_overlap(own, other, started) {
+                let signal = if started {
+                    "body_entered"
+                } else {
+                    "body_exited"
+                };
+                emit(&own.node, signal, &[other.node.to_variant()]);
+            }
+        }
+    }
+
+    /// Counts touching collider pairs between two nodes.
+    /// Returns true when the nodes started or stopped touching entirely
+    fn update_overlap(
+        &mut self,
+        own: &ColliderOwner,
+        other: &ColliderOwner,
+        started: bool,
+    ) -> bool {
+        let key = (own.node.instance_id(), other.node.instance_id());
+        let count = self.overlaps.get(&key).copied().unwrap_or(0);
+        match started {
+            true => {
+                self.overlaps.insert(key, count + 1);
+                count == 0
+            }
+            false => match count {
+                0 => false,
+                1 => {
+                    self.overlaps.remove(&key);
+                    true
+                }
+                _ => {
+                    self.overlaps.insert(key, count - 1);
+                    false
+                }
+            },
+        }
+    }
+}
+
+/// Areas see bodies and other areas entering, bodies only see other bodies
+fn tracks_overlap(own: &ColliderOwner, other: &ColliderOwner) -> bool {
+    own.is_area || !other.is_area
+}
+
+fn emit(node: &Gd<Node3D>, signal: &str, args: &[Variant]) {
+    let mut node = node.clone();
+    let mut call_args = vec![StringName::from(signal).to_variant()];
+    call_args.extend_from_slice(args);
+    node.call_deferred("emit_signal", &call_args);
+}
@@ -1,7 +1,13 @@
 mod debugger;
+mod diff;
 mod editor_plugin;
+mod events;
+mod query;
+mod registry;
+mod settings;
 mod singleton;
 mod world;
+mod worlds;
 
 // Interface module is responsible for all communication between Godot and Rapier
 
@@ -10,6 +16,7 @@ pub use singleton::{
 };
 
 pub use editor_plugin::get_runtime;
+pub use registry::ColliderOwner;
 pub use world::{
-    add_node_to_world, configure_node, move_node, remove_node_from_world, Action, Operation,
+    action_order, add_node_to_world, configure_node, move_node, rotate_node, Action, Operation,
 };
@@ -0,0 +1,506 @@
+use godot::classes::{IRefCounted, RefCounted, Shape3D};
+use godot::prelude::*;
+use rapier3d::parry::query::ShapeCastOptions;
+use rapier3d::prelude::*;
+use std::collections::HashSet;
+
+use super::registry::NodeRegistry;
+use crate::utils::{shape_to_rapier, transform_to_isometry, vector_to_godot, vector_to_rapier};
+use crate::world::events::EventCollider;
+use crate::world::lookup::HandleKind;
+use crate::world::state::PhysicsState;
+
+/*
+    Scene queries (raycasts, shape casts, point queries) against the current state of the world
+
+    Results are dictionaries containing the node that owns the hit collider and the collider's cuid,
+    plus query specific data (point, normal, toi)
+*/
+
+#[derive(GodotClass)]
+#[class(base=RefCounted)]
+/// Restricts which colliders a GR3D query can hit
+pub struct RapierQueryFilter {
+    #[export(flags_3d_physics)]
+    /// The physics layers the query is in, tested against the collision mask of colliders
+    pub collision_layer: u32,
+    #[export(flags_3d_physics)]
+    /// The physics layers the query scans
+    pub collision_mask: u32,
+    #[export]
+    /// Bodies, areas or collision shapes to ignore
+    pub exclude: Array<Gd<Node3D>>,
+    #[export]
+    /// Whether colliders of areas can be hit
+    pub include_sensors: bool,
+    base: Base<RefCounted>,
+}
+
+#[godot_api]
+impl IRefCounted for RapierQueryFilter {
+    fn init(base: Base<RefCounted>) -> Self {
+        Self {
+            collision_layer: u32::MAX,
+            collision_mask: u32::MAX,
+            exclude: Array::new(),
+            include_sensors: false,
+            base,
+        }
+    }
+}
+
+/// Rapier handles belonging to the nodes excluded by a filter
+struct Exclusions {
+    bodies: HashSet<RigidBodyHandle>,
+    colliders: HashSet<ColliderHandle>,
+}
+
+impl Exclusions {
+    fn new(nodes: &Array<Gd<Node3D>>, physics: &PhysicsState) -> Self {
+        let mut exclusions = Self {
+            bodies: HashSet::new(),
+            colliders: HashSet::new(),
+        };
+
+        for node in nodes.iter_shared() {
+            exclusions.insert(&node.clone().upcast(), physics);
+            let shapes = node
+                .find_children_ex("*")
+                .type_("RapierCollisionShape3D")
+                .recursive(true)
+                .owned(false)
+                .done();
+            for shape in shapes.iter_shared() {
+                exclusions.insert(&shape, physics);
+            }
+        }
+
+        exclusions
+    }
+
+    fn insert(&mut self, node: &Gd<Node>, physics: &PhysicsState) {
+        if !node.has_meta("cuid") {
+            return;
+        }
+        let cuid = GString::from_variant(&node.get_meta("cuid")).to_string();
+        if let Some(raw) = physics.lookup_table.get_rapier_handle(&cuid) {
+            match physics
+                .lookup_table
+                .get_godot_uid(HandleKind::RigidBody, &raw)
+            {
+                Some(uid) if uid == cuid => {
+                    self.bodies
+                        .insert(RigidBodyHandle::from_raw_parts(raw.0, raw.1));
+                }
+                _ => {
+                    self.colliders
+                        .insert(ColliderHandle::from_raw_parts(raw.0, raw.1));
+                }
+            }
+        }
+    }
+
+    fn contains(&self, handle: ColliderHandle, collider: &Collider) -> bool {
+        self.colliders.contains(&handle)
+            || collider
+                .parent()
+                .is_some_and(|parent| self.bodies.contains(&parent))
+    }
+}
+
+/// Builds a Rapier query filter from the given Godot filter and passes it to the query
+fn with_filter<R>(
+    filter: &Option<Gd<RapierQueryFilter>>,
+    physics: &PhysicsState,
+    query: impl FnOnce(QueryFilter) -> R,
+) -> R {
+    match filter {
+        Some(filter) => {
+            let filter = filter.bind();
+            let exclusions = Exclusions::new(&filter.exclude, physics);
+            let predicate = |handle: ColliderHandle, collider: &Collider| {
+                !exclusions.contains(handle, collider)
+            };
+
+            let mut query_filter = QueryFilter::new()
+                .groups(InteractionGroups::new(
+                    Group::from_bits_truncate(filter.collision_layer),
+                    Group::from_bits_truncate(filter.collision_mask),
+                ))
+                .predicate(&predicate);
+            if !filter.include_sensors {
+                query_filter = query_filter.exclude_sensors();
+            }
+
+            query(query_filter)
+        }
+        None => query(QueryFilter::new().exclude_sensors()),
+    }
+}
+
+/// Base result dictionary describing the hit collider
+fn hit_to_dict(
+    handle: ColliderHandle,
+    physics: &PhysicsState,
+    registry: &NodeRegistry,
+) -> Dictionary {
+    let mut dict = Dictionary::new();
+    match EventCollider::from_handle(handle, physics) {
+        Some(collider) => {
+            let node = registry.get_owner(&collider).map(|owner| owner.node);
+            dict.set("node", node.to_variant());
+            dict.set("collider", collider.collider_uid);
+        }
+        None => {
+            dict.set("node", Variant::nil());
+            dict.set("collider", GString::new());
+        }
+    }
+    dict
+}
+
+fn to_ray(origin: Vector3, direction: Vector3) -> Ray {
+    Ray::new(
+        point![origin.x, origin.y, origin.z],
+        vector_to_rapier(direction),
+    )
+}
+
+fn to_shape(shape: &Gd<Shape3D>, transform: Transform3D) -> Option<(SharedShape, Isometry<Real>)> {
+    Some((shape_to_rapier(shape)?, transform_to_isometry(transform)))
+}
+
+/// Returns the first collider hit by the ray. The toi is expressed in multiples of direction
+pub fn cast_ray(
+    physics: &PhysicsState,
+    registry: &NodeRegistry,
+    origin: Vector3,
+    direction: Vector3,
+    max_toi: Real,
+    solid: bool,
+    filter: Option<Gd<RapierQueryFilter>>,
+) -> Dictionary {
+    let ray = to_ray(origin, direction);
+    let hit = with_filter(&filter, physics, |query_filter| {
+        physics.query_pipeline.cast_ray(
+            &physics.bodies,
+            &physics.colliders,
+            &ray,
+            max_toi,
+            solid,
+            query_filter,
+        )
+    });
+
+    match hit {
+        Some((handle, toi)) => {
+            let mut dict = hit_to_dict(handle, physics, registry);
+            dict.set("point", origin + direction * toi);
+            dict.set("toi", toi);
+            dict
+        }
+        None => Dictionary::new(),
+    }
+}
+
+/// Same as cast_ray, but also computes the normal at the hit point
+pub fn cast_ray_and_get_normal(
+    physics: &PhysicsState,
+    registry: &NodeRegistry,
+    origin: Vector3,
+    direction: Vector3,
+    max_toi: Real,
+    solid: bool,
+    filter: Option<Gd<RapierQueryFilter>>,
+) -> Dictionary {
+    let ray = to_ray(origin, direction);
+    let hit = with_filter(&filter, physics, |query_filter| {
+        physics.query_pipeline.cast_ray_and_get_normal(
+            &physics.bodies,
+            &physics.colliders,
+            &ray,
+            max_toi,
+            solid,
+            query_filter,
+        )
+    });
+
+    match hit {
+        Some((handle, intersection)) => {
+            ray_hit_to_dict(handle, intersection, origin, direction, physics, registry)
+        }
+        None => Dictionary::new(),
+    }
+}
+
+/// Returns every collider hit by the ray
+pub fn intersections_with_ray(
+    physics: &PhysicsState,
+    registry: &NodeRegistry,
+    origin: Vector3,
+    direction: Vector3,
+    max_toi: Real,
+    solid: bool,
+    filter: Option<Gd<RapierQueryFilter>>,
+) -> Array<Dictionary> {
+    let ray = to_ray(origin, direction);
+    let mut hits = Array::new();
+    with_filter(&filter, physics, |query_filter| {
+        physics.query_pipeline.intersections_with_ray(
+            &physics.bodies,
+            &physics.colliders,
+            &ray,
+            max_toi,
+            solid,
+            query_filter,
+            |handle, intersection| {
+                hits.push(&ray_hit_to_dict(
+                    handle,
+                    intersection,
+                    origin,
+                    direction,
+                    physics,
+                    registry,
+                ));
+                true
+            },
+        )
+    });
+    hits
+}
+
+fn ray_hit_to_dict(
+    handle: ColliderHandle,
+    intersection: RayIntersection,
+    origin: Vector3,
+    direction: Vector3,
+    physics: &PhysicsState,
+    registry: &NodeRegistry,
+) -> Dictionary {
+    let mut dict = hit_to_dict(handle, physics, registry);
+    dict.set("point", origin + direction * intersection.time_of_impact);
+    dict.set("normal", vector_to_godot(intersection.normal));
+    dict.set("toi", intersection.time_of_impact);
+    dict
+}
+
+/// Moves the shape from the transform along velocity and returns the first collider it hits.
+/// The toi is expressed in multiples of velocity
+#[allow(clippy::too_many_arguments)]
+pub fn cast_shape(
+    physics: &PhysicsState,
+    registry: &NodeRegistry,
+    shape: Gd<Shape3D>,
+    transform: Transform3D,
+    velocity: Vector3,
+    max_toi: Real,
+    stop_at_penetration: bool,
+    filter: Option<Gd<RapierQueryFilter>>,
+) -> Dictionary {
+    let Some((shape, position)) = to_shape(&shape, transform) else {
+        return Dictionary::new();
+    };
+
+    let options = ShapeCastOptions {
+        max_time_of_impact: max_toi,
+        stop_at_penetration,
+        ..ShapeCastOptions::default()
+    };
+    let hit = with_filter(&filter, physics, |query_filter| {
+        shape_hit(
+            physics,
+            &position,
+            &vector_to_rapier(velocity),
+            &*shape,
+            options,
+            query_filter,
+        )
+    });
+
+    match hit {
+        Some(hit) => {
+            let mut dict = hit_to_dict(hit.handle, physics, registry);
+            dict.set("point", vector_to_godot(hit.point.coords));
+            dict.set("normal", vector_to_godot(hit.normal));
+            dict.set("toi", hit.toi);
+            dict
+        }
+        None => Dictionary::new(),
+    }
+}
+
+/// The collider first hit by a shape cast, with the point and normal on its surface
+struct ShapeHit {
+    handle: ColliderHandle,
+    point: Point<Real>,
+    normal: Vector<Real>,
+    toi: Real,
+}
+
+fn shape_hit(
+    physics: &PhysicsState,
+    position: &Isometry<Real>,
+    velocity: &Vector<Real>,
+    shape: &dyn Shape,
+    options: ShapeCastOptions,
+    filter: QueryFilter,
+) -> Option<ShapeHit> {
+    let (handle, hit) = physics.query_pipeline.cast_shape(
+        &physics.bodies,
+        &physics.colliders,
+        position,
+        velocity,
+        shape,
+        options,
+        filter,
+    )?;
+    // Witness and normal 1 belong to the hit collider and are in world space,
+    // witness and normal 2 are in the local space of the cast shape
+    Some(ShapeHit {
+        handle,
+        point: hit.witness1,
+        normal: hit.normal1.into_inner(),
+        toi: hit.time_of_impact,
+    })
+}
+
+/// Returns every collider containing the point
+pub fn intersections_with_point(
+    physics: &PhysicsState,
+    registry: &NodeRegistry,
+    point: Vector3,
+    filter: Option<Gd<RapierQueryFilter>>,
+) -> Array<Dictionary> {
+    let mut hits = Array::new();
+    with_filter(&filter, physics, |query_filter| {
+        physics.query_pipeline.intersections_with_point(
+            &physics.bodies,
+            &physics.colliders,
+            &point![point.x, point.y, point.z],
+            query_filter,
+            |handle| {
+                hits.push(&hit_to_dict(handle, physics, registry));
+                true
+            },
+        )
+    });
+    hits
+}
+
+/// Returns every collider intersecting the shape at the given transform
+pub fn intersections_with_shape(
+    physics: &PhysicsState,
+    registry: &NodeRegistry,
+    shape: Gd<Shape3D>,
+    transform: Transform3D,
+    filter: Option<Gd<RapierQueryFilter>>,
+) -> Array<Dictionary> {
+    let mut hits = Array::new();
+    let Some((shape, position)) = to_shape(&shape, transform) else {
+        return hits;
+    };
+
+    with_filter(&filter, physics, |query_filter| {
+        physics.query_pipeline.intersections_with_shape(
+            &physics.bodies,
+            &physics.colliders,
+            &position,
+            &*shape,
+            query_filter,
+            |handle| {
+                hits.push(&hit_to_dict(handle, physics, registry));
+                true
+            },
+        )
+    });
+    hits
+}
+
+/// Returns the point on the closest collider nearest to the given point
+pub fn project_point(
+    physics: &PhysicsState,
+    registry: &NodeRegistry,
+    point: Vector3,
+    solid: bool,
+    filter: Option<Gd<RapierQueryFilter>>,
+) -> Dictionary {
+    let hit = with_filter(&filter, physics, |query_filter| {
+        physics.query_pipeline.project_point(
+            &physics.bodies,
+            &physics.colliders,
+            &point![point.x, point.y, point.z],
+            solid,
+            query_filter,
+        )
+    });
+
+    match hit {
+        Some((handle, projection)) => {
+            let mut dict = hit_to_dict(handle, physics, registry);
+            dict.set("point", vector_to_godot(projection.point.coords));
+            dict.set("is_inside", projection.is_inside);
+            dict
+        }
+        None => Dictionary::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::command::Command;
+    use crate::world::descriptor::{BodyDescriptor, BodyKind, ColliderDescriptor};
+    use crate::World;
+
+    #[test]
+    fn shape_cast_hits_in_world_space() {
+        let mut world = World::new_empty();
+        Command::AddBody(
+            BodyDescriptor::new(
+                "floor",
+                BodyKind::Fixed,
+                Isometry::translation(0.0, -0.5, 0.0),
+            )
+            .with_collider(ColliderDescriptor::new(
+                "floor_shape",
+                SharedShape::cuboid(50.0, 0.5, 50.0),
+            )),
+        )
+        .execute(&mut world.physics);
+        world.step();
+
+        // A rotated ball, away from the origin, falling onto the floor
+        let position = Isometry::new(vector![3.0, 5.0, -2.0], vector![0.4, 0.0, 1.1]);
+        let options = ShapeCastOptions {
+            max_time_of_impact: 10.0,
+            ..ShapeCastOptions::default()
+        };
+        let hit = shape_hit(
+            &world.physics,
+            &position,
+            &vector![0.0, -1.0, 0.0],
+            &Ball::new(0.5),
+            options,
+            QueryFilter::new(),
+        )
+        .unwrap();
+
+        let floor = world
+            .physics
+            .lookup_table
+            .get_rapier_handle("floor_shape")
+            .unwrap();
+        assert_eq!(hit.handle.into_raw_parts(), floor);
+        assert!(
+            (hit.point - point![3.0, 0.0, -2.0]).norm() < 1.0e-3,
+            "point should be on the floor below the ball, at {:?}",
+            hit.point
+        );
+        assert!(
+            (hit.normal - Vector::y()).norm() < 1.0e-3,
+            "normal should point up, got {:?}",
+            hit.normal
+        );
+        assert!((hit.toi - 4.5).abs() < 1.0e-2, "toi {}", hit.toi);
+    }
+}
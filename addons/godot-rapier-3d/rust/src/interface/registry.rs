@@ -0,0 +1,75 @@
+use godot::prelude::*;
+use std::collections::HashMap;
+
+use crate::world::events::EventCollider;
+
+/*
+    Keeps track of the Godot nodes that have been added to the world, so that results coming out of Rapier
+    (events, queries) can be mapped back to nodes via their cuid
+*/
+
+pub struct NodeRegistry {
+    nodes: HashMap<String, InstanceId>, // cuid -> node
+}
+
+/// The node that owns a collider (body or area), plus the collision shape node itself
+pub struct ColliderOwner {
+    pub node: Gd<Node3D>,
+    pub collider: Gd<Node3D>,
+    pub is_area: bool,
+}
+
+impl NodeRegistry {
+    pub fn new() -> Self {
+        Self {
+            nodes: HashMap::new(),
+        }
+    }
+
+    pub fn register(&mut self, cuid: &GString, node: &Gd<Node3D>) {
+        self.nodes.insert(cuid.to_string(), node.instance_id());
+    }
+
+    pub fn unregister(&mut self, cuid: &GString) {
+        self.nodes.remove(&cuid.to_string());
+    }
+
+    pub fn contains(&self, cuid: &str) -> bool {
+        self.nodes.contains_key(cuid)
+    }
+
+    pub fn get_node(&self, cuid: &str) -> Option<Gd<Node3D>> {
+        self.nodes
+            .get(cuid)
+            .and_then(|id| Gd::try_from_instance_id(*id).ok())
+    }
+
+    /// Finds the body or area node that owns a collider
+    pub fn get_owner(&self, collider: &EventCollider) -> Option<ColliderOwner> {
+        let collider_node = self.get_node(&collider.collider_uid)?;
+
+        let node = match &collider.body_uid {
+            Some(body_uid) => self.get_node(body_uid)?,
+            None => find_area(&collider_node)?,
+        };
+
+        let is_area = node.is_class("RapierArea3D");
+        Some(ColliderOwner {
+            node,
+            collider: collider_node,
+            is_area,
+        })
+    }
+}
+
+/// Walks up the tree from a collision shape to the area it belongs to
+fn find_area(collider: &Gd<Node3D>) -> Option<Gd<Node3D>> {
+    let mut current = collider.get_parent();
+    while let Some(parent) = current {
+        if parent.is_class("RapierArea3D") {
+            return parent.try_cast::<Node3D>().ok();
+        }
+        current = parent.get_parent();
+    }
+    None
+}
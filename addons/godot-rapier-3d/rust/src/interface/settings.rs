@@ -0,0 +1,190 @@
+use godot::classes::ProjectSettings;
+use godot::prelude::*;
+use rapier3d::prelude::Real;
+use std::fmt;
+
+use crate::utils::{vector_to_godot, vector_to_rapier};
+use crate::world::settings::{WorldParameter, WorldSettings};
+use crate::World;
+
+/*
+    Maps the physics/rapier_3d/... project settings to world settings.
+    The same names are used by GR3D.set_world_parameter to change them at runtime
+*/
+
+const SETTINGS_PREFIX: &str = "physics/rapier_3d/";
+pub const BUFFER_LENGTH: &str = "buffer_length";
+pub const PARAMETERS: [&str; 13] = [
+    "gravity_vector",
+    "ticks_per_second",
+    "solver_iterations",
+    "additional_friction_iterations",
+    "internal_pgs_iterations",
+    "internal_stabilization_iterations",
+    "ccd_substeps",
+    "contact_natural_frequency",
+    "contact_damping_ratio",
+    "joint_natural_frequency",
+    "joint_damping_ratio",
+    "warmstart_coefficient",
+    "length_unit",
+];
+
+/// Reasons a world parameter can be rejected
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParameterError {
+    UnknownParameter { name: String },
+    InvalidValue { name: String },
+}
+
+impl ParameterError {
+    /// Stable identifier exposed to Godot
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParameterError::UnknownParameter { .. } => "UNKNOWN_PARAMETER",
+            ParameterError::InvalidValue { .. } => "INVALID_VALUE",
+        }
+    }
+}
+
+impl fmt::Display for ParameterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParameterError::UnknownParameter { name } => {
+                write!(f, "Unknown world parameter '{}'", name)
+            }
+            ParameterError::InvalidValue { name } => {
+                write!(f, "Invalid value for world parameter '{}'", name)
+            }
+        }
+    }
+}
+
+/// Reads the world settings from the project settings. Missing settings keep Rapier's defaults
+pub fn world_settings_from_project() -> WorldSettings {
+    let project = ProjectSettings::singleton();
+    let read = |name: &str| {
+        let path = format!("{}{}", SETTINGS_PREFIX, name);
+        match project.has_setting(&path) {
+            true => Some(project.get_setting(&path)),
+            false => None,
+        }
+    };
+
+    let mut settings = WorldSettings::default();
+    if let Some(value) = read(BUFFER_LENGTH) {
+        match buffer_length(&value) {
+            Ok(length) => settings.buffer_length = length,
+            Err(e) => log::error!("Invalid project setting: {}", e),
+        }
+    }
+    for name in PARAMETERS {
+        if let Some(value) = read(name) {
+            match world_parameter(name, &value) {
+                Ok(parameter) => settings.parameters.push(parameter),
+                Err(e) => log::error!("Invalid project setting: {}", e),
+            }
+        }
+    }
+    settings
+}
+
+pub fn buffer_length(value: &Variant) -> Result<usize, ParameterError> {
+    count(value, 1).ok_or(ParameterError::InvalidValue {
+        name: BUFFER_LENGTH.to_string(),
+    })
+}
+
+/// Converts a named value to the world parameter it sets
+pub fn world_parameter(name: &str, value: &Variant) -> Result<WorldParameter, ParameterError> {
+    let parameter = match name {
+        "gravity_vector" => value
+            .try_to::<Vector3>()
+            .ok()
+            .filter(|gravity| gravity.is_finite())
+            .map(|gravity| WorldParameter::Gravity(vector_to_rapier(gravity))),
+        "ticks_per_second" => count(value, 1)
+            .and_then(|ticks| u32::try_from(ticks).ok())
+            .map(WorldParameter::TicksPerSecond),
+        "solver_iterations" => count(value, 1).map(WorldParameter::SolverIterations),
+        "additional_friction_iterations" => {
+            count(value, 0).map(WorldParameter::AdditionalFrictionIterations)
+        }
+        "internal_pgs_iterations" => count(value, 0).map(WorldParameter::InternalPgsIterations),
+        "internal_stabilization_iterations" => {
+            count(value, 0).map(WorldParameter::InternalStabilizationIterations)
+        }
+        "ccd_substeps" => count(value, 1).map(WorldParameter::CcdSubsteps),
+        "contact_natural_frequency" => {
+            real(value, false).map(WorldParameter::ContactNaturalFrequency)
+        }
+        "contact_damping_ratio" => real(value, true).map(WorldParameter::ContactDampingRatio),
+        "joint_natural_frequency" => real(value, false).map(WorldParameter::JointNaturalFrequency),
+        "joint_damping_ratio" => real(value, true).map(WorldParameter::JointDampingRatio),
+        "warmstart_coefficient" => real(value, true).map(WorldParameter::WarmstartCoefficient),
+        "length_unit" => real(value, false).map(WorldParameter::LengthUnit),
+        _ => {
+            return Err(ParameterError::UnknownParameter {
+                name: name.to_string(),
+            })
+        }
+    };
+    parameter.ok_or(ParameterError::InvalidValue {
+        name: name.to_string(),
+    })
+}
+
+/// Current value of every world parameter, and the buffer length, keyed by setting name
+pub fn world_parameters_to_dict(world: &World) -> Dictionary {
+    let params = &world.physics.integration_parameters;
+    let mut dict = Dictionary::new();
+    dict.set("gravity_vector", vector_to_godot(world.physics.gravity));
+    dict.set("ticks_per_second", params.inv_dt().round() as i64);
+    dict.set(
+        "solver_iterations",
+        params.num_solver_iterations.get() as i64,
+    );
+    dict.set(
+        "additional_friction_iterations",
+        params.num_additional_friction_iterations as i64,
+    );
+    dict.set(
+        "internal_pgs_iterations",
+        params.num_internal_pgs_iterations as i64,
+    );
+    dict.set(
+        "internal_stabilization_iterations",
+        params.num_internal_stabilization_iterations as i64,
+    );
+    dict.set("ccd_substeps", params.max_ccd_substeps as i64);
+    dict.set(
+        "contact_natural_frequency",
+        params.contact_natural_frequency,
+    );
+    dict.set("contact_damping_ratio", params.contact_damping_ratio);
+    dict.set("joint_natural_frequency", params.joint_natural_frequency);
+    dict.set("joint_damping_ratio", params.joint_damping_ratio);
+    dict.set("warmstart_coefficient", params.warmstart_coefficient);
+    dict.set("length_unit", params.length_unit);
+    dict.set(BUFFER_LENGTH, world.buffer.max_len as i64);
+    dict
+}
+
+/// Integer of at least min
+fn count(value: &Variant, min: i64) -> Option<usize> {
+    value
+        .try_to::<i64>()
+        .ok()
+        .filter(|count| *count >= min)
+        .map(|count| count as usize)
+}
+
+/// Finite real number, positive or also zero if allow_zero. Integers are accepted too
+fn real(value: &Variant, allow_zero: bool) -> Option<Real> {
+    let number = value
+        .try_to::<f64>()
+        .or_else(|_| value.try_to::<i64>().map(|number| number as f64))
+        .ok()?;
+    let valid = number.is_finite() && (number > 0.0 || (allow_zero && number == 0.0));
+    valid.then_some(number as Real)
+}
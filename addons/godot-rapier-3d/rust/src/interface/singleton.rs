@@ -1,11 +1,24 @@
 use super::debugger::GR3DDebugger;
-use super::world::ingest_action;
+use super::diff::diff_to_dict;
+use super::query::{self, RapierQueryFilter};
+use super::registry::ColliderOwner;
+use super::settings::{
+    buffer_length, world_parameter, world_parameters_to_dict, world_settings_from_project,
+    BUFFER_LENGTH,
+};
+use super::world::{extract_identity, ingest_action};
+use super::worlds::{WorldContext, WorldError, WorldRegistry, DEFAULT_WORLD};
 use super::Operation;
 use crate::nodes::{generate_cuid, IRapierObject};
+use crate::resources::{RapierSnapshot, SnapshotFormats};
+use crate::utils::vector_to_godot;
 use crate::utils::{init_logger, set_log_level};
-use crate::world::state::restore_snapshot;
+use crate::world::command::Command;
+use crate::world::diff::diff_snapshots;
+use crate::world::events::EventCollider;
+use crate::world::state::{restore_snapshot, unpack_snapshot};
 use crate::World;
-use godot::classes::{Engine, IObject, Object};
+use godot::classes::{Engine, IObject, Object, SceneTree, Shape3D};
 use godot::prelude::*;
 
 /*
@@ -15,8 +28,9 @@ use godot::prelude::*;
 #[derive(GodotClass)]
 #[class(base = Object)]
 pub struct GR3D {
-    pub world: World,
+    worlds: WorldRegistry,
     debugger: GR3DDebugger,
+    snapshot_formats: Option<SnapshotFormats>,
     base: Base<Object>,
 }
 
@@ -24,27 +38,96 @@ pub struct GR3D {
 impl IObject for GR3D {
     fn init(base: Base<Object>) -> Self {
         Self {
-            world: World::new_empty(),
+            worlds: WorldRegistry::new(world_settings_from_project()),
             debugger: GR3DDebugger::new(),
+            snapshot_formats: None,
             base,
         }
     }
 }
 
+impl GR3D {
+    /// The world the node with the given world_id belongs to, for reads
+    pub fn get_world(&self, world_id: i64) -> Option<&World> {
+        self.worlds.get(world_id).map(|context| &context.world)
+    }
+
+    /// The nodes of a collider in the given world, e.g. to resolve the colliders a character hit
+    pub fn get_collider_owner(
+        &self,
+        world_id: i64,
+        collider: &EventCollider,
+    ) -> Option<ColliderOwner> {
+        self.worlds.get(world_id)?.registry.get_owner(collider)
+    }
+
+    /// The world a GR3D function acts on, logging an error if it does not exist
+    fn context(&self, world_id: i64) -> Option<&WorldContext> {
+        self.worlds
+            .find(world_id)
+            .map_err(|e| log::error!("{}", e))
+            .ok()
+    }
+
+    fn context_mut(&mut self, world_id: i64) -> Option<&mut WorldContext> {
+        self.worlds
+            .find_mut(world_id)
+            .map_err(|e| log::error!("{}", e))
+            .ok()
+    }
+}
+
 #[godot_api]
 impl GR3D {
     #[func]
-    /// Advance the simulation by the given number of steps
+    /// Advance the simulation of the world by the given number of steps
     pub fn step(&mut self, count: i64) {
-        for _ in 0..count {
-            self.world.step();
+        self.step_world(count, DEFAULT_WORLD)
+    }
+
+    #[func]
+    /// Same as step, on the world with the given id
+    pub fn step_world(&mut self, count: i64, world_id: i64) {
+        if let Some(context) = self.context_mut(world_id) {
+            for _ in 0..count {
+                context.step();
+            }
         }
     }
 
+    #[func]
+    /// Creates a new empty world, with its own buffer, tick counter and snapshots, and returns its id.
+    /// Nodes are added to it by setting their world_id before they enter the tree
+    pub fn create_world(&mut self) -> i64 {
+        self.worlds.create()
+    }
+
+    #[func]
+    /// Destroys the world and everything in it. The default world (0) can't be destroyed.
+    /// Returns { ok } on success or { ok, error, message } if the world does not exist
+    pub fn destroy_world(&mut self, world_id: i64) -> Dictionary {
+        world_result(self.worlds.destroy(world_id), "destroy world")
+    }
+
+    #[func]
+    /// Returns the ids of every existing world, including the default world (0)
+    pub fn get_world_ids(&self) -> PackedInt64Array {
+        PackedInt64Array::from(self.worlds.ids().as_slice())
+    }
+
     #[func]
     /// Returns a past world state as a snapshot. The timestep_id must still be in the world buffer.
     pub fn get_snapshot(&mut self, timestep_id: i64) -> PackedByteArray {
-        match self.world.get_snapshot(Some(timestep_id)) {
+        self.get_snapshot_world(timestep_id, DEFAULT_WORLD)
+    }
+
+    #[func]
+    /// Same as get_snapshot, on the world with the given id
+    pub fn get_snapshot_world(&mut self, timestep_id: i64, world_id: i64) -> PackedByteArray {
+        match self
+            .context_mut(world_id)
+            .and_then(|context| context.world.get_snapshot(Some(timestep_id)))
+        {
             Some(snapshot) => PackedByteArray::from(snapshot.as_slice()),
             None => PackedByteArray::from(&[]),
         }
@@ -53,58 +136,800 @@ impl GR3D {
     #[func]
     /// Returns the current world state as a snapshot
     pub fn save_snapshot(&mut self) -> PackedByteArray {
-        match self.world.get_snapshot(None) {
+        self.save_snapshot_world(DEFAULT_WORLD)
+    }
+
+    #[func]
+    /// Same as save_snapshot, on the world with the given id
+    pub fn save_snapshot_world(&mut self, world_id: i64) -> PackedByteArray {
+        match self
+            .context_mut(world_id)
+            .and_then(|context| context.world.get_snapshot(None))
+        {
             Some(snapshot) => PackedByteArray::from(snapshot.as_slice()),
             None => PackedByteArray::from(&[]),
         }
     }
 
     #[func]
-    /// Overwrite the current state of the simulation to match the given snapshot
-    pub fn restore_snapshot(&mut self, snapshot: PackedByteArray) {
-        restore_snapshot(&mut self.world, snapshot.to_vec());
+    /// Overwrite the current state of the simulation to match the given snapshot.
+    /// Returns { ok, tick } on success or { ok, error, message } if the snapshot could not be unpacked
+    pub fn restore_snapshot(&mut self, snapshot: PackedByteArray) -> Dictionary {
+        self.restore_snapshot_world(snapshot, DEFAULT_WORLD)
+    }
+
+    #[func]
+    /// Same as restore_snapshot, on the world with the given id
+    pub fn restore_snapshot_world(
+        &mut self,
+        snapshot: PackedByteArray,
+        world_id: i64,
+    ) -> Dictionary {
+        let context = match self.worlds.find_mut(world_id) {
+            Ok(context) => context,
+            Err(e) => return world_result(Err(e), "restore snapshot"),
+        };
+
+        let mut dict = Dictionary::new();
+        match restore_snapshot(&mut context.world, snapshot.to_vec()) {
+            Ok(tick) => {
+                context.resync_events();
+                dict.set("ok", true);
+                dict.set("tick", tick as i64);
+            }
+            Err(e) => {
+                log::error!("Failed to restore snapshot: {}", e);
+                dict.set("ok", false);
+                dict.set("error", e.code());
+                dict.set("message", e.to_string());
+            }
+        }
+        dict
+    }
+
+    #[func]
+    /// Returns the current world state as a RapierSnapshot resource, which can be saved to a .rapiersnap file
+    pub fn save_snapshot_resource(&mut self) -> Option<Gd<RapierSnapshot>> {
+        self.save_snapshot_resource_world(DEFAULT_WORLD)
+    }
+
+    #[func]
+    /// Same as save_snapshot_resource, on the world with the given id
+    pub fn save_snapshot_resource_world(&mut self, world_id: i64) -> Option<Gd<RapierSnapshot>> {
+        let world = &mut self.context_mut(world_id)?.world;
+        let snapshot = world.get_snapshot(None)?;
+        let mut resource = RapierSnapshot::new_gd();
+        {
+            let mut resource = resource.bind_mut();
+            resource.snapshot = PackedByteArray::from(snapshot.as_slice());
+            resource.tick = world.state.timestep_id as i64;
+            resource.time = world.state.time as f64;
+            resource.body_count = world.physics.bodies.len() as i64;
+            resource.scene_path = current_scene_path();
+        }
+        Some(resource)
+    }
+
+    #[func]
+    /// Overwrite the current state of the simulation to match the given RapierSnapshot resource.
+    /// Returns the same result as restore_snapshot
+    pub fn restore_snapshot_resource(&mut self, resource: Gd<RapierSnapshot>) -> Dictionary {
+        self.restore_snapshot_resource_world(resource, DEFAULT_WORLD)
+    }
+
+    #[func]
+    /// Same as restore_snapshot_resource, on the world with the given id
+    pub fn restore_snapshot_resource_world(
+        &mut self,
+        resource: Gd<RapierSnapshot>,
+        world_id: i64,
+    ) -> Dictionary {
+        let snapshot = resource.bind().snapshot.clone();
+        self.restore_snapshot_world(snapshot, world_id)
+    }
+
+    #[func]
+    /// Whether snapshots are LZ4 compressed. Applies to snapshots taken after the change, including buffered ones
+    pub fn set_snapshot_compression(&mut self, enabled: bool) {
+        self.set_snapshot_compression_world(enabled, DEFAULT_WORLD)
+    }
+
+    #[func]
+    /// Same as set_snapshot_compression, on the world with the given id
+    pub fn set_snapshot_compression_world(&mut self, enabled: bool, world_id: i64) {
+        if let Some(context) = self.context_mut(world_id) {
+            context.world.compress_snapshots = enabled;
+        }
+    }
+
+    #[func]
+    pub fn get_snapshot_compression(&self) -> bool {
+        self.get_snapshot_compression_world(DEFAULT_WORLD)
+    }
+
+    #[func]
+    // Same as get_snapshot_compression, on the world with the given id
+    pub fn get_snapshot_compression_world(&self, world_id: i64) -> bool {
+        self.context(world_id)
+            .is_some_and(|context| context.world.compress_snapshots)
+    }
+
+    #[func]
+    /// Overwrite a previous state of the simulation to match the given snapshot,
+    /// and then roll-forward the simulation to get back to the current timestep.
+    /// Returns { ok, steps } on success or { ok, error, message } if the snapshot's tick is not in the buffer
+    pub fn apply_correction(&mut self, snapshot: PackedByteArray) -> Dictionary {
+        self.apply_correction_world(snapshot, DEFAULT_WORLD)
+    }
+
+    #[func]
+    /// Same as apply_correction, on the world with the given id
+    pub fn apply_correction_world(
+        &mut self,
+        snapshot: PackedByteArray,
+        world_id: i64,
+    ) -> Dictionary {
+        let context = match self.worlds.find_mut(world_id) {
+            Ok(context) => context,
+            Err(e) => return world_result(Err(e), "apply correction"),
+        };
+        let result = context.world.apply_correction(snapshot.to_vec());
+
+        // Signals are not emitted again for resimulated steps, but the overlaps follow the corrected state
+        context.resync_events();
+
+        let mut dict = Dictionary::new();
+        match result {
+            Ok(steps) => {
+                dict.set("ok", true);
+                dict.set("steps", steps as i64);
+            }
+            Err(e) => {
+                log::error!("Failed to apply correction: {}", e);
+                dict.set("ok", false);
+                dict.set("error", e.code());
+                dict.set("message", e.to_string());
+            }
+        }
+        dict
+    }
+
+    #[func]
+    /// Returns the changes between two buffered ticks, for peers that already have the base tick.
+    /// Only the bytes that changed are encoded, compressed. Returns an empty array if either tick is not in the world buffer
+    pub fn get_delta_snapshot(&self, base_tick: i64, tick: i64) -> PackedByteArray {
+        self.get_delta_snapshot_world(base_tick, tick, DEFAULT_WORLD)
+    }
+
+    #[func]
+    /// Same as get_delta_snapshot, on the world with the given id
+    pub fn get_delta_snapshot_world(
+        &self,
+        base_tick: i64,
+        tick: i64,
+        world_id: i64,
+    ) -> PackedByteArray {
+        let Some(context) = self.context(world_id) else {
+            return PackedByteArray::from(&[]);
+        };
+        match context
+            .world
+            .get_delta_snapshot(base_tick as usize, tick as usize)
+        {
+            Ok(delta) => PackedByteArray::from(delta.as_slice()),
+            Err(e) => {
+                log::error!("Failed to create delta snapshot: {}", e);
+                PackedByteArray::from(&[])
+            }
+        }
     }
 
-    // #[func]
-    // /// Overwrite a previous state of the simulation to match the given snapshot,
-    // /// and then roll-forward the simulation to get back to the current timestep
-    // pub fn apply_correction(&mut self, snapshot: PackedByteArray) {
-    //     apply_correction(&mut self.world, snapshot.to_vec());
-    // }
+    #[func]
+    /// Rebuilds a snapshot from the buffered base tick and a delta from get_delta_snapshot,
+    /// then overwrites the current state of the simulation with it.
+    /// Returns { ok, tick } on success or { ok, error, message } on failure
+    pub fn apply_delta_snapshot(&mut self, base_tick: i64, delta: PackedByteArray) -> Dictionary {
+        self.apply_delta_snapshot_world(base_tick, delta, DEFAULT_WORLD)
+    }
+
+    #[func]
+    /// Same as apply_delta_snapshot, on the world with the given id
+    pub fn apply_delta_snapshot_world(
+        &mut self,
+        base_tick: i64,
+        delta: PackedByteArray,
+        world_id: i64,
+    ) -> Dictionary {
+        let context = match self.worlds.find_mut(world_id) {
+            Ok(context) => context,
+            Err(e) => return world_result(Err(e), "apply delta snapshot"),
+        };
+        let result = context
+            .world
+            .apply_delta_snapshot(base_tick as usize, delta.as_slice());
+
+        let mut dict = Dictionary::new();
+        match result {
+            Ok(tick) => {
+                context.resync_events();
+                dict.set("ok", true);
+                dict.set("tick", tick as i64);
+            }
+            Err(e) => {
+                log::error!("Failed to apply delta snapshot: {}", e);
+                dict.set("ok", false);
+                dict.set("error", e.code());
+                dict.set("message", e.to_string());
+            }
+        }
+        dict
+    }
+
+    #[func]
+    /// Returns { ok, hash } with the 64-bit checksum of a past world state (body positions, velocities, sleep state and joints).
+    /// Peers can compare checksums to detect desyncs.
+    /// Returns { ok, error, message } if the timestep_id has no state in the world buffer (e.g. it is too old or was marked stale)
+    pub fn get_state_hash(&self, timestep_id: i64) -> Dictionary {
+        self.get_state_hash_world(timestep_id, DEFAULT_WORLD)
+    }
+
+    #[func]
+    /// Same as get_state_hash, on the world with the given id
+    pub fn get_state_hash_world(&self, timestep_id: i64, world_id: i64) -> Dictionary {
+        let world = match self.worlds.find(world_id) {
+            Ok(context) => &context.world,
+            Err(e) => return world_result(Err(e), "get state hash"),
+        };
+
+        let mut dict = Dictionary::new();
+        match world.get_state_hash(Some(timestep_id as usize)) {
+            Some(hash) => {
+                dict.set("ok", true);
+                dict.set("hash", hash as i64);
+            }
+            None => {
+                dict.set("ok", false);
+                dict.set("error", "NOT_BUFFERED");
+                dict.set(
+                    "message",
+                    format!("Timestep {} has no state in the world buffer", timestep_id),
+                );
+            }
+        }
+        dict
+    }
+
+    #[func]
+    /// Returns a 64-bit checksum of the current world state
+    pub fn get_state_hash_current(&self) -> i64 {
+        self.get_state_hash_current_world(DEFAULT_WORLD)
+    }
+
+    #[func]
+    /// Same as get_state_hash_current, on the world with the given id
+    pub fn get_state_hash_current_world(&self, world_id: i64) -> i64 {
+        self.context(world_id)
+            .and_then(|context| context.world.get_state_hash(None))
+            .unwrap_or(0) as i64
+    }
+
+    #[func]
+    /// Compares two snapshots and returns { ok, identical, only_in_a, only_in_b, bodies }.
+    /// only_in_a / only_in_b list the cuids of bodies, colliders and joints present in only one snapshot.
+    /// bodies lists every body whose translation, rotation, linvel, angvel or sleeping state differs,
+    /// as { cuid, <field>: { a, b, magnitude } }. Returns { ok, error, message } if a snapshot is invalid
+    pub fn diff_snapshots(&self, a: PackedByteArray, b: PackedByteArray) -> Dictionary {
+        match (unpack_snapshot(a.to_vec()), unpack_snapshot(b.to_vec())) {
+            (Ok(a), Ok(b)) => diff_to_dict(&diff_snapshots(&a, &b)),
+            (Err(e), _) | (_, Err(e)) => {
+                let mut dict = Dictionary::new();
+                dict.set("ok", false);
+                dict.set("error", e.code());
+                dict.set("message", e.to_string());
+                dict
+            }
+        }
+    }
+
+    #[func]
+    /// Changes a world parameter, named like its physics/rapier_3d/* project setting.
+    /// Parameters are queued and applied at the start of the next step, so they are replayed when resimulating.
+    /// The buffer length does not affect the simulation and is applied immediately.
+    /// Returns { ok } on success or { ok, error, message } if the name or value is invalid
+    pub fn set_world_parameter(&mut self, name: GString, value: Variant) -> Dictionary {
+        self.set_world_parameter_world(name, value, DEFAULT_WORLD)
+    }
+
+    #[func]
+    /// Same as set_world_parameter, on the world with the given id
+    pub fn set_world_parameter_world(
+        &mut self,
+        name: GString,
+        value: Variant,
+        world_id: i64,
+    ) -> Dictionary {
+        let world = match self.worlds.find_mut(world_id) {
+            Ok(context) => &mut context.world,
+            Err(e) => return world_result(Err(e), "set world parameter"),
+        };
+
+        let name = name.to_string();
+        let result = match name.as_str() {
+            BUFFER_LENGTH => buffer_length(&value).map(|length| world.buffer.set_max_len(length)),
+            _ => world_parameter(&name, &value)
+                .map(|parameter| world.queue_command(Command::SetParameter(parameter))),
+        };
+
+        let mut dict = Dictionary::new();
+        match result {
+            Ok(()) => {
+                dict.set("ok", true);
+            }
+            Err(e) => {
+                log::error!("Failed to set world parameter: {}", e);
+                dict.set("ok", false);
+                dict.set("error", e.code());
+                dict.set("message", e.to_string());
+            }
+        }
+        dict
+    }
+
+    #[func]
+    /// Returns the current value of every world parameter and the buffer length, keyed by name
+    pub fn get_world_parameters(&self) -> Dictionary {
+        self.get_world_parameters_world(DEFAULT_WORLD)
+    }
+
+    #[func]
+    /// Same as get_world_parameters, on the world with the given id
+    pub fn get_world_parameters_world(&self, world_id: i64) -> Dictionary {
+        match self.worlds.find(world_id) {
+            Ok(context) => world_parameters_to_dict(&context.world),
+            Err(e) => world_result(Err(e), "get world parameters"),
+        }
+    }
+
+    #[func]
+    /// Changes the gravity from the next step on, see set_world_parameter
+    pub fn set_gravity(&mut self, gravity: Vector3) {
+        self.set_gravity_world(gravity, DEFAULT_WORLD)
+    }
+
+    #[func]
+    /// Same as set_gravity, on the world with the given id
+    pub fn set_gravity_world(&mut self, gravity: Vector3, world_id: i64) {
+        self.set_world_parameter_world("gravity_vector".into(), gravity.to_variant(), world_id);
+    }
+
+    #[func]
+    /// Returns the current world gravity
+    pub fn get_gravity(&self) -> Vector3 {
+        self.get_gravity_world(DEFAULT_WORLD)
+    }
+
+    #[func]
+    /// Same as get_gravity, on the world with the given id
+    pub fn get_gravity_world(&self, world_id: i64) -> Vector3 {
+        self.context(world_id)
+            .map(|context| vector_to_godot(context.world.physics.gravity))
+            .unwrap_or_default()
+    }
+
+    #[func]
+    /// Changes the length of a step from the next step on, see set_world_parameter
+    pub fn set_ticks_per_second(&mut self, ticks: i64) {
+        self.set_ticks_per_second_world(ticks, DEFAULT_WORLD)
+    }
+
+    #[func]
+    /// Same as set_ticks_per_second, on the world with the given id
+    pub fn set_ticks_per_second_world(&mut self, ticks: i64, world_id: i64) {
+        self.set_world_parameter_world("ticks_per_second".into(), ticks.to_variant(), world_id);
+    }
+
+    #[func]
+    /// Changes the amount of solver iterations from the next step on, see set_world_parameter
+    pub fn set_solver_iterations(&mut self, iterations: i64) {
+        self.set_solver_iterations_world(iterations, DEFAULT_WORLD)
+    }
+
+    #[func]
+    /// Same as set_solver_iterations, on the world with the given id
+    pub fn set_solver_iterations_world(&mut self, iterations: i64, world_id: i64) {
+        self.set_world_parameter_world(
+            "solver_iterations".into(),
+            iterations.to_variant(),
+            world_id,
+        );
+    }
+
+    #[func]
+    /// Changes the maximum amount of CCD substeps from the next step on, see set_world_parameter
+    pub fn set_ccd_substeps(&mut self, substeps: i64) {
+        self.set_ccd_substeps_world(substeps, DEFAULT_WORLD)
+    }
+
+    #[func]
+    /// Same as set_ccd_substeps, on the world with the given id
+    pub fn set_ccd_substeps_world(&mut self, substeps: i64, world_id: i64) {
+        self.set_world_parameter_world("ccd_substeps".into(), substeps.to_variant(), world_id);
+    }
+
+    #[func]
+    /// Changes the amount of ticks kept in the world buffer, which limits how far back corrections can go
+    pub fn set_buffer_length(&mut self, length: i64) {
+        self.set_buffer_length_world(length, DEFAULT_WORLD)
+    }
+
+    #[func]
+    /// Same as set_buffer_length, on the world with the given id
+    pub fn set_buffer_length_world(&mut self, length: i64, world_id: i64) {
+        self.set_world_parameter_world(BUFFER_LENGTH.into(), length.to_variant(), world_id);
+    }
 
     #[func]
     /// Get the current count of all objects registered in the simulation
     pub fn get_counts(&self) -> Dictionary {
-        let counts = self.world.get_counts();
+        self.get_counts_world(DEFAULT_WORLD)
+    }
+
+    #[func]
+    /// Same as get_counts, on the world with the given id
+    pub fn get_counts_world(&self, world_id: i64) -> Dictionary {
         let mut dict = Dictionary::new();
-        dict.set("bodies", counts.0 as i64);
-        dict.set("colliders", counts.1 as i64);
-        dict.set("joints", counts.2 as i64);
-        dict.set("multibodies", counts.3 as i64);
+        if let Some(context) = self.context(world_id) {
+            let counts = context.world.get_counts();
+            dict.set("bodies", counts.0 as i64);
+            dict.set("colliders", counts.1 as i64);
+            dict.set("joints", counts.2 as i64);
+            dict.set("multibodies", counts.3 as i64);
+        }
         dict
     }
 
     #[func]
     /// Returns the current tick
     pub fn get_tick(&self) -> i64 {
-        self.world.state.timestep_id as i64
+        self.get_tick_world(DEFAULT_WORLD)
+    }
+
+    #[func]
+    /// Same as get_tick, on the world with the given id
+    pub fn get_tick_world(&self, world_id: i64) -> i64 {
+        self.context(world_id)
+            .map_or(0, |context| context.world.state.timestep_id as i64)
     }
 
     #[func]
     /// Returns the current timestamp
     pub fn get_time(&self) -> f64 {
-        self.world.state.time as f64
+        self.get_time_world(DEFAULT_WORLD)
+    }
+
+    #[func]
+    /// Same as get_time, on the world with the given id
+    pub fn get_time_world(&self, world_id: i64) -> f64 {
+        self.context(world_id)
+            .map_or(0.0, |context| context.world.state.time as f64)
+    }
+
+    #[func]
+    /// Casts a ray and returns the first hit (node, collider, point, toi), or an empty dictionary
+    pub fn cast_ray(
+        &self,
+        origin: Vector3,
+        direction: Vector3,
+        max_toi: f32,
+        solid: bool,
+        filter: Option<Gd<RapierQueryFilter>>,
+    ) -> Dictionary {
+        self.cast_ray_world(origin, direction, max_toi, solid, filter, DEFAULT_WORLD)
+    }
+
+    #[func]
+    /// Same as cast_ray, on the world with the given id
+    pub fn cast_ray_world(
+        &self,
+        origin: Vector3,
+        direction: Vector3,
+        max_toi: f32,
+        solid: bool,
+        filter: Option<Gd<RapierQueryFilter>>,
+        world_id: i64,
+    ) -> Dictionary {
+        let Some(context) = self.context(world_id) else {
+            return Dictionary::new();
+        };
+        query::cast_ray(
+            &context.world.physics,
+            &context.registry,
+            origin,
+            direction,
+            max_toi,
+            solid,
+            filter,
+        )
+    }
+
+    #[func]
+    /// Casts a ray and returns the first hit (node, collider, point, normal, toi), or an empty dictionary
+    pub fn cast_ray_and_get_normal(
+        &self,
+        origin: Vector3,
+        direction: Vector3,
+        max_toi: f32,
+        solid: bool,
+        filter: Option<Gd<RapierQueryFilter>>,
+    ) -> Dictionary {
+        self.cast_ray_and_get_normal_world(origin, direction, max_toi, solid, filter, DEFAULT_WORLD)
+    }
+
+    #[func]
+    /// Same as cast_ray_and_get_normal, on the world with the given id
+    pub fn cast_ray_and_get_normal_world(
+        &self,
+        origin: Vector3,
+        direction: Vector3,
+        max_toi: f32,
+        solid: bool,
+        filter: Option<Gd<RapierQueryFilter>>,
+        world_id: i64,
+    ) -> Dictionary {
+        let Some(context) = self.context(world_id) else {
+            return Dictionary::new();
+        };
+        query::cast_ray_and_get_normal(
+            &context.world.physics,
+            &context.registry,
+            origin,
+            direction,
+            max_toi,
+            solid,
+            filter,
+        )
+    }
+
+    #[func]
+    /// Casts a ray and returns every hit (node, collider, point, normal, toi)
+    pub fn intersections_with_ray(
+        &self,
+        origin: Vector3,
+        direction: Vector3,
+        max_toi: f32,
+        solid: bool,
+        filter: Option<Gd<RapierQueryFilter>>,
+    ) -> Array<Dictionary> {
+        self.intersections_with_ray_world(origin, direction, max_toi, solid, filter, DEFAULT_WORLD)
+    }
+
+    #[func]
+    /// Same as intersections_with_ray, on the world with the given id
+    pub fn intersections_with_ray_world(
+        &self,
+        origin: Vector3,
+        direction: Vector3,
+        max_toi: f32,
+        solid: bool,
+        filter: Option<Gd<RapierQueryFilter>>,
+        world_id: i64,
+    ) -> Array<Dictionary> {
+        let Some(context) = self.context(world_id) else {
+            return Array::new();
+        };
+        query::intersections_with_ray(
+            &context.world.physics,
+            &context.registry,
+            origin,
+            direction,
+            max_toi,
+            solid,
+            filter,
+        )
+    }
+
+    #[func]
+    /// Moves a shape along velocity and returns the first hit (node, collider, point, normal, toi), or an empty dictionary
+    #[allow(clippy::too_many_arguments)]
+    pub fn cast_shape(
+        &self,
+        shape: Gd<Shape3D>,
+        transform: Transform3D,
+        velocity: Vector3,
+        max_toi: f32,
+        stop_at_penetration: bool,
+        filter: Option<Gd<RapierQueryFilter>>,
+    ) -> Dictionary {
+        self.cast_shape_world(
+            shape,
+            transform,
+            velocity,
+            max_toi,
+            stop_at_penetration,
+            filter,
+            DEFAULT_WORLD,
+        )
+    }
+
+    #[func]
+    /// Same as cast_shape, on the world with the given id
+    #[allow(clippy::too_many_arguments)]
+    pub fn cast_shape_world(
+        &self,
+        shape: Gd<Shape3D>,
+        transform: Transform3D,
+        velocity: Vector3,
+        max_toi: f32,
+        stop_at_penetration: bool,
+        filter: Option<Gd<RapierQueryFilter>>,
+        world_id: i64,
+    ) -> Dictionary {
+        let Some(context) = self.context(world_id) else {
+            return Dictionary::new();
+        };
+        query::cast_shape(
+            &context.world.physics,
+            &context.registry,
+            shape,
+            transform,
+            velocity,
+            max_toi,
+            stop_at_penetration,
+            filter,
+        )
+    }
+
+    #[func]
+    /// Returns every collider (node, collider) containing the point
+    pub fn intersections_with_point(
+        &self,
+        point: Vector3,
+        filter: Option<Gd<RapierQueryFilter>>,
+    ) -> Array<Dictionary> {
+        self.intersections_with_point_world(point, filter, DEFAULT_WORLD)
+    }
+
+    #[func]
+    /// Same as intersections_with_point, on the world with the given id
+    pub fn intersections_with_point_world(
+        &self,
+        point: Vector3,
+        filter: Option<Gd<RapierQueryFilter>>,
+        world_id: i64,
+    ) -> Array<Dictionary> {
+        let Some(context) = self.context(world_id) else {
+            return Array::new();
+        };
+        query::intersections_with_point(&context.world.physics, &context.registry, point, filter)
+    }
+
+    #[func]
+    /// Returns every collider (node, collider) intersecting the shape
+    pub fn intersections_with_shape(
+        &self,
+        shape: Gd<Shape3D>,
+        transform: Transform3D,
+        filter: Option<Gd<RapierQueryFilter>>,
+    ) -> Array<Dictionary> {
+        self.intersections_with_shape_world(shape, transform, filter, DEFAULT_WORLD)
+    }
+
+    #[func]
+    /// Same as intersections_with_shape, on the world with the given id
+    pub fn intersections_with_shape_world(
+        &self,
+        shape: Gd<Shape3D>,
+        transform: Transform3D,
+        filter: Option<Gd<RapierQueryFilter>>,
+        world_id: i64,
+    ) -> Array<Dictionary> {
+        let Some(context) = self.context(world_id) else {
+            return Array::new();
+        };
+        query::intersections_with_shape(
+            &context.world.physics,
+            &context.registry,
+            shape,
+            transform,
+            filter,
+        )
+    }
+
+    #[func]
+    /// Returns the closest point on any collider (node, collider, point, is_inside), or an empty dictionary
+    pub fn project_point(
+        &self,
+        point: Vector3,
+        solid: bool,
+        filter: Option<Gd<RapierQueryFilter>>,
+    ) -> Dictionary {
+        self.project_point_world(point, solid, filter, DEFAULT_WORLD)
+    }
+
+    #[func]
+    /// Same as project_point, on the world with the given id
+    pub fn project_point_world(
+        &self,
+        point: Vector3,
+        solid: bool,
+        filter: Option<Gd<RapierQueryFilter>>,
+        world_id: i64,
+    ) -> Dictionary {
+        let Some(context) = self.context(world_id) else {
+            return Dictionary::new();
+        };
+        query::project_point(
+            &context.world.physics,
+            &context.registry,
+            point,
+            solid,
+            filter,
+        )
     }
 
     #[func]
     pub fn _ingest_action(&mut self, node: Gd<Node3D>, operation: Operation, data: Dictionary) {
-        ingest_action(node, operation, data, &mut self.world);
+        let Some((cuid, world_id)) = extract_identity(&node) else {
+            return;
+        };
+        // Removals go to the world the node was added to, even if it re-entered the tree in another world since
+        let world_id = match operation {
+            Operation::RemoveNode => self.worlds.world_of(&cuid.to_string()).unwrap_or(world_id),
+            _ => world_id,
+        };
+        let Some(context) = self.worlds.get_mut(world_id) else {
+            log::error!(
+                "'{}' belongs to world {}, which does not exist",
+                node.get_name(),
+                world_id
+            );
+            return;
+        };
+
+        ingest_action(
+            cuid.clone(),
+            node.clone(),
+            operation.clone(),
+            data,
+            &mut context.world,
+        );
+        match operation {
+            Operation::AddNode => context.registry.register(&cuid, &node),
+            Operation::RemoveNode => context.registry.unregister(&cuid),
+            _ => {}
+        }
+    }
+
+    #[func]
+    // Discard the actions queued for the given timestep, e.g. to replay it with different inputs
+    pub fn _clear_actions(&mut self, timestep_id: i64) {
+        self._clear_actions_world(timestep_id, DEFAULT_WORLD)
+    }
+
+    #[func]
+    // Same as _clear_actions, on the world with the given id
+    pub fn _clear_actions_world(&mut self, timestep_id: i64, world_id: i64) {
+        if let Some(context) = self.context_mut(world_id) {
+            context.world.buffer.clear_actions(timestep_id as usize);
+        }
     }
 
     #[func]
     // Draw lines representing the current state of the world according to Rapier
     pub fn _get_debug_lines(&mut self) -> Array<Array<Variant>> {
-        self.debugger.render(&self.world)
+        self._get_debug_lines_world(DEFAULT_WORLD)
+    }
+
+    #[func]
+    // Same as _get_debug_lines, on the world with the given id
+    pub fn _get_debug_lines_world(&mut self, world_id: i64) -> Array<Array<Variant>> {
+        match self.worlds.find(world_id) {
+            Ok(context) => self.debugger.render(&context.world),
+            Err(e) => {
+                log::error!("Failed to draw debug lines: {}", e);
+                Array::new()
+            }
+        }
     }
 
     #[func]
@@ -123,14 +948,20 @@ pub fn register() {
     init_logger();
     set_log_level(crate::utils::LogLevel::Debug);
 
-    Engine::singleton().register_singleton(NAME, &GR3D::new_alloc());
+    let mut singleton = GR3D::new_alloc();
+    singleton.bind_mut().snapshot_formats = Some(SnapshotFormats::register());
+    Engine::singleton().register_singleton(NAME, &singleton);
 }
 
 pub fn unregister() {
     let mut engine = Engine::singleton();
     if let Some(my_singleton) = engine.get_singleton(NAME) {
         engine.unregister_singleton(NAME);
-        my_singleton.free();
+        let mut singleton = my_singleton.cast::<GR3D>();
+        if let Some(formats) = singleton.bind_mut().snapshot_formats.take() {
+            formats.unregister();
+        }
+        singleton.free();
     } else {
         log::error!("Failed to get {} singleton", NAME);
     }
@@ -153,6 +984,32 @@ pub fn get_singleton() -> Option<Gd<GR3D>> {
     }
 }
 
+fn world_result(result: Result<(), WorldError>, operation: &str) -> Dictionary {
+    let mut dict = Dictionary::new();
+    match result {
+        Ok(()) => {
+            dict.set("ok", true);
+        }
+        Err(e) => {
+            log::error!("Failed to {}: {}", operation, e);
+            dict.set("ok", false);
+            dict.set("error", e.code());
+            dict.set("message", e.to_string());
+        }
+    }
+    dict
+}
+
+/// Path of the scene file currently running, or an empty string
+fn current_scene_path() -> GString {
+    Engine::singleton()
+        .get_main_loop()
+        .and_then(|main_loop| main_loop.try_cast::<SceneTree>().ok())
+        .and_then(|tree| tree.get_current_scene())
+        .map(|scene| scene.get_scene_file_path())
+        .unwrap_or_default()
+}
+
 pub fn get_tree(node: &impl IRapierObject) -> Option<Gd<SceneTree>> {
     node.base().get_tree()
 }
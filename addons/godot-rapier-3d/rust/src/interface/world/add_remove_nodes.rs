@@ -1,57 +1,87 @@
-use crate::nodes::{IRapierObject, Identifiable};
+use crate::nodes::{Collidable, ColliderSettings, Identifiable, Jointable};
 use crate::nodes::{
-    RapierArea3D, RapierCollisionShape3D, RapierKinematicCharacter3D, RapierPIDCharacter3D,
-    RapierRigidBody3D, RapierStaticBody3D,
+    RapierArea3D, RapierCollisionShape3D, RapierFixedJoint3D, RapierKinematicCharacter3D,
+    RapierPIDCharacter3D, RapierPrismaticJoint3D, RapierRevoluteJoint3D, RapierRigidBody3D,
+    RapierRopeJoint3D, RapierSphericalJoint3D, RapierSpringJoint3D, RapierStaticBody3D,
 };
-use crate::utils::{transform_to_isometry, vector_to_point, vector_to_rapier};
-use crate::world::state::PhysicsState;
-use godot::classes::{
-    BoxShape3D, CapsuleShape3D, ConcavePolygonShape3D, CylinderShape3D, SphereShape3D,
+use crate::utils::{
+    is_expensive_shape, shape_to_convex_decomposition, shape_to_rapier, transform_to_isometry,
+    SUPPORTED_SHAPES,
 };
+use crate::world::descriptor::{BodyDescriptor, BodyKind, ColliderDescriptor};
+use crate::world::objects::{insert_body, insert_collider};
+use crate::world::{lookup::HandleKind, state::PhysicsState};
 use godot::prelude::*;
 use rapier3d::prelude::*;
 
-const SUPPORTED_SHAPES: &str = "Only primitives and ConcavePolygonShape3D are supported";
-
 pub fn add_node_to_world(node: Gd<Node3D>, physics: &mut PhysicsState) {
     let transform = node.get_global_transform();
     let class = node.get_class().to_string();
     match class.as_str() {
         "RapierArea3D" => {
             let casted = node.cast::<RapierArea3D>();
-            insert_area_children(&casted, physics);
+            let settings = casted.bind().collider_settings();
+            insert_area_children(&casted, settings, physics);
         }
         "RapierKinematicCharacter3D" => {
-            let rb = RigidBodyBuilder::kinematic_position_based()
-                .position(transform_to_isometry(transform))
-                .ccd_enabled(true);
-
             let mut casted = node.cast::<RapierKinematicCharacter3D>();
-            insert_rb_with_children(rb, &mut casted, physics);
+            let mut body = body_descriptor(&casted, BodyKind::KinematicPositionBased, transform);
+            body.ccd = true;
+            insert_rb_with_children(body, &mut casted, physics);
         }
         "RapierPIDCharacter3D" => {
-            let rb = RigidBodyBuilder::dynamic().position(transform_to_isometry(transform));
             let mut casted = node.cast::<RapierPIDCharacter3D>();
-            insert_rb_with_children(rb, &mut casted, physics);
+            let mut body = body_descriptor(&casted, BodyKind::Dynamic, transform);
+            body.gravity_scale = casted.bind().gravity_scale;
+            insert_rb_with_children(body, &mut casted, physics);
         }
         "RapierRigidBody3D" => {
-            let rb = RigidBodyBuilder::dynamic().position(transform_to_isometry(transform));
             let mut casted = node.cast::<RapierRigidBody3D>();
-            insert_rb_with_children(rb, &mut casted, physics);
+            let mut body = body_descriptor(&casted, BodyKind::Dynamic, transform);
+            body.gravity_scale = casted.bind().gravity_scale;
+            insert_rb_with_children(body, &mut casted, physics);
         }
         "RapierStaticBody3D" => {
-            let rb = RigidBodyBuilder::fixed().position(transform_to_isometry(transform));
             let mut casted = node.cast::<RapierStaticBody3D>();
-            insert_rb_with_children(rb, &mut casted, physics);
+            let body = body_descriptor(&casted, BodyKind::Fixed, transform);
+            insert_rb_with_children(body, &mut casted, physics);
         }
         "RapierCollisionShape3D" => (), // Ignore colliders - they are inserted at the same time as the parent rigid body
+        "RapierFixedJoint3D" => {
+            let mut casted = node.cast::<RapierFixedJoint3D>();
+            insert_joint(&mut casted, physics);
+        }
+        "RapierRevoluteJoint3D" => {
+            let mut casted = node.cast::<RapierRevoluteJoint3D>();
+            insert_joint(&mut casted, physics);
+        }
+        "RapierPrismaticJoint3D" => {
+            let mut casted = node.cast::<RapierPrismaticJoint3D>();
+            insert_joint(&mut casted, physics);
+        }
+        "RapierSphericalJoint3D" => {
+            let mut casted = node.cast::<RapierSphericalJoint3D>();
+            insert_joint(&mut casted, physics);
+        }
+        "RapierRopeJoint3D" => {
+            let mut casted = node.cast::<RapierRopeJoint3D>();
+            insert_joint(&mut casted, physics);
+        }
+        "RapierSpringJoint3D" => {
+            let mut casted = node.cast::<RapierSpringJoint3D>();
+            insert_joint(&mut casted, physics);
+        }
         _ => {
             log::error!("Unknown object type: {}", &class);
         }
     }
 }
 
-fn insert_area_children(node: &Node3D, physics: &mut PhysicsState) {
+fn insert_area_children(
+    node: &Gd<RapierArea3D>,
+    settings: ColliderSettings,
+    physics: &mut PhysicsState,
+) {
     let children = node
         .find_children_ex("*")
         .type_("RapierCollisionShape3D")
@@ -67,20 +97,35 @@ fn insert_area_children(node: &Node3D, physics: &mut PhysicsState) {
             );
         }
         _ => {
+            let world_id = node.bind().get_world_id();
             for child in children.iter_shared() {
                 let mut casted = child.cast::<RapierCollisionShape3D>();
-                insert_collider(&mut casted, None, physics, true);
+                warn_world_mismatch(&casted, world_id);
+                if let Some(mut collider) = collider_descriptor(&casted, settings, true) {
+                    collider.gravity = node.bind().gravity_override();
+                    let raw_handle = insert_collider(&collider, None, physics);
+                    casted.bind_mut().set_handle_raw(raw_handle);
+                }
             }
         }
     }
 }
 
+fn body_descriptor(
+    node: &Gd<impl Collidable>,
+    kind: BodyKind,
+    transform: Transform3D,
+) -> BodyDescriptor {
+    let cuid = node.bind().get_cuid().to_string();
+    BodyDescriptor::new(&cuid, kind, transform_to_isometry(transform))
+}
+
 fn insert_rb_with_children(
-    rb: impl Into<RigidBody>,
-    node: &mut Gd<impl IRapierObject>,
+    mut body: BodyDescriptor,
+    node: &mut Gd<impl Collidable>,
     physics: &mut PhysicsState,
 ) {
-    let bodies = &mut physics.bodies;
+    let settings = node.bind().collider_settings();
 
     let children = node
         .find_children_ex("*")
@@ -97,128 +142,128 @@ fn insert_rb_with_children(
             );
         }
         _ => {
-            let parent_handle = bodies.insert(rb);
-
+            let mut shapes = Vec::new();
+            let world_id = node.bind().get_world_id();
             for child in children.iter_shared() {
-                let mut casted = child.cast::<RapierCollisionShape3D>();
-                insert_collider(&mut casted, Some(parent_handle), physics, false);
+                let casted = child.cast::<RapierCollisionShape3D>();
+                warn_world_mismatch(&casted, world_id);
+                if let Some(collider) = collider_descriptor(&casted, settings, false) {
+                    body.colliders.push(collider);
+                    shapes.push(casted);
+                }
             }
 
-            let node_uid = node.bind().get_cuid();
-            let raw_handle = parent_handle.into_raw_parts();
-            physics.lookup_table.insert(node_uid, raw_handle);
-            node.bind_mut().set_handle_raw(raw_handle);
+            let inserted = insert_body(&body, physics);
+            for (mut shape, raw_handle) in shapes.into_iter().zip(inserted.colliders) {
+                shape.bind_mut().set_handle_raw(raw_handle);
+            }
+            node.bind_mut().set_handle_raw(inserted.body);
         }
     }
 }
 
-fn insert_collider(
-    node: &mut Gd<RapierCollisionShape3D>,
-    parent: Option<RigidBodyHandle>,
-    physics: &mut PhysicsState,
+/// Collision shapes are added to the world of their body or area, but their own actions go to their own world
+fn warn_world_mismatch(shape: &Gd<RapierCollisionShape3D>, world_id: i64) {
+    if shape.bind().get_world_id() != world_id {
+        log::warn!(
+            "'{}' must have the same world_id as its body or area ({})",
+            shape.get_name(),
+            world_id
+        );
+    }
+}
+
+/// Describes the collider of a collision shape, with the settings inherited from its body or area
+fn collider_descriptor(
+    node: &Gd<RapierCollisionShape3D>,
+    parent_settings: ColliderSettings,
     sensor: bool, // TODO - could be exposed to godot by reading from node directly in here
-) {
-    let lookup_table = &mut physics.lookup_table;
+) -> Option<ColliderDescriptor> {
+    let shape = shape_to_collider_shape(node)?;
+    let transform = node.get_transform(); // Get LOCAL transform to maintain relative position to parent
+    let node = node.bind();
 
-    if let Some(collider) = shape_to_collider(node) {
-        let is_exp = is_expensive(&collider);
+    Some(ColliderDescriptor {
+        cuid: node.get_cuid().to_string(),
+        shape,
+        position: transform_to_isometry(transform),
+        groups: node.resolve_collision_groups(parent_settings.groups),
+        material: node.resolve_material(parent_settings.material),
+        sensor,
+        contact_force_threshold: parent_settings.contact_force_threshold,
+        gravity: None,
+    })
+}
 
-        let built = collider.sensor(sensor).build();
-        let handle = match parent {
-            Some(parent) => {
-                &mut physics
-                    .colliders
-                    .insert_with_parent(built, parent, &mut physics.bodies)
-            }
-            None => &mut physics.colliders.insert(built),
-        };
+/// Connects the two bodies referenced by the joint node. Both bodies must already be in the world
+fn insert_joint(node: &mut Gd<impl Jointable>, physics: &mut PhysicsState) {
+    let (path_a, path_b) = node.bind().body_paths();
+    let body_a = find_joint_body(node, &path_a, physics);
+    let body_b = find_joint_body(node, &path_b, physics);
 
-        let raw_handle = handle.into_raw_parts();
-        let node_uid = node.bind().get_cuid();
-        lookup_table.insert(node_uid, raw_handle);
-        if !is_exp {
-            lookup_table.insert_snapshot_collider(raw_handle);
+    match (body_a, body_b) {
+        (Some(body_a), Some(body_b)) => {
+            let joint = node.bind().build_joint();
+            let handle = physics.impulse_joints.insert(body_a, body_b, joint, true);
+            let raw_handle = handle.into_raw_parts();
+            let node_uid = node.bind().get_cuid();
+            physics.lookup_table.insert(
+                &node_uid.to_string(),
+                HandleKind::ImpulseJoint,
+                raw_handle,
+            );
+            node.bind_mut().set_handle_raw(raw_handle);
+        }
+        _ => {
+            log::error!(
+                "'{}' must reference two Rapier bodies that have been added to the world",
+                node.bind().base().get_name()
+            );
         }
-        node.bind_mut().set_handle_raw(raw_handle);
     }
 }
 
-fn shape_to_collider(node: &Gd<RapierCollisionShape3D>) -> Option<ColliderBuilder> {
-    let shape = node.bind().get_shape();
-    let transform = node.get_transform(); // Get LOCAL transform to maintain relative position to parent
-    match shape {
-        Some(shape) => match shape.get_class().to_string().as_str() {
-            "SphereShape3D" => {
-                let casted = shape.cast::<SphereShape3D>();
-                Some(
-                    ColliderBuilder::ball(casted.get_radius())
-                        .position(transform_to_isometry(transform)),
-                )
-            }
-            "BoxShape3D" => {
-                let casted = shape.cast::<BoxShape3D>();
-                Some(
-                    ColliderBuilder::cuboid(
-                        casted.get_size().x / 2.0,
-                        casted.get_size().y / 2.0,
-                        casted.get_size().z / 2.0,
-                    )
-                    .position(transform_to_isometry(transform)),
-                )
-            }
-            "CapsuleShape3D" => {
-                let casted = shape.cast::<CapsuleShape3D>();
-                Some(
-                    ColliderBuilder::capsule_y(casted.get_height() / 4.0, casted.get_radius())
-                        .position(transform_to_isometry(transform)),
-                )
-            }
-            "CylinderShape3D" => {
-                let casted = shape.cast::<CylinderShape3D>();
-                Some(
-                    ColliderBuilder::cylinder(casted.get_height() / 2.0, casted.get_radius())
-                        .position(transform_to_isometry(transform)),
-                )
-            }
-            "ConcavePolygonShape3D" => {
-                let casted = shape.cast::<ConcavePolygonShape3D>();
-
-                let faces = casted.get_faces().to_vec();
-                let mut vertices: Vec<Point<f32>> = vec![];
-                let mut indices: Vec<[u32; 3]> = vec![];
-
-                for (idx, vert) in faces.iter().enumerate() {
-                    vertices.push(vector_to_point(&vector_to_rapier(vert.clone())));
-                    if idx % 3 == 0 {
-                        indices.push([idx as u32, (idx + 1) as u32, (idx + 2) as u32]);
-                    }
-                }
+/// Resolves a joint body path to the handle of the body it points to
+fn find_joint_body(
+    node: &Gd<impl Jointable>,
+    path: &NodePath,
+    physics: &PhysicsState,
+) -> Option<RigidBodyHandle> {
+    let body = node.bind().base().get_node_or_null(path)?;
+    if !body.has_meta("cuid") {
+        return None;
+    }
+    let body_uid = GString::from_variant(&body.get_meta("cuid")).to_string();
+    let raw_handle = physics.lookup_table.get_rapier_handle(&body_uid)?;
+    match physics
+        .lookup_table
+        .get_godot_uid(HandleKind::RigidBody, &raw_handle)
+    {
+        Some(uid) if uid == body_uid => {
+            Some(RigidBodyHandle::from_raw_parts(raw_handle.0, raw_handle.1))
+        }
+        _ => None,
+    }
+}
 
-                let trimesh =
-                    ColliderBuilder::trimesh_with_flags(vertices, indices, TriMeshFlags::empty());
-                match trimesh {
-                    Ok(trimesh) => {
-                        if is_expensive(&trimesh) {
-                            if let Some(tree) = node.get_tree() {
-                                if tree.is_debugging_collisions_hint() {
-                                    log::warn!("Debug rendering of '{:?}' collider will be skipped because it has more than {} vertices", node.get_name(), crate::config::DEBUG_MAX_VERTEX_COUNT);
-                                }
-                            }
-                        }
+fn shape_to_collider_shape(node: &Gd<RapierCollisionShape3D>) -> Option<SharedShape> {
+    match node.bind().get_shape() {
+        Some(shape) => {
+            let rapier_shape = match node.bind().decomposition_params() {
+                Some(params) => shape_to_convex_decomposition(&shape, &params)?,
+                None => shape_to_rapier(&shape)?,
+            };
 
-                        Some(trimesh.position(transform_to_isometry(transform)))
-                    }
-                    Err(_) => {
-                        log::error!("Failed to create trimesh collider from collision shape");
-                        None
+            if is_expensive_shape(&*rapier_shape) {
+                if let Some(tree) = node.get_tree() {
+                    if tree.is_debugging_collisions_hint() {
+                        log::warn!("Debug rendering of '{:?}' collider will be skipped because it has more than {} vertices", node.get_name(), crate::config::DEBUG_MAX_VERTEX_COUNT);
                     }
                 }
             }
-            _ => {
-                log::error!("Unknown shape class: {}", shape.get_class().to_string());
-                None
-            }
-        },
+
+            Some(rapier_shape)
+        }
         None => {
             log::error!(
                 "Missing shape on: '{}'. {}",
@@ -229,79 +274,3 @@ fn shape_to_collider(node: &Gd<RapierCollisionShape3D>) -> Option<ColliderBuilde
         }
     }
 }
-
-pub fn remove_node_from_world(node: Gd<Node3D>, physics: &mut PhysicsState) {
-    let class = node.get_class().to_string();
-    match class.as_str() {
-        "RapierArea3D" => {
-            let casted = node.cast::<RapierCollisionShape3D>();
-            remove_collider_node_if_exists(&casted, physics);
-        }
-        "RapierKinematicCharacter3D" => {
-            let casted = node.cast::<RapierKinematicCharacter3D>();
-            remove_body(&casted, physics);
-        }
-        "RapierRigidBody3D" => {
-            let casted = node.cast::<RapierRigidBody3D>();
-            remove_body(&casted, physics);
-        }
-        "RapierStaticBody3D" => {
-            let casted = node.cast::<RapierStaticBody3D>();
-            remove_body(&casted, physics);
-        }
-        "RapierCollisionShape3D" => {
-            let casted = node.cast::<RapierCollisionShape3D>();
-            remove_collider_node_if_exists(&casted, physics);
-        }
-        _ => {
-            log::error!("Unknown object type: {}", &class);
-        }
-    }
-}
-
-/// Returns true if the collider is expensive to render/serialize
-/// and should be excluded from snapshots and debug rendering
-fn is_expensive(builder: &ColliderBuilder) -> bool {
-    match builder.shape.as_trimesh() {
-        Some(trimesh) => {
-            let vertices = trimesh.vertices().len();
-            vertices > crate::config::DEBUG_MAX_VERTEX_COUNT
-        }
-        None => false,
-    }
-}
-
-/// Removes the given RapierCollisionShape3D from cheap or expensive colliders if it exists in either set
-fn remove_collider_node_if_exists(node: &Gd<RapierCollisionShape3D>, physics: &mut PhysicsState) {
-    let node_uid = node.bind().get_cuid();
-    if let Some(raw_handle) = physics.lookup_table.remove_by_uid(&node_uid.into()) {
-        remove_collider_if_exists(&raw_handle, physics);
-    }
-}
-
-/// Removes the given collider handle from all lookup tables and collider set
-pub fn remove_collider_if_exists(raw_handle: &(u32, u32), physics: &mut PhysicsState) {
-    physics.lookup_table.remove_by_handle(raw_handle);
-    physics.lookup_table.remove_snapshot_collider(raw_handle);
-    let handle = ColliderHandle::from_raw_parts(raw_handle.0, raw_handle.1);
-    if physics.colliders.contains(handle) {
-        physics
-            .colliders
-            .remove(handle, &mut physics.islands, &mut physics.bodies, false);
-    }
-}
-
-/// Removes the given rigid body from the world
-fn remove_body(node: &Gd<impl IRapierObject>, physics: &mut PhysicsState) {
-    let node_uid = node.bind().get_cuid();
-    if let Some(handle) = physics.lookup_table.remove_by_uid(&node_uid) {
-        physics.bodies.remove(
-            RigidBodyHandle::from_raw_parts(handle.0, handle.1),
-            &mut physics.islands,
-            &mut physics.colliders,
-            &mut physics.impulse_joints,
-            &mut physics.multibody_joints,
-            false,
-        );
-    }
-}
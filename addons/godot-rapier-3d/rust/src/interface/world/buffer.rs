@@ -2,19 +2,33 @@ use godot::prelude::*;
 
 use crate::{
     nodes::{
-        Identifiable, RapierArea3D, RapierCollisionShape3D, RapierKinematicCharacter3D,
-        RapierPIDCharacter3D, RapierRigidBody3D, RapierStaticBody3D,
+        Identifiable, RapierArea3D, RapierCollisionShape3D, RapierFixedJoint3D,
+        RapierKinematicCharacter3D, RapierPIDCharacter3D, RapierPrismaticJoint3D,
+        RapierRevoluteJoint3D, RapierRigidBody3D, RapierRopeJoint3D, RapierSphericalJoint3D,
+        RapierSpringJoint3D, RapierStaticBody3D,
     },
+    world::command::{Command, OperationOrder},
     World,
 };
 
+use super::modify_nodes::node_force;
+
 #[derive(GodotConvert, Var, Export, Debug, Clone)]
 #[godot(via = GString)]
 pub enum Operation {
     AddNode,
     RemoveNode,
     MoveNode,
+    RotateNode,
     ConfigureNode,
+    ApplyImpulse,
+    ApplyImpulseAtPoint,
+    ApplyTorqueImpulse,
+    AddForce,
+    AddTorque,
+    ResetForces,
+    SetLinearVelocity,
+    SetAngularVelocity,
 }
 
 #[derive(Clone)]
@@ -36,31 +50,91 @@ impl Action {
     }
 }
 
-/// Constructs a new action and then adds it to the world buffer at the current timestep
-pub fn ingest_action(node: Gd<Node3D>, operation: Operation, data: Dictionary, world: &mut World) {
-    if let Some(cuid) = extract_cuid(node.clone()) {
-        let action = Action::new(cuid, node, operation, data);
-        let timestep_id = world.state.timestep_id;
-        world.buffer.insert_action(action, timestep_id);
+/// Constructs a new action and then adds it to the world buffer at the current timestep.
+/// Removals and forces do not need the node once queued, so they are queued as the equivalent commands
+pub fn ingest_action(
+    cuid: GString,
+    node: Gd<Node3D>,
+    operation: Operation,
+    data: Dictionary,
+    world: &mut World,
+) {
+    match operation {
+        Operation::AddNode
+        | Operation::ConfigureNode
+        | Operation::MoveNode
+        | Operation::RotateNode => {
+            let action = Action::new(cuid, node, operation, data);
+            let timestep_id = world.state.timestep_id;
+            world.buffer.insert_action(action, timestep_id);
+        }
+        Operation::RemoveNode => world.queue_command(Command::Remove {
+            cuid: cuid.to_string(),
+        }),
+        _ => {
+            if let Some(force) = node_force(&node, &operation, &data) {
+                world.queue_command(Command::Force {
+                    cuid: cuid.to_string(),
+                    force,
+                });
+            }
+        }
     }
 }
 
-// NOTE sorting should happen right before stepping the world (so that it only happens once per timestep)
-// sorting = by cuid -> by operation -> (within move operations, magnitude of )
-pub fn sort_actions(actions: Vec<Action>) -> Vec<Action> {
-    actions
+/// Sort key of an action, so that the actions of a timestep are applied in the same order on every peer,
+/// regardless of the order in which they were queued.
+/// Sorting = by cuid -> by operation (see OperationOrder) -> by payload
+pub fn action_order(action: &Action) -> (String, OperationOrder, Vec<u8>) {
+    (
+        action.cuid.to_string(),
+        operation_order(&action.operation),
+        payload_key(&action.data),
+    )
 }
 
-fn extract_cuid(node: Gd<Node3D>) -> Option<GString> {
+/// Place of the operation in the order shared with commands
+fn operation_order(operation: &Operation) -> OperationOrder {
+    match operation {
+        Operation::AddNode => OperationOrder::Add,
+        Operation::ConfigureNode => OperationOrder::Configure,
+        Operation::MoveNode => OperationOrder::Move,
+        Operation::RotateNode => OperationOrder::Rotate,
+        Operation::ResetForces => OperationOrder::ResetForces,
+        Operation::SetLinearVelocity => OperationOrder::SetLinearVelocity,
+        Operation::SetAngularVelocity => OperationOrder::SetAngularVelocity,
+        Operation::ApplyImpulse => OperationOrder::ApplyImpulse,
+        Operation::ApplyImpulseAtPoint => OperationOrder::ApplyImpulseAtPoint,
+        Operation::ApplyTorqueImpulse => OperationOrder::ApplyTorqueImpulse,
+        Operation::AddForce => OperationOrder::AddForce,
+        Operation::AddTorque => OperationOrder::AddTorque,
+        Operation::RemoveNode => OperationOrder::Remove,
+    }
+}
+
+/// Stable byte representation of an action's data, used to order otherwise identical actions
+fn payload_key(data: &Dictionary) -> Vec<u8> {
+    godot::global::var_to_bytes(&data.to_variant()).to_vec()
+}
+
+/// Returns the cuid and world id of the node if it was recognized
+pub fn extract_identity(node: &Gd<Node3D>) -> Option<(GString, i64)> {
+    let node = node.clone();
     match node.get_class().to_string().as_str() {
-        "RapierArea3D" => Some(node.cast::<RapierArea3D>().bind().get_cuid()),
-        "RapierCollisionShape3D" => Some(node.cast::<RapierCollisionShape3D>().bind().get_cuid()),
+        "RapierArea3D" => Some(identity(&*node.cast::<RapierArea3D>().bind())),
+        "RapierCollisionShape3D" => Some(identity(&*node.cast::<RapierCollisionShape3D>().bind())),
         "RapierKinematicCharacter3D" => {
-            Some(node.cast::<RapierKinematicCharacter3D>().bind().get_cuid())
+            Some(identity(&*node.cast::<RapierKinematicCharacter3D>().bind()))
         }
-        "RapierPIDCharacter3D" => Some(node.cast::<RapierPIDCharacter3D>().bind().get_cuid()),
-        "RapierRigidBody3D" => Some(node.cast::<RapierRigidBody3D>().bind().get_cuid()),
-        "RapierStaticBody3D" => Some(node.cast::<RapierStaticBody3D>().bind().get_cuid()),
+        "RapierPIDCharacter3D" => Some(identity(&*node.cast::<RapierPIDCharacter3D>().bind())),
+        "RapierRigidBody3D" => Some(identity(&*node.cast::<RapierRigidBody3D>().bind())),
+        "RapierStaticBody3D" => Some(identity(&*node.cast::<RapierStaticBody3D>().bind())),
+        "RapierFixedJoint3D" => Some(identity(&*node.cast::<RapierFixedJoint3D>().bind())),
+        "RapierRevoluteJoint3D" => Some(identity(&*node.cast::<RapierRevoluteJoint3D>().bind())),
+        "RapierPrismaticJoint3D" => Some(identity(&*node.cast::<RapierPrismaticJoint3D>().bind())),
+        "RapierSphericalJoint3D" => Some(identity(&*node.cast::<RapierSphericalJoint3D>().bind())),
+        "RapierRopeJoint3D" => Some(identity(&*node.cast::<RapierRopeJoint3D>().bind())),
+        "RapierSpringJoint3D" => Some(identity(&*node.cast::<RapierSpringJoint3D>().bind())),
         _ => {
             log::error!(
                 "Node class not recognized: {}",
@@ -70,3 +144,83 @@ fn extract_cuid(node: Gd<Node3D>) -> Option<GString> {
         }
     }
 }
+
+fn identity(node: &impl Identifiable) -> (GString, i64) {
+    (node.get_cuid(), node.get_world_id())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::objects::BodyForce;
+    use rapier3d::prelude::*;
+
+    /// Every operation, in the order they are applied to a node
+    fn operations() -> Vec<Operation> {
+        vec![
+            Operation::AddNode,
+            Operation::ConfigureNode,
+            Operation::MoveNode,
+            Operation::RotateNode,
+            Operation::ResetForces,
+            Operation::SetLinearVelocity,
+            Operation::SetAngularVelocity,
+            Operation::ApplyImpulse,
+            Operation::ApplyImpulseAtPoint,
+            Operation::ApplyTorqueImpulse,
+            Operation::AddForce,
+            Operation::AddTorque,
+            Operation::RemoveNode,
+        ]
+    }
+
+    #[test]
+    fn operation_orders_are_unique_and_ordered() {
+        let orders: Vec<OperationOrder> = operations().iter().map(operation_order).collect();
+        assert!(orders.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn commands_share_the_order_of_their_actions() {
+        let cuid = String::from("a");
+        let force = |force| Command::Force {
+            cuid: cuid.clone(),
+            force,
+        };
+        let v = vector![1.0, 2.0, 3.0];
+        let equivalents = [
+            (
+                Operation::RemoveNode,
+                Command::Remove { cuid: cuid.clone() },
+            ),
+            (Operation::ResetForces, force(BodyForce::ResetForces)),
+            (
+                Operation::SetLinearVelocity,
+                force(BodyForce::SetLinearVelocity(v)),
+            ),
+            (
+                Operation::SetAngularVelocity,
+                force(BodyForce::SetAngularVelocity(v)),
+            ),
+            (Operation::ApplyImpulse, force(BodyForce::ApplyImpulse(v))),
+            (
+                Operation::ApplyImpulseAtPoint,
+                force(BodyForce::ApplyImpulseAtPoint(v, point![0.0, 0.0, 0.0])),
+            ),
+            (
+                Operation::ApplyTorqueImpulse,
+                force(BodyForce::ApplyTorqueImpulse(v)),
+            ),
+            (Operation::AddForce, force(BodyForce::AddForce(v))),
+            (Operation::AddTorque, force(BodyForce::AddTorque(v))),
+        ];
+        for (operation, command) in equivalents {
+            assert_eq!(
+                operation_order(&operation),
+                command.order(),
+                "{:?}",
+                operation
+            );
+        }
+    }
+}
@@ -2,6 +2,6 @@ mod add_remove_nodes;
 mod buffer;
 mod modify_nodes;
 
-pub use add_remove_nodes::{add_node_to_world, remove_node_from_world};
-pub use buffer::{ingest_action, Action, Operation};
-pub use modify_nodes::{configure_node, move_node};
+pub use add_remove_nodes::add_node_to_world;
+pub use buffer::{action_order, extract_identity, ingest_action, Action, Operation};
+pub use modify_nodes::{configure_node, move_node, rotate_node};
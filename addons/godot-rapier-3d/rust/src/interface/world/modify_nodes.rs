@@ -2,37 +2,71 @@ use godot::prelude::*;
 use rapier3d::{
     control::{CharacterLength, KinematicCharacterController, PdController, PidController},
     math::UnitVector,
-    prelude::{AxesMask, QueryFilter, RigidBodyHandle, RigidBodyVelocity},
+    prelude::{AxesMask, ColliderHandle, ImpulseJointHandle, RigidBodyHandle, RigidBodyVelocity},
 };
 
+use super::Operation;
 use crate::{
-    nodes::{Identifiable, RapierKinematicCharacter3D, RapierPIDCharacter3D, RapierRigidBody3D},
-    utils::{uniform_rapier_vector, vector_to_rapier},
+    nodes::{
+        Collidable, ColliderSettings, Identifiable, Jointable, MotionMode, RapierArea3D,
+        RapierCollisionShape3D, RapierFixedJoint3D, RapierKinematicCharacter3D,
+        RapierPIDCharacter3D, RapierPrismaticJoint3D, RapierRevoluteJoint3D, RapierRigidBody3D,
+        RapierRopeJoint3D, RapierSphericalJoint3D, RapierSpringJoint3D, RapierStaticBody3D,
+        SlideCollision,
+    },
+    utils::{quaternion_to_rapier, uniform_rapier_vector, vector_to_point, vector_to_rapier},
+    world::character::{
+        align_character, floating_controller, move_character, rotate_character, CharacterRotation,
+    },
+    world::events::EventCollider,
+    world::gravity::wake_bodies_inside,
+    world::objects::BodyForce,
     world::state::PhysicsState,
 };
 
-pub fn configure_node(node: Gd<Node3D>) {
+pub fn configure_node(node: Gd<Node3D>, physics: &mut PhysicsState) {
     let class = node.get_class().to_string();
 
+    if let Some(settings) = get_collider_settings(&node) {
+        match class.as_str() {
+            "RapierCollisionShape3D" => {
+                let casted = node.clone().cast::<RapierCollisionShape3D>();
+                let parent_settings = find_parent_collider_settings(&casted).unwrap_or(settings);
+                configure_collider(&casted, parent_settings, physics);
+            }
+            _ => {
+                for child in find_child_collision_shapes(&node).iter_shared() {
+                    let casted = child.cast::<RapierCollisionShape3D>();
+                    configure_collider(&casted, settings, physics);
+                }
+            }
+        }
+    }
+
     match class.as_str() {
         "RapierKinematicCharacter3D" => {
             let mut casted = node.cast::<RapierKinematicCharacter3D>();
             let mut char = casted.bind_mut();
 
-            char.controller = KinematicCharacterController {
+            let controller = KinematicCharacterController {
                 up: UnitVector::new_normalize(vector_to_rapier(char.get_up_direction())),
                 offset: CharacterLength::Relative(char.get_safe_margin()),
                 slide: char.get_slide(),
-                autostep: None, // TODO
+                autostep: char.get_autostep(),
                 max_slope_climb_angle: char.get_floor_max_angle(),
                 min_slope_slide_angle: char.get_floor_min_slide_angle(),
                 snap_to_ground: Some(CharacterLength::Relative(char.get_floor_snap_length())),
                 normal_nudge_factor: char.get_normal_nudge_factor(),
-            }
+            };
+            char.controller = match char.motion_mode {
+                MotionMode::Grounded => controller,
+                MotionMode::Floating => floating_controller(controller),
+            };
         }
         "RapierPIDCharacter3D" => {
             let mut casted = node.cast::<RapierPIDCharacter3D>();
             let mut char = casted.bind_mut();
+            configure_gravity_scale(char.get_handle_raw(), char.gravity_scale, physics);
 
             char.controller = PidController {
                 pd: PdController {
@@ -47,8 +81,41 @@ pub fn configure_node(node: Gd<Node3D>) {
                 ..PidController::default()
             };
         }
-        "RapierArea3D" | "RapierRigidBody3D" | "RapierCollisionShape3D" | "RapierStaticBody3D" => {
-            // TODO
+        "RapierFixedJoint3D" => {
+            let casted = node.cast::<RapierFixedJoint3D>();
+            configure_joint(&casted, physics);
+        }
+        "RapierRevoluteJoint3D" => {
+            let casted = node.cast::<RapierRevoluteJoint3D>();
+            configure_joint(&casted, physics);
+        }
+        "RapierPrismaticJoint3D" => {
+            let casted = node.cast::<RapierPrismaticJoint3D>();
+            configure_joint(&casted, physics);
+        }
+        "RapierSphericalJoint3D" => {
+            let casted = node.cast::<RapierSphericalJoint3D>();
+            configure_joint(&casted, physics);
+        }
+        "RapierRopeJoint3D" => {
+            let casted = node.cast::<RapierRopeJoint3D>();
+            configure_joint(&casted, physics);
+        }
+        "RapierSpringJoint3D" => {
+            let casted = node.cast::<RapierSpringJoint3D>();
+            configure_joint(&casted, physics);
+        }
+        "RapierRigidBody3D" => {
+            let casted = node.cast::<RapierRigidBody3D>();
+            let body = casted.bind();
+            configure_gravity_scale(body.get_handle_raw(), body.gravity_scale, physics);
+        }
+        "RapierArea3D" => {
+            let casted = node.cast::<RapierArea3D>();
+            configure_area_gravity(&casted, physics);
+        }
+        "RapierCollisionShape3D" | "RapierStaticBody3D" => {
+            // Only collision groups and materials are configurable for now
         }
         _ => log::error!(
             "Trying to configure a '{}' node which is not a configurable node type",
@@ -57,65 +124,176 @@ pub fn configure_node(node: Gd<Node3D>) {
     }
 }
 
-pub fn move_node(node: Gd<Node3D>, desired_movement: Vector3, physics: &mut PhysicsState) {
-    let class = node.get_class().to_string();
+fn configure_gravity_scale(raw: Option<(u32, u32)>, scale: f32, physics: &mut PhysicsState) {
+    if let Some(raw) = raw {
+        let handle = RigidBodyHandle::from_raw_parts(raw.0, raw.1);
+        if let Some(body) = physics.bodies.get_mut(handle) {
+            body.set_gravity_scale(scale, true);
+        }
+    }
+}
 
-    match class.as_str() {
-        "RapierKinematicCharacter3D" => {
-            let mut casted = node.cast::<RapierKinematicCharacter3D>();
-            let mut char = casted.bind_mut();
-            let handle = physics.lookup_table.get_rapier_handle(&char.get_cuid());
+/// Updates the gravity override of every collider of the area, and wakes the bodies inside it
+fn configure_area_gravity(node: &Gd<RapierArea3D>, physics: &mut PhysicsState) {
+    let gravity = node.bind().gravity_override();
+    let area: Gd<Node3D> = node.clone().upcast();
+    for child in find_child_collision_shapes(&area).iter_shared() {
+        let shape = child.cast::<RapierCollisionShape3D>();
+        let Some(raw) = physics
+            .lookup_table
+            .get_rapier_handle(&shape.bind().get_cuid().to_string())
+        else {
+            continue;
+        };
+        let changed = match &gravity {
+            Some(gravity) => {
+                physics
+                    .gravity_overrides
+                    .insert(raw, gravity.clone())
+                    .as_ref()
+                    != Some(gravity)
+            }
+            None => physics.gravity_overrides.remove(&raw).is_some(),
+        };
+        if changed {
+            wake_bodies_inside(ColliderHandle::from_raw_parts(raw.0, raw.1), physics);
+        }
+    }
+}
 
-            if let Some(raw) = handle {
-                let controller = &char.controller;
-                let handle = RigidBodyHandle::from_raw_parts(raw.0, raw.1);
+/// Rebuilds the joint data from the current node properties (e.g. motor targets)
+fn configure_joint(node: &Gd<impl Jointable>, physics: &mut PhysicsState) {
+    let joint = node.bind();
+    if let Some(raw) = joint.get_handle_raw() {
+        let handle = ImpulseJointHandle::from_raw_parts(raw.0, raw.1);
+        if let Some(impulse_joint) = physics.impulse_joints.get_mut(handle, true) {
+            impulse_joint.data = joint.build_joint();
+        }
+    }
+}
 
-                if !physics.bodies.contains(handle) {
-                    return;
-                }
+/// Returns the collider settings of any collidable node
+fn get_collider_settings(node: &Gd<Node3D>) -> Option<ColliderSettings> {
+    let node = node.clone();
+    match node.get_class().to_string().as_str() {
+        "RapierArea3D" => Some(node.cast::<RapierArea3D>().bind().collider_settings()),
+        "RapierCollisionShape3D" => Some(
+            node.cast::<RapierCollisionShape3D>()
+                .bind()
+                .collider_settings(),
+        ),
+        "RapierKinematicCharacter3D" => Some(
+            node.cast::<RapierKinematicCharacter3D>()
+                .bind()
+                .collider_settings(),
+        ),
+        "RapierPIDCharacter3D" => Some(
+            node.cast::<RapierPIDCharacter3D>()
+                .bind()
+                .collider_settings(),
+        ),
+        "RapierRigidBody3D" => Some(node.cast::<RapierRigidBody3D>().bind().collider_settings()),
+        "RapierStaticBody3D" => Some(node.cast::<RapierStaticBody3D>().bind().collider_settings()),
+        _ => None,
+    }
+}
 
-                let body = &physics.bodies[handle];
-                let collider = &physics.colliders[body.colliders()[0]];
-                let mass = body.mass();
+/// Walks up the tree to find the collider settings of the body or area that owns the given collision shape
+fn find_parent_collider_settings(node: &Gd<RapierCollisionShape3D>) -> Option<ColliderSettings> {
+    let mut current = node.get_parent();
+    while let Some(parent) = current {
+        if let Ok(parent_3d) = parent.clone().try_cast::<Node3D>() {
+            if !parent_3d.is_class("RapierCollisionShape3D") {
+                if let Some(settings) = get_collider_settings(&parent_3d) {
+                    return Some(settings);
+                }
+            }
+        }
+        current = parent.get_parent();
+    }
+    None
+}
 
-                let mut collisions = vec![];
-                let movement = controller.move_shape(
-                    physics.integration_parameters.dt,
-                    &physics.bodies,
-                    &physics.colliders,
-                    &physics.query_pipeline,
-                    collider.shape(),
-                    collider.position(),
-                    vector_to_rapier(desired_movement),
-                    QueryFilter::new().exclude_rigid_body(handle),
-                    |c| collisions.push(c),
-                );
+fn find_child_collision_shapes(node: &Gd<Node3D>) -> Array<Gd<Node>> {
+    node.find_children_ex("*")
+        .type_("RapierCollisionShape3D")
+        .recursive(true)
+        .owned(false)
+        .done()
+}
 
-                // Apply impulses to other rigidbodies that were contacted
-                controller.solve_character_collision_impulses(
-                    physics.integration_parameters.dt,
-                    &mut physics.bodies,
-                    &physics.colliders,
-                    &physics.query_pipeline,
-                    collider.shape(),
-                    mass,
-                    &*collisions,
-                    QueryFilter::new().exclude_rigid_body(handle),
-                );
+/// Updates the collision groups, active events and material of the collider belonging to the given collision shape
+fn configure_collider(
+    node: &Gd<RapierCollisionShape3D>,
+    parent_settings: ColliderSettings,
+    physics: &mut PhysicsState,
+) {
+    let shape = node.bind();
+    if let Some(raw) = physics
+        .lookup_table
+        .get_rapier_handle(&shape.get_cuid().to_string())
+    {
+        let handle = ColliderHandle::from_raw_parts(raw.0, raw.1);
+        if let Some(collider) = physics.colliders.get_mut(handle) {
+            let groups = shape.resolve_collision_groups(parent_settings.groups);
+            collider.set_collision_groups(groups);
+            collider.set_solver_groups(groups);
+            collider.set_active_events(parent_settings.active_events());
+            collider.set_contact_force_event_threshold(
+                parent_settings.contact_force_threshold.unwrap_or(0.0),
+            );
+            shape
+                .resolve_material(parent_settings.material)
+                .apply_to_collider(collider);
+        }
+    }
+}
 
-                let body = &mut physics.bodies[handle];
+pub fn move_node(node: Gd<Node3D>, desired_movement: Vector3, physics: &mut PhysicsState) {
+    let class = node.get_class().to_string();
 
-                let pose = body.position();
-                body.set_next_kinematic_translation(pose.translation.vector + movement.translation);
+    match class.as_str() {
+        "RapierKinematicCharacter3D" => {
+            let mut casted = node.cast::<RapierKinematicCharacter3D>();
+            let mut char = casted.bind_mut();
+            let Some(raw) = physics
+                .lookup_table
+                .get_rapier_handle(&char.get_cuid().to_string())
+            else {
+                return;
+            };
 
-                char.last_movement = Some(movement);
-                char.last_collisions = collisions;
+            let handle = RigidBodyHandle::from_raw_parts(raw.0, raw.1);
+            let desired_movement = vector_to_rapier(desired_movement);
+            let floating = char.motion_mode == MotionMode::Floating;
+            let on_leave = char.platform_leave();
+            if let Some(result) = move_character(
+                &char.controller,
+                floating,
+                on_leave,
+                handle,
+                desired_movement,
+                physics,
+            ) {
+                if char.align_to_floor {
+                    align_character(&char.controller, &result, handle, physics);
+                }
+                char.last_movement = Some(result.movement);
+                char.last_collisions = result
+                    .collisions
+                    .into_iter()
+                    .map(|collision| SlideCollision {
+                        collider: EventCollider::from_handle(collision.handle, physics),
+                        collision,
+                    })
+                    .collect();
+                char.stepped_up = result.stepped_up;
             }
         }
         "RapierPIDCharacter3D" => {
             let mut casted = node.cast::<RapierPIDCharacter3D>();
             let uid = casted.bind().get_cuid();
-            let handle = physics.lookup_table.get_rapier_handle(&uid);
+            let handle = physics.lookup_table.get_rapier_handle(&uid.to_string());
 
             if let Some(raw) = handle {
                 let controller = &mut casted.bind_mut().controller;
@@ -148,7 +326,7 @@ pub fn move_node(node: Gd<Node3D>, desired_movement: Vector3, physics: &mut Phys
         "RapierRigidBody3D" => {
             let mut casted = node.cast::<RapierRigidBody3D>();
             let uid = casted.bind().get_cuid();
-            let handle = physics.lookup_table.get_rapier_handle(&uid);
+            let handle = physics.lookup_table.get_rapier_handle(&uid.to_string());
 
             if let Some(raw) = handle {
                 let controller = &mut casted.bind_mut().controller;
@@ -173,3 +351,88 @@ pub fn move_node(node: Gd<Node3D>, desired_movement: Vector3, physics: &mut Phys
         _ => log::error!("Cannot move node '{}'", class),
     }
 }
+
+/// Rotates the body of the given character by the rotation or towards the facing direction of the action's data
+pub fn rotate_node(node: Gd<Node3D>, data: &Dictionary, physics: &mut PhysicsState) {
+    let class = node.get_class().to_string();
+
+    match class.as_str() {
+        "RapierKinematicCharacter3D" => {
+            let uid = node.cast::<RapierKinematicCharacter3D>().bind().get_cuid();
+            let Some(raw) = physics.lookup_table.get_rapier_handle(&uid.to_string()) else {
+                return;
+            };
+            let rotation = match data.contains_key("facing") {
+                true => extract_data(data, "facing")
+                    .map(|direction| CharacterRotation::Face(vector_to_rapier(direction))),
+                false => extract_data(data, "rotation")
+                    .map(|rotation| CharacterRotation::RotateBy(quaternion_to_rapier(rotation))),
+            };
+            if let Some(rotation) = rotation {
+                let handle = RigidBodyHandle::from_raw_parts(raw.0, raw.1);
+                rotate_character(handle, rotation, physics);
+            }
+        }
+        _ => log::error!("Cannot rotate node '{}'", class),
+    }
+}
+
+/// Converts a force action to the BodyForce applied to the rigid body of the given node.
+/// Logs an error if the node cannot receive forces
+pub fn node_force(
+    node: &Gd<Node3D>,
+    operation: &Operation,
+    data: &Dictionary,
+) -> Option<BodyForce> {
+    let class = node.get_class().to_string();
+    match class.as_str() {
+        "RapierRigidBody3D" | "RapierPIDCharacter3D" => action_to_force(operation, data),
+        _ => {
+            log::error!("Cannot apply forces to node '{}'", class);
+            None
+        }
+    }
+}
+
+/// Converts a force operation and its data to a BodyForce, logging an error if the data is missing or invalid
+fn action_to_force(operation: &Operation, data: &Dictionary) -> Option<BodyForce> {
+    let vector = |key: &str| extract_data(data, key).map(vector_to_rapier);
+    match operation {
+        Operation::ApplyImpulse => vector("impulse").map(BodyForce::ApplyImpulse),
+        Operation::ApplyImpulseAtPoint => {
+            let impulse = vector("impulse")?;
+            let point = vector("point")?;
+            Some(BodyForce::ApplyImpulseAtPoint(
+                impulse,
+                vector_to_point(&point),
+            ))
+        }
+        Operation::ApplyTorqueImpulse => vector("torque").map(BodyForce::ApplyTorqueImpulse),
+        Operation::AddForce => vector("force").map(BodyForce::AddForce),
+        Operation::AddTorque => vector("torque").map(BodyForce::AddTorque),
+        Operation::ResetForces => Some(BodyForce::ResetForces),
+        Operation::SetLinearVelocity => vector("velocity").map(BodyForce::SetLinearVelocity),
+        Operation::SetAngularVelocity => vector("velocity").map(BodyForce::SetAngularVelocity),
+        _ => {
+            log::error!("'{:?}' is not a force operation", operation);
+            None
+        }
+    }
+}
+
+/// Reads a value from action data, logging an error if it is missing or invalid
+fn extract_data<T: FromGodot>(data: &Dictionary, key: &str) -> Option<T> {
+    match data.get(key) {
+        Some(value) => match T::try_from_variant(&value) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                log::error!("Action has invalid '{}' data: {}", key, e);
+                None
+            }
+        },
+        None => {
+            log::error!("Action missing '{}' data: {:?}", key, data);
+            None
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::events::EventDispatcher;
+use super::registry::NodeRegistry;
+use crate::world::events::touching_pairs;
+use crate::world::settings::WorldSettings;
+use crate::World;
+
+/*
+    Independent physics worlds, each with its own buffer, tick counter and snapshots.
+    Nodes pick their world with their world_id property, GR3D functions take the id of the world they act on
+*/
+
+pub const DEFAULT_WORLD: i64 = 0;
+
+/// A world, plus the Godot side state mapping its results back to the nodes in it
+pub struct WorldContext {
+    pub world: World,
+    pub registry: NodeRegistry,
+    pub events: EventDispatcher,
+}
+
+impl WorldContext {
+    fn new(settings: &WorldSettings) -> Self {
+        Self {
+            world: World::new(settings),
+            registry: NodeRegistry::new(),
+            events: EventDispatcher::new(),
+        }
+    }
+
+    /// Advances the world by one step and emits the signals of its events
+    pub fn step(&mut self) {
+        self.world.step();
+        let events = self.world.drain_events();
+        self.events.dispatch(events, &self.registry);
+    }
+
+    /// Drops the events of steps whose state was overwritten or resimulated, without emitting signals,
+    /// and recounts the overlaps from the bodies touching now
+    pub fn resync_events(&mut self) {
+        self.world.drain_events();
+        let pairs = touching_pairs(&self.world.physics);
+        self.events.resync_overlaps(pairs, &self.registry);
+    }
+}
+
+/// Reasons a world can not be selected or destroyed
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorldError {
+    UnknownWorld { id: i64 },
+    DefaultWorld,
+}
+
+impl WorldError {
+    /// Stable identifier exposed to Godot
+    pub fn code(&self) -> &'static str {
+        match self {
+            WorldError::UnknownWorld { .. } => "UNKNOWN_WORLD",
+            WorldError::DefaultWorld => "DEFAULT_WORLD",
+        }
+    }
+}
+
+impl fmt::Display for WorldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WorldError::UnknownWorld { id } => write!(f, "World {} does not exist", id),
+            WorldError::DefaultWorld => write!(f, "The default world can not be destroyed"),
+        }
+    }
+}
+
+pub struct WorldRegistry {
+    worlds: BTreeMap<i64, WorldContext>,
+    next_id: i64, // Ids are never reused, so stale ids held by nodes can't reach a new world
+    settings: WorldSettings, // New worlds are created with the project settings
+}
+
+impl WorldRegistry {
+    pub fn new(settings: WorldSettings) -> Self {
+        let mut worlds = BTreeMap::new();
+        worlds.insert(DEFAULT_WORLD, WorldContext::new(&settings));
+        Self {
+            worlds,
+            next_id: DEFAULT_WORLD + 1,
+            settings,
+        }
+    }
+
+    /// Creates a new empty world and returns its id
+    pub fn create(&mut self) -> i64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.worlds.insert(id, WorldContext::new(&self.settings));
+        id
+    }
+
+    /// Destroys the world and everything in it
+    pub fn destroy(&mut self, id: i64) -> Result<(), WorldError> {
+        if id == DEFAULT_WORLD {
+            return Err(WorldError::DefaultWorld);
+        }
+        self.worlds
+            .remove(&id)
+            .map(|_| ())
+            .ok_or(WorldError::UnknownWorld { id })
+    }
+
+    pub fn ids(&self) -> Vec<i64> {
+        self.worlds.keys().copied().collect()
+    }
+
+    pub fn get(&self, id: i64) -> Option<&WorldContext> {
+        self.worlds.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: i64) -> Option<&mut WorldContext> {
+        self.worlds.get_mut(&id)
+    }
+
+    /// Same as get, with an error for GR3D functions called with an unknown world_id
+    pub fn find(&self, id: i64) -> Result<&WorldContext, WorldError> {
+        self.worlds.get(&id).ok_or(WorldError::UnknownWorld { id })
+    }
+
+    pub fn find_mut(&mut self, id: i64) -> Result<&mut WorldContext, WorldError> {
+        self.worlds
+            .get_mut(&id)
+            .ok_or(WorldError::UnknownWorld { id })
+    }
+
+    /// The world a node was added to, from the registries of every world
+    pub fn world_of(&self, cuid: &str) -> Option<i64> {
+        self.worlds
+            .iter()
+            .find(|(_, context)| context.registry.contains(cuid))
+            .map(|(id, _)| *id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn worlds_step_independently() {
+        let mut worlds = WorldRegistry::new(WorldSettings::default());
+        let minigame = worlds.create();
+        assert_eq!(worlds.ids(), vec![DEFAULT_WORLD, minigame]);
+
+        for _ in 0..3 {
+            worlds.find_mut(minigame).unwrap().step();
+        }
+        assert_eq!(worlds.get(minigame).unwrap().world.state.timestep_id, 3);
+        assert_eq!(
+            worlds.get(DEFAULT_WORLD).unwrap().world.state.timestep_id,
+            0
+        );
+    }
+
+    #[test]
+    fn destroying_worlds() {
+        let mut worlds = WorldRegistry::new(WorldSettings::default());
+        let prediction = worlds.create();
+
+        assert_eq!(worlds.destroy(DEFAULT_WORLD), Err(WorldError::DefaultWorld));
+        assert_eq!(worlds.destroy(prediction), Ok(()));
+        assert!(worlds.get(prediction).is_none());
+        assert_eq!(
+            worlds.find(prediction).err(),
+            Some(WorldError::UnknownWorld { id: prediction })
+        );
+
+        // Ids are not reused
+        assert_ne!(worlds.create(), prediction);
+    }
+}
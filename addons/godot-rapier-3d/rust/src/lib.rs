@@ -3,6 +3,7 @@ use godot::prelude::*;
 pub mod config;
 mod interface;
 mod nodes;
+mod resources;
 mod utils;
 mod world;
 
@@ -2,13 +2,55 @@ use godot::classes::notify::Node3DNotification;
 use godot::classes::{INode3D, Node3D};
 use godot::prelude::*;
 
-use crate::nodes::IRapierObject;
+use crate::nodes::{Collidable, IRapierObject, Identifiable};
+use crate::utils::{vector_to_point, vector_to_rapier};
+use crate::world::gravity::{GravityMode, GravityOverride};
+
+#[derive(GodotConvert, Var, Export, Debug, Clone, Copy, Default, PartialEq)]
+#[godot(via = GString)]
+/// How the gravity of an area combines with the world gravity for the bodies inside it
+pub enum GravityOverrideMode {
+    #[default]
+    Disabled,
+    Add,     // Added to the world gravity and to the gravity of lower priority areas
+    Replace, // Replaces the world gravity, lower priority areas are ignored
+}
 
 #[derive(GodotClass)]
 #[class(tool, base=Node3D)]
 pub struct RapierArea3D {
     #[var(usage_flags = [EDITOR, STORAGE, READ_ONLY])]
     pub handle: Array<u32>,
+    #[export]
+    /// The physics world this node is added to when it enters the tree, see GR3D.create_world
+    pub world_id: i64,
+    #[export(flags_3d_physics)]
+    #[var(get, set = set_collision_layer)]
+    /// The physics layers this area is in. Collision shapes may override this
+    pub collision_layer: u32,
+    #[export(flags_3d_physics)]
+    #[var(get, set = set_collision_mask)]
+    /// The physics layers this area scans for collisions. Collision shapes may override this
+    pub collision_mask: u32,
+    #[export]
+    #[var(get, set = set_gravity_override)]
+    /// Overrides the gravity of the dynamic bodies inside this area
+    pub gravity_override: GravityOverrideMode,
+    #[export]
+    #[var(get, set = set_gravity_point)]
+    /// Pull bodies towards the origin of this area instead of along gravity_direction
+    pub gravity_point: bool,
+    #[export]
+    #[var(get, set = set_gravity_direction)]
+    pub gravity_direction: Vector3,
+    #[export]
+    #[var(get, set = set_gravity)]
+    /// Strength of this area's gravity
+    pub gravity: f32,
+    #[export]
+    #[var(get, set = set_priority)]
+    /// Overlapping areas are applied from the highest priority to the lowest
+    pub priority: i32,
     base: Base<Node3D>,
 }
 
@@ -17,6 +59,14 @@ impl INode3D for RapierArea3D {
     fn init(base: Base<Node3D>) -> Self {
         Self {
             handle: Array::new(),
+            world_id: 0,
+            collision_layer: 1,
+            collision_mask: 1,
+            gravity_override: GravityOverrideMode::Disabled,
+            gravity_point: false,
+            gravity_direction: Vector3::DOWN,
+            gravity: 9.81,
+            priority: 0,
             base,
         }
     }
@@ -47,4 +97,89 @@ impl RapierArea3D {
     fn match_rapier(&mut self) {
         self.sync()
     }
+
+    #[func]
+    fn set_collision_layer(&mut self, layer: u32) {
+        self.collision_layer = layer;
+        self.on_collision_groups_changed();
+    }
+
+    #[func]
+    fn set_collision_mask(&mut self, mask: u32) {
+        self.collision_mask = mask;
+        self.on_collision_groups_changed();
+    }
+
+    #[func]
+    fn set_gravity_override(&mut self, mode: GravityOverrideMode) {
+        self.gravity_override = mode;
+        self.on_gravity_changed();
+    }
+
+    #[func]
+    fn set_gravity_point(&mut self, point: bool) {
+        self.gravity_point = point;
+        self.on_gravity_changed();
+    }
+
+    #[func]
+    fn set_gravity_direction(&mut self, direction: Vector3) {
+        self.gravity_direction = direction;
+        self.on_gravity_changed();
+    }
+
+    #[func]
+    fn set_gravity(&mut self, gravity: f32) {
+        self.gravity = gravity;
+        self.on_gravity_changed();
+    }
+
+    #[func]
+    fn set_priority(&mut self, priority: i32) {
+        self.priority = priority;
+        self.on_gravity_changed();
+    }
+
+    #[signal]
+    /// Emitted when a body enters this area
+    fn body_entered(body: Gd<Node3D>);
+
+    #[signal]
+    /// Emitted when a body exits this area
+    fn body_exited(body: Gd<Node3D>);
+
+    #[signal]
+    /// Emitted when another area enters this area
+    fn area_entered(area: Gd<Node3D>);
+
+    #[signal]
+    /// Emitted when another area exits this area
+    fn area_exited(area: Gd<Node3D>);
+}
+
+impl RapierArea3D {
+    /// The gravity override applied to the bodies inside this area, if enabled
+    pub fn gravity_override(&self) -> Option<GravityOverride> {
+        let mode = match self.gravity_override {
+            GravityOverrideMode::Disabled => return None,
+            GravityOverrideMode::Add => GravityMode::Add,
+            GravityOverrideMode::Replace => GravityMode::Replace,
+        };
+        Some(GravityOverride {
+            area: self.get_cuid().to_string(),
+            mode,
+            priority: self.priority,
+            point: self.gravity_point,
+            center: vector_to_point(&vector_to_rapier(self.base().get_global_position())),
+            direction: vector_to_rapier(self.gravity_direction.normalized_or_zero()),
+            strength: self.gravity,
+        })
+    }
+
+    /// Queues a reconfiguration so that gravity changes made at runtime reach the simulation deterministically
+    fn on_gravity_changed(&self) {
+        if self.base().is_inside_tree() {
+            self.on_reconfigure();
+        }
+    }
 }
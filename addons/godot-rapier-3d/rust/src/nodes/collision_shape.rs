@@ -1,8 +1,11 @@
 use godot::classes::notify::Node3DNotification;
 use godot::classes::{CollisionShape3D, INode3D, Node3D, Shape3D};
 use godot::prelude::*;
+use rapier3d::parry::transformation::vhacd::VHACDParameters;
+use rapier3d::prelude::InteractionGroups;
 
-use crate::nodes::IRapierObject;
+use crate::nodes::{Collidable, IRapierObject};
+use crate::resources::{watch_physics_material, MaterialProperties, RapierPhysicsMaterial};
 
 #[derive(GodotClass)]
 #[class(tool, base=Node3D)]
@@ -10,7 +13,38 @@ pub struct RapierCollisionShape3D {
     #[var(usage_flags = [EDITOR, STORAGE, READ_ONLY])]
     pub handle: Array<u32>,
     #[export]
+    /// The physics world this node is added to when it enters the tree, see GR3D.create_world
+    pub world_id: i64,
+    #[export]
     pub col_shape: Option<Gd<CollisionShape3D>>,
+    #[export]
+    #[var(get, set = set_override_collision_groups)]
+    /// Use this shape's own collision layer and mask instead of the parent body's
+    pub override_collision_groups: bool,
+    #[export(flags_3d_physics)]
+    #[var(get, set = set_collision_layer)]
+    /// The physics layers this collider is in (only used when overriding)
+    pub collision_layer: u32,
+    #[export(flags_3d_physics)]
+    #[var(get, set = set_collision_mask)]
+    /// The physics layers this collider scans for collisions (only used when overriding)
+    pub collision_mask: u32,
+    #[export]
+    #[var(get, set = set_physics_material)]
+    /// Friction, restitution and density of this collider. Overrides the material of the parent body
+    pub physics_material: Option<Gd<RapierPhysicsMaterial>>,
+    #[export]
+    /// Convert a ConcavePolygonShape3D into a set of convex hulls (V-HACD) so that dynamic bodies can use it.
+    /// Read when the collider is added to the world
+    pub convex_decomposition: bool,
+    #[export]
+    /// Voxel resolution used by the decomposition, higher is more accurate but slower
+    pub decomposition_resolution: u32,
+    #[export]
+    /// Maximum concavity allowed for each convex hull, lower produces more hulls
+    pub decomposition_concavity: f32,
+    #[export]
+    pub decomposition_max_convex_hulls: u32,
     base: Base<Node3D>,
 }
 
@@ -19,7 +53,16 @@ impl INode3D for RapierCollisionShape3D {
     fn init(base: Base<Node3D>) -> Self {
         Self {
             handle: Array::new(),
+            world_id: 0,
             col_shape: None,
+            override_collision_groups: false,
+            collision_layer: 1,
+            collision_mask: 1,
+            physics_material: None,
+            convex_decomposition: false,
+            decomposition_resolution: 64,
+            decomposition_concavity: 0.01,
+            decomposition_max_convex_hulls: 1024,
             base,
         }
     }
@@ -51,6 +94,68 @@ impl RapierCollisionShape3D {
         self.sync()
     }
 
+    #[func]
+    fn set_override_collision_groups(&mut self, value: bool) {
+        self.override_collision_groups = value;
+        self.on_collision_groups_changed();
+    }
+
+    #[func]
+    fn set_collision_layer(&mut self, layer: u32) {
+        self.collision_layer = layer;
+        self.on_collision_groups_changed();
+    }
+
+    #[func]
+    fn set_collision_mask(&mut self, mask: u32) {
+        self.collision_mask = mask;
+        self.on_collision_groups_changed();
+    }
+
+    #[func]
+    fn set_physics_material(&mut self, material: Option<Gd<RapierPhysicsMaterial>>) {
+        watch_physics_material(&self.to_gd().upcast(), &self.physics_material, &material);
+        self.physics_material = material;
+        self.on_physics_material_changed();
+    }
+
+    #[func]
+    fn _on_physics_material_changed(&self) {
+        self.on_physics_material_changed();
+    }
+
+    /// Returns the collision groups this collider should use given the groups of its parent body
+    pub fn resolve_collision_groups(&self, parent_groups: InteractionGroups) -> InteractionGroups {
+        match self.override_collision_groups {
+            true => self.collision_groups(),
+            false => parent_groups,
+        }
+    }
+
+    /// Returns the material this collider should use given the material of its parent body
+    pub fn resolve_material(
+        &self,
+        parent_material: Option<MaterialProperties>,
+    ) -> MaterialProperties {
+        match &self.physics_material {
+            Some(material) => material.bind().properties(),
+            None => parent_material.unwrap_or_default(),
+        }
+    }
+
+    /// Returns the V-HACD parameters if this shape should be converted into a convex decomposition
+    pub fn decomposition_params(&self) -> Option<VHACDParameters> {
+        match self.convex_decomposition {
+            true => Some(VHACDParameters {
+                resolution: self.decomposition_resolution,
+                concavity: self.decomposition_concavity,
+                max_convex_hulls: self.decomposition_max_convex_hulls,
+                ..VHACDParameters::default()
+            }),
+            false => None,
+        }
+    }
+
     #[func]
     pub fn get_shape(&self) -> Option<Gd<Shape3D>> {
         match &self.col_shape {
@@ -0,0 +1,114 @@
+use godot::prelude::*;
+use rapier3d::prelude::{ActiveEvents, Group, InteractionGroups, Real};
+
+use super::super::{
+    pid_character::RapierPIDCharacter3D, RapierArea3D, RapierCollisionShape3D,
+    RapierKinematicCharacter3D, RapierRigidBody3D, RapierStaticBody3D,
+};
+use super::IRapierObject;
+use crate::resources::{MaterialProperties, RapierPhysicsMaterial};
+use crate::world::descriptor::ColliderDescriptor;
+
+// Trait that applies to nodes with collision layers and masks
+
+/// Collider settings that child collision shapes inherit from their body or area
+#[derive(Clone, Copy, Debug)]
+pub struct ColliderSettings {
+    pub groups: InteractionGroups,
+    pub contact_force_threshold: Option<Real>, // None = contact force events disabled
+    pub material: Option<MaterialProperties>,  // None = collision shapes use Rapier's defaults
+}
+
+impl ColliderSettings {
+    pub fn active_events(&self) -> ActiveEvents {
+        ColliderDescriptor::active_events_for(self.contact_force_threshold)
+    }
+}
+
+pub trait Collidable: IRapierObject {
+    fn collision_layer_bits(&self) -> u32;
+    fn collision_mask_bits(&self) -> u32;
+
+    fn contact_force_threshold(&self) -> Option<Real> {
+        None
+    }
+
+    fn physics_material(&self) -> Option<Gd<RapierPhysicsMaterial>> {
+        None
+    }
+
+    fn collider_settings(&self) -> ColliderSettings {
+        ColliderSettings {
+            groups: self.collision_groups(),
+            contact_force_threshold: self.contact_force_threshold(),
+            material: self
+                .physics_material()
+                .map(|material| material.bind().properties()),
+        }
+    }
+
+    /// Godot style layer + mask mapped to Rapier memberships + filter
+    fn collision_groups(&self) -> InteractionGroups {
+        InteractionGroups::new(
+            Group::from_bits_truncate(self.collision_layer_bits()),
+            Group::from_bits_truncate(self.collision_mask_bits()),
+        )
+    }
+
+    /// Queues a reconfiguration so that changes made at runtime reach the simulation deterministically
+    fn on_collision_groups_changed(&self) {
+        if self.base().is_inside_tree() {
+            self.on_reconfigure();
+        }
+    }
+
+    /// Queues a reconfiguration when the physics material is swapped or edited
+    fn on_physics_material_changed(&self) {
+        if self.base().is_inside_tree() {
+            self.on_reconfigure();
+        }
+    }
+}
+
+macro_rules! impl_collidable {
+    ($t:ty) => {
+        impl Collidable for $t {
+            fn collision_layer_bits(&self) -> u32 {
+                self.collision_layer
+            }
+
+            fn collision_mask_bits(&self) -> u32 {
+                self.collision_mask
+            }
+        }
+    };
+    ($t:ty, body) => {
+        impl Collidable for $t {
+            fn collision_layer_bits(&self) -> u32 {
+                self.collision_layer
+            }
+
+            fn collision_mask_bits(&self) -> u32 {
+                self.collision_mask
+            }
+
+            fn contact_force_threshold(&self) -> Option<Real> {
+                match self.report_contact_forces {
+                    true => Some(self.contact_force_threshold),
+                    false => None,
+                }
+            }
+
+            fn physics_material(&self) -> Option<Gd<RapierPhysicsMaterial>> {
+                self.physics_material.clone()
+            }
+        }
+    };
+}
+
+impl_collidable!(RapierArea3D);
+impl_collidable!(RapierCollisionShape3D);
+impl_collidable!(RapierKinematicCharacter3D, body);
+impl_collidable!(RapierPIDCharacter3D, body);
+impl_collidable!(RapierRigidBody3D, body);
+impl_collidable!(RapierStaticBody3D, body);
@@ -5,16 +5,32 @@ use godot::prelude::*;
 
 pub trait Controllable: IRapierObject {
     fn on_move_by_amount(&self, amount: Vector3) {
-        if let Some(mut singleton) = get_singleton() {
-            let mut dict = Dictionary::new();
-            dict.set("movement", amount);
+        let mut dict = Dictionary::new();
+        dict.set("movement", amount);
+        self.queue_action(Operation::MoveNode, dict);
+    }
+
+    fn on_rotate_by(&self, rotation: Quaternion) {
+        let mut dict = Dictionary::new();
+        dict.set("rotation", rotation);
+        self.queue_action(Operation::RotateNode, dict);
+    }
 
+    fn on_set_facing(&self, direction: Vector3) {
+        let mut dict = Dictionary::new();
+        dict.set("facing", direction);
+        self.queue_action(Operation::RotateNode, dict);
+    }
+
+    /// Queues the action, it is applied during the next step
+    fn queue_action(&self, operation: Operation, data: Dictionary) {
+        if let Some(mut singleton) = get_singleton() {
             singleton.call_deferred(
                 "_ingest_action",
                 &[
                     self.to_gd().to_variant(),
-                    Operation::MoveNode.to_variant(),
-                    dict.to_variant(),
+                    operation.to_variant(),
+                    data.to_variant(),
                 ],
             );
         }
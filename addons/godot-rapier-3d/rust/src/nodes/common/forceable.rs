@@ -6,7 +6,7 @@ use super::super::{
 };
 use super::identifiable::Identifiable;
 use super::IRapierObject;
-use crate::interface::get_singleton;
+use crate::interface::{get_singleton, Operation};
 
 // Trait that applies to rigid bodies - can be affected by external forces, impulses etc.
 
@@ -15,8 +15,11 @@ pub trait Forceable: IRapierObject + Identifiable {
         if let Some(singleton) = get_singleton() {
             if let Some(raw_handle) = self.get_handle_raw() {
                 let handle = RigidBodyHandle::from_raw_parts(raw_handle.0, raw_handle.1);
-                if singleton.bind().world.physics.bodies.contains(handle) {
-                    let body = &singleton.bind().world.physics.bodies[handle];
+                let gr3d = singleton.bind();
+                let bodies = gr3d
+                    .get_world(self.get_world_id())
+                    .map(|w| &w.physics.bodies);
+                if let Some(body) = bodies.and_then(|bodies| bodies.get(handle)) {
                     let linvel = body.linvel();
                     let angvel = body.angvel();
                     return BodyState {
@@ -31,7 +34,72 @@ pub trait Forceable: IRapierObject + Identifiable {
         BodyState::empty()
     }
 
-    // TODO apply impulses, forces etc.
+    /// Applies an instantaneous impulse at the center of mass
+    fn on_apply_impulse(&self, impulse: Vector3) {
+        let mut dict = Dictionary::new();
+        dict.set("impulse", impulse);
+        self.ingest_force_action(Operation::ApplyImpulse, dict);
+    }
+
+    /// Applies an instantaneous impulse at the given world-space point
+    fn on_apply_impulse_at_point(&self, impulse: Vector3, point: Vector3) {
+        let mut dict = Dictionary::new();
+        dict.set("impulse", impulse);
+        dict.set("point", point);
+        self.ingest_force_action(Operation::ApplyImpulseAtPoint, dict);
+    }
+
+    /// Applies an instantaneous angular impulse
+    fn on_apply_torque_impulse(&self, torque: Vector3) {
+        let mut dict = Dictionary::new();
+        dict.set("torque", torque);
+        self.ingest_force_action(Operation::ApplyTorqueImpulse, dict);
+    }
+
+    /// Adds a force that keeps being applied every step until reset
+    fn on_add_force(&self, force: Vector3) {
+        let mut dict = Dictionary::new();
+        dict.set("force", force);
+        self.ingest_force_action(Operation::AddForce, dict);
+    }
+
+    /// Adds a torque that keeps being applied every step until reset
+    fn on_add_torque(&self, torque: Vector3) {
+        let mut dict = Dictionary::new();
+        dict.set("torque", torque);
+        self.ingest_force_action(Operation::AddTorque, dict);
+    }
+
+    /// Removes all forces and torques previously added
+    fn on_reset_forces(&self) {
+        self.ingest_force_action(Operation::ResetForces, Dictionary::new());
+    }
+
+    fn on_set_linear_velocity(&self, velocity: Vector3) {
+        let mut dict = Dictionary::new();
+        dict.set("velocity", velocity);
+        self.ingest_force_action(Operation::SetLinearVelocity, dict);
+    }
+
+    fn on_set_angular_velocity(&self, velocity: Vector3) {
+        let mut dict = Dictionary::new();
+        dict.set("velocity", velocity);
+        self.ingest_force_action(Operation::SetAngularVelocity, dict);
+    }
+
+    // Forces must go through the action queue so that they are replayed during rollback
+    fn ingest_force_action(&self, operation: Operation, data: Dictionary) {
+        if let Some(mut singleton) = get_singleton() {
+            singleton.call_deferred(
+                "_ingest_action",
+                &[
+                    self.to_gd().to_variant(),
+                    operation.to_variant(),
+                    data.to_variant(),
+                ],
+            );
+        }
+    }
 }
 
 macro_rules! impl_forceable {
@@ -44,6 +112,63 @@ impl_forceable!(RapierKinematicCharacter3D);
 impl_forceable!(RapierRigidBody3D);
 impl_forceable!(RapierPIDCharacter3D);
 
+// Exposes the forces to Godot, for the nodes simulated as dynamic bodies.
+// Invoked next to the node's primary #[godot_api] block, which secondary blocks must share a module with
+macro_rules! impl_force_funcs {
+    ($t:ty) => {
+        #[godot_api(secondary)]
+        impl $t {
+            #[func]
+            /// Applies an instantaneous impulse at the center of mass
+            fn apply_impulse(&self, impulse: Vector3) {
+                self.on_apply_impulse(impulse);
+            }
+
+            #[func]
+            /// Applies an instantaneous impulse at the given global position
+            fn apply_impulse_at_point(&self, impulse: Vector3, point: Vector3) {
+                self.on_apply_impulse_at_point(impulse, point);
+            }
+
+            #[func]
+            /// Applies an instantaneous angular impulse
+            fn apply_torque_impulse(&self, torque: Vector3) {
+                self.on_apply_torque_impulse(torque);
+            }
+
+            #[func]
+            /// Adds a constant force that is applied every step until `reset_forces` is called
+            fn add_force(&self, force: Vector3) {
+                self.on_add_force(force);
+            }
+
+            #[func]
+            /// Adds a constant torque that is applied every step until `reset_forces` is called
+            fn add_torque(&self, torque: Vector3) {
+                self.on_add_torque(torque);
+            }
+
+            #[func]
+            /// Removes all constant forces and torques
+            fn reset_forces(&self) {
+                self.on_reset_forces();
+            }
+
+            #[func]
+            fn set_linear_velocity(&self, velocity: Vector3) {
+                self.on_set_linear_velocity(velocity);
+            }
+
+            #[func]
+            fn set_angular_velocity(&self, velocity: Vector3) {
+                self.on_set_angular_velocity(velocity);
+            }
+        }
+    };
+}
+
+pub(crate) use impl_force_funcs;
+
 pub struct BodyState {
     pub linvel: Vector3,
     pub angvel: Vector3,
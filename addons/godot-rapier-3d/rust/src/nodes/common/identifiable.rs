@@ -2,8 +2,9 @@ use cuid2;
 use godot::prelude::*;
 
 use crate::nodes::{
-    RapierArea3D, RapierCollisionShape3D, RapierKinematicCharacter3D, RapierPIDCharacter3D,
-    RapierRigidBody3D, RapierStaticBody3D,
+    RapierArea3D, RapierCollisionShape3D, RapierFixedJoint3D, RapierKinematicCharacter3D,
+    RapierPIDCharacter3D, RapierPrismaticJoint3D, RapierRevoluteJoint3D, RapierRigidBody3D,
+    RapierRopeJoint3D, RapierSphericalJoint3D, RapierSpringJoint3D, RapierStaticBody3D,
 };
 
 pub trait Identifiable {
@@ -12,6 +13,9 @@ pub trait Identifiable {
     fn has_cuid(&self) -> bool;
     fn get_handle_raw(&self) -> Option<(u32, u32)>;
     fn set_handle_raw(&mut self, handle: (u32, u32));
+    /// The world the node was added to when it last entered the tree, or its world_id if it never did
+    fn get_world_id(&self) -> i64;
+    fn lock_world_id(&mut self);
 }
 
 macro_rules! impl_identifiable {
@@ -45,6 +49,18 @@ macro_rules! impl_identifiable {
             fn set_handle_raw(&mut self, handle: (u32, u32)) {
                 self.handle = Array::from(&[handle.0, handle.1]);
             }
+
+            fn get_world_id(&self) -> i64 {
+                match self.base().has_meta("entered_world_id") {
+                    true => i64::from_variant(&self.base().get_meta("entered_world_id")),
+                    false => self.world_id,
+                }
+            }
+
+            fn lock_world_id(&mut self) {
+                let world_id = self.world_id.to_variant();
+                self.base_mut().set_meta("entered_world_id", &world_id);
+            }
         }
     };
 }
@@ -55,6 +71,12 @@ impl_identifiable!(RapierCollisionShape3D);
 impl_identifiable!(RapierRigidBody3D);
 impl_identifiable!(RapierStaticBody3D);
 impl_identifiable!(RapierPIDCharacter3D);
+impl_identifiable!(RapierFixedJoint3D);
+impl_identifiable!(RapierRevoluteJoint3D);
+impl_identifiable!(RapierPrismaticJoint3D);
+impl_identifiable!(RapierSphericalJoint3D);
+impl_identifiable!(RapierRopeJoint3D);
+impl_identifiable!(RapierSpringJoint3D);
 
 pub fn generate_cuid() -> GString {
     cuid2::create_id().into()
@@ -0,0 +1,67 @@
+use godot::prelude::*;
+use rapier3d::prelude::GenericJoint;
+
+use super::super::{
+    RapierFixedJoint3D, RapierPrismaticJoint3D, RapierRevoluteJoint3D, RapierRopeJoint3D,
+    RapierSphericalJoint3D, RapierSpringJoint3D,
+};
+use super::IRapierObject;
+use crate::utils::{vector_to_point, vector_to_rapier};
+
+// Trait that applies to joint nodes - connects two bodies with a Rapier impulse joint
+
+pub const JOINT_CLASSES: [&str; 6] = [
+    "RapierFixedJoint3D",
+    "RapierRevoluteJoint3D",
+    "RapierPrismaticJoint3D",
+    "RapierSphericalJoint3D",
+    "RapierRopeJoint3D",
+    "RapierSpringJoint3D",
+];
+
+pub fn is_joint(node: &Gd<Node3D>) -> bool {
+    JOINT_CLASSES
+        .iter()
+        .any(|class| node.is_class(&GString::from(*class)))
+}
+
+pub trait Jointable: IRapierObject {
+    /// Paths to the two connected bodies, relative to the joint node
+    fn body_paths(&self) -> (NodePath, NodePath);
+
+    /// Builds the Rapier joint from the current node properties
+    fn build_joint(&self) -> GenericJoint;
+
+    /// Queues a reconfiguration so that changes made at runtime reach the simulation deterministically
+    fn on_joint_changed(&self) {
+        if self.base().is_inside_tree() {
+            self.on_reconfigure();
+        }
+    }
+}
+
+macro_rules! impl_jointable {
+    ($t:ty) => {
+        impl Jointable for $t {
+            fn body_paths(&self) -> (NodePath, NodePath) {
+                (self.body_a.clone(), self.body_b.clone())
+            }
+
+            fn build_joint(&self) -> GenericJoint {
+                self.joint_builder()
+                    .local_anchor1(vector_to_point(&vector_to_rapier(self.anchor_a)))
+                    .local_anchor2(vector_to_point(&vector_to_rapier(self.anchor_b)))
+                    .contacts_enabled(!self.exclude_nodes_from_collision)
+                    .build()
+                    .into()
+            }
+        }
+    };
+}
+
+impl_jointable!(RapierFixedJoint3D);
+impl_jointable!(RapierRevoluteJoint3D);
+impl_jointable!(RapierPrismaticJoint3D);
+impl_jointable!(RapierSphericalJoint3D);
+impl_jointable!(RapierRopeJoint3D);
+impl_jointable!(RapierSpringJoint3D);
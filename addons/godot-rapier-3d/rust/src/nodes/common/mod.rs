@@ -1,11 +1,16 @@
+mod collidable;
 mod controllable;
 mod forceable;
 mod identifiable;
+mod jointable;
 mod rapier_object;
 
 // Common functionality across all Godot x Rapier nodes goes in this module
 
+pub use collidable::{Collidable, ColliderSettings};
 pub use controllable::Controllable;
+pub(crate) use forceable::impl_force_funcs;
 pub use forceable::Forceable;
 pub use identifiable::{generate_cuid, Identifiable};
+pub use jointable::{is_joint, Jointable};
 pub use rapier_object::IRapierObject;
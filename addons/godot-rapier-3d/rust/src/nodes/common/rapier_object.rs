@@ -5,8 +5,9 @@ use rapier3d::prelude::RigidBodyHandle;
 use std::fmt;
 
 use super::super::{
-    pid_character::RapierPIDCharacter3D, RapierArea3D, RapierCollisionShape3D,
-    RapierKinematicCharacter3D, RapierRigidBody3D, RapierStaticBody3D,
+    pid_character::RapierPIDCharacter3D, RapierArea3D, RapierCollisionShape3D, RapierFixedJoint3D,
+    RapierKinematicCharacter3D, RapierPrismaticJoint3D, RapierRevoluteJoint3D, RapierRigidBody3D,
+    RapierRopeJoint3D, RapierSphericalJoint3D, RapierSpringJoint3D, RapierStaticBody3D,
 };
 use super::identifiable::Identifiable;
 use crate::interface::Operation;
@@ -29,6 +30,8 @@ pub trait IRapierObject: Identifiable + WithBaseField + GodotClass<Base = Node3D
     }
 
     fn on_enter_runtime_tree(&mut self) {
+        // Actions and reads keep going to this world until the node enters the tree again
+        self.lock_world_id();
         if let Some(mut singleton) = get_singleton() {
             singleton.call_deferred(
                 "_ingest_action",
@@ -50,6 +53,23 @@ pub trait IRapierObject: Identifiable + WithBaseField + GodotClass<Base = Node3D
         }
     }
 
+    fn on_reconfigure(&self) {
+        if Engine::singleton().is_editor_hint() {
+            return;
+        }
+
+        if let Some(mut singleton) = get_singleton() {
+            singleton.call_deferred(
+                "_ingest_action",
+                &[
+                    self.base().to_variant(),
+                    Operation::ConfigureNode.to_variant(),
+                    Dictionary::new().to_variant(),
+                ],
+            );
+        }
+    }
+
     fn on_exit_tree(&mut self) {
         match Engine::singleton().is_editor_hint() {
             true => (),
@@ -74,7 +94,12 @@ pub trait IRapierObject: Identifiable + WithBaseField + GodotClass<Base = Node3D
     fn sync(&mut self) {
         if let Some(singleton) = get_singleton() {
             if let Some(raw_handle) = self.get_handle_raw() {
-                let physics = &singleton.bind().world.physics;
+                let world_id = self.get_world_id();
+                let gr3d = singleton.bind();
+                let Some(world) = gr3d.get_world(world_id) else {
+                    return;
+                };
+                let physics = &world.physics;
                 let mut node = self.base_mut();
                 let class = node.get_class();
                 match class.to_string().as_str() {
@@ -115,3 +140,9 @@ impl_irapier_object!(RapierCollisionShape3D);
 impl_irapier_object!(RapierRigidBody3D);
 impl_irapier_object!(RapierStaticBody3D);
 impl_irapier_object!(RapierPIDCharacter3D);
+impl_irapier_object!(RapierFixedJoint3D);
+impl_irapier_object!(RapierRevoluteJoint3D);
+impl_irapier_object!(RapierPrismaticJoint3D);
+impl_irapier_object!(RapierSphericalJoint3D);
+impl_irapier_object!(RapierRopeJoint3D);
+impl_irapier_object!(RapierSpringJoint3D);
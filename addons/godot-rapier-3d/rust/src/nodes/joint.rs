@@ -0,0 +1,495 @@
+use godot::classes::{INode3D, Node3D};
+use godot::prelude::*;
+use rapier3d::math::UnitVector;
+use rapier3d::prelude::{
+    FixedJointBuilder, PrismaticJointBuilder, RevoluteJointBuilder, RopeJointBuilder,
+    SphericalJointBuilder, SpringJointBuilder,
+};
+
+use super::common::Jointable;
+use crate::nodes::IRapierObject;
+use crate::utils::vector_to_rapier;
+
+/*
+    Joint nodes connect two bodies (body_a and body_b) with a Rapier impulse joint
+
+    Anchors are expressed in the local space of their body
+*/
+
+/// Axis used when the exported axis is zero
+fn axis_or(axis: Vector3, fallback: Vector3) -> UnitVector<f32> {
+    match axis.length_squared() > 0.0 {
+        true => UnitVector::new_normalize(vector_to_rapier(axis)),
+        false => UnitVector::new_normalize(vector_to_rapier(fallback)),
+    }
+}
+
+#[derive(GodotClass)]
+#[class(tool, base=Node3D)]
+/// Locks all relative motion between two bodies
+pub struct RapierFixedJoint3D {
+    #[var(usage_flags = [EDITOR, STORAGE, READ_ONLY])]
+    pub handle: Array<u32>,
+    #[export]
+    /// The physics world this node is added to when it enters the tree, see GR3D.create_world
+    pub world_id: i64,
+    #[export]
+    pub body_a: NodePath,
+    #[export]
+    pub body_b: NodePath,
+    #[export]
+    /// Attachment point in the local space of body_a
+    pub anchor_a: Vector3,
+    #[export]
+    /// Attachment point in the local space of body_b
+    pub anchor_b: Vector3,
+    #[export]
+    /// Disable collisions between the two connected bodies
+    pub exclude_nodes_from_collision: bool,
+    base: Base<Node3D>,
+}
+
+#[godot_api]
+impl INode3D for RapierFixedJoint3D {
+    fn init(base: Base<Node3D>) -> Self {
+        Self {
+            handle: Array::new(),
+            world_id: 0,
+            body_a: NodePath::default(),
+            body_b: NodePath::default(),
+            anchor_a: Vector3::ZERO,
+            anchor_b: Vector3::ZERO,
+            exclude_nodes_from_collision: true,
+            base,
+        }
+    }
+
+    fn enter_tree(&mut self) {
+        self.on_enter_tree();
+    }
+
+    fn exit_tree(&mut self) {
+        self.on_exit_tree();
+    }
+}
+
+impl RapierFixedJoint3D {
+    pub fn joint_builder(&self) -> FixedJointBuilder {
+        FixedJointBuilder::new()
+    }
+}
+
+#[derive(GodotClass)]
+#[class(tool, base=Node3D)]
+/// Allows rotation of two bodies around a single axis (hinge)
+pub struct RapierRevoluteJoint3D {
+    #[var(usage_flags = [EDITOR, STORAGE, READ_ONLY])]
+    pub handle: Array<u32>,
+    #[export]
+    /// The physics world this node is added to when it enters the tree, see GR3D.create_world
+    pub world_id: i64,
+    #[export]
+    pub body_a: NodePath,
+    #[export]
+    pub body_b: NodePath,
+    #[export]
+    /// Attachment point in the local space of body_a
+    pub anchor_a: Vector3,
+    #[export]
+    /// Attachment point in the local space of body_b
+    pub anchor_b: Vector3,
+    #[export]
+    /// Disable collisions between the two connected bodies
+    pub exclude_nodes_from_collision: bool,
+    #[export]
+    /// Rotation axis, in the local space of both bodies
+    pub axis: Vector3,
+    #[export]
+    pub limits_enabled: bool,
+    #[export]
+    /// Minimum angle in radians
+    pub lower_limit: f32,
+    #[export]
+    /// Maximum angle in radians
+    pub upper_limit: f32,
+    #[export]
+    pub motor_enabled: bool,
+    #[export]
+    #[var(get, set = set_motor_target_velocity)]
+    /// Target angular velocity in radians per second
+    pub motor_target_velocity: f32,
+    #[export]
+    #[var(get, set = set_motor_target_position)]
+    /// Target angle in radians, only used when motor_stiffness > 0
+    pub motor_target_position: f32,
+    #[export]
+    pub motor_stiffness: f32,
+    #[export]
+    pub motor_damping: f32,
+    #[export]
+    pub motor_max_force: f32,
+    base: Base<Node3D>,
+}
+
+#[godot_api]
+impl INode3D for RapierRevoluteJoint3D {
+    fn init(base: Base<Node3D>) -> Self {
+        Self {
+            handle: Array::new(),
+            world_id: 0,
+            body_a: NodePath::default(),
+            body_b: NodePath::default(),
+            anchor_a: Vector3::ZERO,
+            anchor_b: Vector3::ZERO,
+            exclude_nodes_from_collision: true,
+            axis: Vector3::UP,
+            limits_enabled: false,
+            lower_limit: -std::f32::consts::PI,
+            upper_limit: std::f32::consts::PI,
+            motor_enabled: false,
+            motor_target_velocity: 0.0,
+            motor_target_position: 0.0,
+            motor_stiffness: 0.0,
+            motor_damping: 1.0,
+            motor_max_force: f32::MAX,
+            base,
+        }
+    }
+
+    fn enter_tree(&mut self) {
+        self.on_enter_tree();
+    }
+
+    fn exit_tree(&mut self) {
+        self.on_exit_tree();
+    }
+}
+
+#[godot_api]
+impl RapierRevoluteJoint3D {
+    #[func]
+    fn set_motor_target_velocity(&mut self, velocity: f32) {
+        self.motor_target_velocity = velocity;
+        self.on_joint_changed();
+    }
+
+    #[func]
+    fn set_motor_target_position(&mut self, position: f32) {
+        self.motor_target_position = position;
+        self.on_joint_changed();
+    }
+
+    pub fn joint_builder(&self) -> RevoluteJointBuilder {
+        let mut builder = RevoluteJointBuilder::new(axis_or(self.axis, Vector3::UP));
+        if self.limits_enabled {
+            builder = builder.limits([self.lower_limit, self.upper_limit]);
+        }
+        if self.motor_enabled {
+            builder = builder
+                .motor(
+                    self.motor_target_position,
+                    self.motor_target_velocity,
+                    self.motor_stiffness,
+                    self.motor_damping,
+                )
+                .motor_max_force(self.motor_max_force);
+        }
+        builder
+    }
+}
+
+#[derive(GodotClass)]
+#[class(tool, base=Node3D)]
+/// Allows translation of two bodies along a single axis (slider)
+pub struct RapierPrismaticJoint3D {
+    #[var(usage_flags = [EDITOR, STORAGE, READ_ONLY])]
+    pub handle: Array<u32>,
+    #[export]
+    /// The physics world this node is added to when it enters the tree, see GR3D.create_world
+    pub world_id: i64,
+    #[export]
+    pub body_a: NodePath,
+    #[export]
+    pub body_b: NodePath,
+    #[export]
+    /// Attachment point in the local space of body_a
+    pub anchor_a: Vector3,
+    #[export]
+    /// Attachment point in the local space of body_b
+    pub anchor_b: Vector3,
+    #[export]
+    /// Disable collisions between the two connected bodies
+    pub exclude_nodes_from_collision: bool,
+    #[export]
+    /// Translation axis, in the local space of both bodies
+    pub axis: Vector3,
+    #[export]
+    pub limits_enabled: bool,
+    #[export]
+    /// Minimum distance along the axis
+    pub lower_limit: f32,
+    #[export]
+    /// Maximum distance along the axis
+    pub upper_limit: f32,
+    #[export]
+    pub motor_enabled: bool,
+    #[export]
+    #[var(get, set = set_motor_target_velocity)]
+    /// Target velocity along the axis
+    pub motor_target_velocity: f32,
+    #[export]
+    #[var(get, set = set_motor_target_position)]
+    /// Target distance along the axis, only used when motor_stiffness > 0
+    pub motor_target_position: f32,
+    #[export]
+    pub motor_stiffness: f32,
+    #[export]
+    pub motor_damping: f32,
+    #[export]
+    pub motor_max_force: f32,
+    base: Base<Node3D>,
+}
+
+#[godot_api]
+impl INode3D for RapierPrismaticJoint3D {
+    fn init(base: Base<Node3D>) -> Self {
+        Self {
+            handle: Array::new(),
+            world_id: 0,
+            body_a: NodePath::default(),
+            body_b: NodePath::default(),
+            anchor_a: Vector3::ZERO,
+            anchor_b: Vector3::ZERO,
+            exclude_nodes_from_collision: true,
+            axis: Vector3::RIGHT,
+            limits_enabled: false,
+            lower_limit: -1.0,
+            upper_limit: 1.0,
+            motor_enabled: false,
+            motor_target_velocity: 0.0,
+            motor_target_position: 0.0,
+            motor_stiffness: 0.0,
+            motor_damping: 1.0,
+            motor_max_force: f32::MAX,
+            base,
+        }
+    }
+
+    fn enter_tree(&mut self) {
+        self.on_enter_tree();
+    }
+
+    fn exit_tree(&mut self) {
+        self.on_exit_tree();
+    }
+}
+
+#[godot_api]
+impl RapierPrismaticJoint3D {
+    #[func]
+    fn set_motor_target_velocity(&mut self, velocity: f32) {
+        self.motor_target_velocity = velocity;
+        self.on_joint_changed();
+    }
+
+    #[func]
+    fn set_motor_target_position(&mut self, position: f32) {
+        self.motor_target_position = position;
+        self.on_joint_changed();
+    }
+
+    pub fn joint_builder(&self) -> PrismaticJointBuilder {
+        let mut builder = PrismaticJointBuilder::new(axis_or(self.axis, Vector3::RIGHT));
+        if self.limits_enabled {
+            builder = builder.limits([self.lower_limit, self.upper_limit]);
+        }
+        if self.motor_enabled {
+            // The prismatic builder has no combined position + velocity motor setter
+            builder.0.set_motor(
+                self.motor_target_position,
+                self.motor_target_velocity,
+                self.motor_stiffness,
+                self.motor_damping,
+            );
+            builder = builder.motor_max_force(self.motor_max_force);
+        }
+        builder
+    }
+}
+
+#[derive(GodotClass)]
+#[class(tool, base=Node3D)]
+/// Allows free rotation of two bodies around the anchor point (ball and socket)
+pub struct RapierSphericalJoint3D {
+    #[var(usage_flags = [EDITOR, STORAGE, READ_ONLY])]
+    pub handle: Array<u32>,
+    #[export]
+    /// The physics world this node is added to when it enters the tree, see GR3D.create_world
+    pub world_id: i64,
+    #[export]
+    pub body_a: NodePath,
+    #[export]
+    pub body_b: NodePath,
+    #[export]
+    /// Attachment point in the local space of body_a
+    pub anchor_a: Vector3,
+    #[export]
+    /// Attachment point in the local space of body_b
+    pub anchor_b: Vector3,
+    #[export]
+    /// Disable collisions between the two connected bodies
+    pub exclude_nodes_from_collision: bool,
+    base: Base<Node3D>,
+}
+
+#[godot_api]
+impl INode3D for RapierSphericalJoint3D {
+    fn init(base: Base<Node3D>) -> Self {
+        Self {
+            handle: Array::new(),
+            world_id: 0,
+            body_a: NodePath::default(),
+            body_b: NodePath::default(),
+            anchor_a: Vector3::ZERO,
+            anchor_b: Vector3::ZERO,
+            exclude_nodes_from_collision: true,
+            base,
+        }
+    }
+
+    fn enter_tree(&mut self) {
+        self.on_enter_tree();
+    }
+
+    fn exit_tree(&mut self) {
+        self.on_exit_tree();
+    }
+}
+
+impl RapierSphericalJoint3D {
+    pub fn joint_builder(&self) -> SphericalJointBuilder {
+        SphericalJointBuilder::new()
+    }
+}
+
+#[derive(GodotClass)]
+#[class(tool, base=Node3D)]
+/// Keeps the anchors of two bodies within a maximum distance of each other
+pub struct RapierRopeJoint3D {
+    #[var(usage_flags = [EDITOR, STORAGE, READ_ONLY])]
+    pub handle: Array<u32>,
+    #[export]
+    /// The physics world this node is added to when it enters the tree, see GR3D.create_world
+    pub world_id: i64,
+    #[export]
+    pub body_a: NodePath,
+    #[export]
+    pub body_b: NodePath,
+    #[export]
+    /// Attachment point in the local space of body_a
+    pub anchor_a: Vector3,
+    #[export]
+    /// Attachment point in the local space of body_b
+    pub anchor_b: Vector3,
+    #[export]
+    /// Disable collisions between the two connected bodies
+    pub exclude_nodes_from_collision: bool,
+    #[export]
+    pub max_distance: f32,
+    base: Base<Node3D>,
+}
+
+#[godot_api]
+impl INode3D for RapierRopeJoint3D {
+    fn init(base: Base<Node3D>) -> Self {
+        Self {
+            handle: Array::new(),
+            world_id: 0,
+            body_a: NodePath::default(),
+            body_b: NodePath::default(),
+            anchor_a: Vector3::ZERO,
+            anchor_b: Vector3::ZERO,
+            exclude_nodes_from_collision: true,
+            max_distance: 1.0,
+            base,
+        }
+    }
+
+    fn enter_tree(&mut self) {
+        self.on_enter_tree();
+    }
+
+    fn exit_tree(&mut self) {
+        self.on_exit_tree();
+    }
+}
+
+impl RapierRopeJoint3D {
+    pub fn joint_builder(&self) -> RopeJointBuilder {
+        RopeJointBuilder::new(self.max_distance)
+    }
+}
+
+#[derive(GodotClass)]
+#[class(tool, base=Node3D)]
+/// Pulls the anchors of two bodies towards a rest length
+pub struct RapierSpringJoint3D {
+    #[var(usage_flags = [EDITOR, STORAGE, READ_ONLY])]
+    pub handle: Array<u32>,
+    #[export]
+    /// The physics world this node is added to when it enters the tree, see GR3D.create_world
+    pub world_id: i64,
+    #[export]
+    pub body_a: NodePath,
+    #[export]
+    pub body_b: NodePath,
+    #[export]
+    /// Attachment point in the local space of body_a
+    pub anchor_a: Vector3,
+    #[export]
+    /// Attachment point in the local space of body_b
+    pub anchor_b: Vector3,
+    #[export]
+    /// Disable collisions between the two connected bodies
+    pub exclude_nodes_from_collision: bool,
+    #[export]
+    pub rest_length: f32,
+    #[export]
+    pub stiffness: f32,
+    #[export]
+    pub damping: f32,
+    base: Base<Node3D>,
+}
+
+#[godot_api]
+impl INode3D for RapierSpringJoint3D {
+    fn init(base: Base<Node3D>) -> Self {
+        Self {
+            handle: Array::new(),
+            world_id: 0,
+            body_a: NodePath::default(),
+            body_b: NodePath::default(),
+            anchor_a: Vector3::ZERO,
+            anchor_b: Vector3::ZERO,
+            exclude_nodes_from_collision: true,
+            rest_length: 1.0,
+            stiffness: 10.0,
+            damping: 1.0,
+            base,
+        }
+    }
+
+    fn enter_tree(&mut self) {
+        self.on_enter_tree();
+    }
+
+    fn exit_tree(&mut self) {
+        self.on_exit_tree();
+    }
+}
+
+impl RapierSpringJoint3D {
+    pub fn joint_builder(&self) -> SpringJointBuilder {
+        SpringJointBuilder::new(self.rest_length, self.stiffness, self.damping)
+    }
+}
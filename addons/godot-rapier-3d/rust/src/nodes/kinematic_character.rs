@@ -2,12 +2,18 @@ use godot::classes::notify::Node3DNotification;
 use godot::classes::{INode3D, Node3D};
 use godot::prelude::*;
 use rapier3d::control::{
-    CharacterCollision, EffectiveCharacterMovement, KinematicCharacterController,
+    CharacterAutostep, CharacterCollision, CharacterLength, EffectiveCharacterMovement,
+    KinematicCharacterController,
 };
 
-use super::common::{Controllable, Forceable};
+use super::common::{Collidable, Controllable, Forceable, Identifiable};
+use super::RapierKinematicCollision3D;
+use crate::interface::get_singleton;
 use crate::nodes::IRapierObject;
+use crate::resources::{watch_physics_material, RapierPhysicsMaterial};
 use crate::utils::vector_to_godot;
+use crate::world::character::{floor_normal, PlatformLeave, PlatformState};
+use crate::world::events::EventCollider;
 
 /*
     I had some issues with jittering when desired_movement is pushing into the floor.
@@ -15,11 +21,49 @@ use crate::utils::vector_to_godot;
     https://github.com/dimforge/rapier/issues/809
 */
 
+#[derive(GodotConvert, Var, Export, Debug, Clone, Copy, Default, PartialEq)]
+#[godot(via = GString)]
+/// How lengths of the character controller are expressed
+pub enum LengthMode {
+    #[default]
+    Absolute, // In meters
+    Relative, // In multiples of the character shape's size (height for heights, width for widths)
+}
+
+#[derive(GodotConvert, Var, Export, Debug, Clone, Copy, Default, PartialEq)]
+#[godot(via = GString)]
+/// How the character interacts with the surfaces it hits, like CharacterBody3D.motion_mode
+pub enum MotionMode {
+    #[default]
+    Grounded, // Walks on floors, snaps to the ground and climbs slopes and steps
+    Floating, // Has no floor, e.g. to fly or swim. Every surface is a wall
+}
+
+#[derive(GodotConvert, Var, Export, Debug, Clone, Copy, Default, PartialEq)]
+#[godot(via = GString)]
+/// What the character does with the velocity of the platform it leaves, like CharacterBody3D.platform_on_leave
+pub enum PlatformOnLeave {
+    #[default]
+    AddVelocity,
+    AddUpwardVelocity,
+    DoNothing,
+}
+
+/// A collision gathered while moving, with the UIDs of the hit collider resolved while its handle was valid
+#[derive(Clone, Debug)]
+pub struct SlideCollision {
+    pub collision: CharacterCollision,
+    pub collider: Option<EventCollider>,
+}
+
 #[derive(GodotClass)]
 #[class(tool, base=Node3D)]
 pub struct RapierKinematicCharacter3D {
     #[var(usage_flags = [EDITOR, STORAGE, READ_ONLY])]
     pub handle: Array<u32>,
+    #[export]
+    /// The physics world this node is added to when it enters the tree, see GR3D.create_world
+    pub world_id: i64,
 
     // bool floor_block_on_wall = true
     // bool floor_constant_speed = false // IMPORTANT
@@ -33,7 +77,10 @@ pub struct RapierKinematicCharacter3D {
     normal_nudge_factor: f32,
     // bool floor_stop_on_slope = true // unsure how to do
     // max_slides: i32, // can't do?
-    // MotionMode motion_mode = 0 (grounded vs flying characters) // nice to have but tricky
+    #[export]
+    #[var(get, set = set_motion_mode)]
+    /// Floating characters ignore the floor, slope and autostep settings
+    pub motion_mode: MotionMode,
     #[export]
     safe_margin: f32,
     #[export]
@@ -42,12 +89,49 @@ pub struct RapierKinematicCharacter3D {
     #[export]
     slide: bool,
 
+    #[export]
+    /// Tilts the character so that its up axis follows the floor normal, and back to up_direction while airborne
+    pub align_to_floor: bool,
+
+    #[export]
+    /// Velocity added to the character's movement when it jumps or falls off a moving platform, until it lands
+    platform_on_leave: PlatformOnLeave,
+
+    #[export]
+    /// Highest step the character climbs automatically, 0 disables stepping
+    autostep_max_height: f32,
+    #[export]
+    /// Free space needed on top of a step for the character to climb it
+    autostep_min_width: f32,
+    #[export]
+    /// Whether the character can also step onto dynamic bodies
+    autostep_include_dynamic_bodies: bool,
+    #[export]
+    /// Whether autostep_max_height and autostep_min_width are in meters or relative to the character's shape
+    autostep_length_mode: LengthMode,
+
     pub last_movement: Option<EffectiveCharacterMovement>,
-    pub last_collisions: Vec<CharacterCollision>,
+    pub last_collisions: Vec<SlideCollision>,
+    pub stepped_up: bool,
     pub controller: KinematicCharacterController,
 
-    // rapier specific settings
-    // autostep: Option<CharacterAutostep> nice to have but expensive performance apparently
+    #[export(flags_3d_physics)]
+    #[var(get, set = set_collision_layer)]
+    /// The physics layers this body is in. Collision shapes may override this
+    pub collision_layer: u32,
+    #[export(flags_3d_physics)]
+    #[var(get, set = set_collision_mask)]
+    /// The physics layers this body scans for collisions. Collision shapes may override this
+    pub collision_mask: u32,
+    #[export]
+    /// Emit `contact_force` signals when the contact force with another body exceeds the threshold
+    pub report_contact_forces: bool,
+    #[export]
+    pub contact_force_threshold: f32,
+    #[export]
+    #[var(get, set = set_physics_material)]
+    /// Friction, restitution and density of this body's colliders. Collision shapes may override this
+    pub physics_material: Option<Gd<RapierPhysicsMaterial>>,
     base: Base<Node3D>,
 }
 
@@ -56,16 +140,30 @@ impl INode3D for RapierKinematicCharacter3D {
     fn init(base: Base<Node3D>) -> Self {
         Self {
             handle: Array::new(),
+            world_id: 0,
             floor_max_angle: 0.7853982,       // (45 degrees in radians)
             floor_min_slide_angle: 0.7853982, // (45 degrees in radians)
             floor_snap_length: 0.2,
             normal_nudge_factor: 0.0001,
+            motion_mode: MotionMode::Grounded,
             safe_margin: 0.01,
             up_direction: Vector3::UP,
             slide: true,
+            align_to_floor: false,
+            platform_on_leave: PlatformOnLeave::AddVelocity,
+            autostep_max_height: 0.0,
+            autostep_min_width: 0.2,
+            autostep_include_dynamic_bodies: false,
+            autostep_length_mode: LengthMode::Absolute,
             last_movement: None,
             last_collisions: Vec::new(),
+            stepped_up: false,
             controller: KinematicCharacterController::default(),
+            collision_layer: 1,
+            collision_mask: 1,
+            report_contact_forces: false,
+            contact_force_threshold: 0.0,
+            physics_material: None,
             base,
         }
     }
@@ -97,11 +195,55 @@ impl RapierKinematicCharacter3D {
         self.sync()
     }
 
+    #[func]
+    fn set_collision_layer(&mut self, layer: u32) {
+        self.collision_layer = layer;
+        self.on_collision_groups_changed();
+    }
+
+    #[func]
+    fn set_collision_mask(&mut self, mask: u32) {
+        self.collision_mask = mask;
+        self.on_collision_groups_changed();
+    }
+
+    #[func]
+    fn set_motion_mode(&mut self, mode: MotionMode) {
+        self.motion_mode = mode;
+        if self.base().is_inside_tree() {
+            self.on_reconfigure();
+        }
+    }
+
+    #[func]
+    fn set_physics_material(&mut self, material: Option<Gd<RapierPhysicsMaterial>>) {
+        watch_physics_material(&self.to_gd().upcast(), &self.physics_material, &material);
+        self.physics_material = material;
+        self.on_physics_material_changed();
+    }
+
+    #[func]
+    fn _on_physics_material_changed(&self) {
+        self.on_physics_material_changed();
+    }
+
     #[func]
     fn move_by_amount(&self, amount: Vector3) {
         self.on_move_by_amount(amount);
     }
 
+    #[func]
+    /// Rotates the character by the given rotation, in world space
+    fn rotate_by(&self, rotation: Quaternion) {
+        self.on_rotate_by(rotation);
+    }
+
+    #[func]
+    /// Turns the character around its up axis until its forward axis (-Z) points in the given direction
+    fn set_facing(&self, direction: Vector3) {
+        self.on_set_facing(direction);
+    }
+
     #[func]
     fn get_real_velocity(&self) -> Vector3 {
         self.get_body_state().linvel
@@ -138,6 +280,22 @@ impl RapierKinematicCharacter3D {
         }
     }
 
+    #[func]
+    /// Velocity of the moving body the character stands on, where it stands.
+    /// After leaving the platform, its velocity when the character left it
+    fn get_platform_velocity(&self) -> Vector3 {
+        match self.platform() {
+            Some(platform) => vector_to_godot(platform.velocity),
+            None => Vector3::ZERO,
+        }
+    }
+
+    #[func]
+    /// Whether the character climbed a step during its last movement
+    fn did_step_up(&self) -> bool {
+        self.stepped_up
+    }
+
     #[func]
     fn get_slide_collision_count(&self) -> i32 {
         self.last_collisions.len() as i32
@@ -151,8 +309,117 @@ impl RapierKinematicCharacter3D {
         }
     }
 
+    #[func]
+    /// Normal of the floor the character is standing on, or zero if it is not on the floor.
+    /// The up direction is returned if the floor was not hit during the last movement, e.g. when standing still
+    fn get_floor_normal(&self) -> Vector3 {
+        if !self.is_on_floor() {
+            return Vector3::ZERO;
+        }
+        let collisions = self.last_collisions.iter().map(|slide| &slide.collision);
+        vector_to_godot(floor_normal(&self.controller, collisions))
+    }
+
+    #[func]
+    /// Angle between the floor normal and the given up direction in radians, or zero if the character is not on the floor
+    fn get_floor_angle(&self, up_direction: Vector3) -> f32 {
+        match self.is_on_floor() {
+            true => self.get_floor_normal().angle_to(up_direction),
+            false => 0.0,
+        }
+    }
+
+    #[func]
+    /// One of the collisions of the last movement, from 0 to get_slide_collision_count() - 1
+    fn get_slide_collision(&self, index: i32) -> Option<Gd<RapierKinematicCollision3D>> {
+        let collision = usize::try_from(index)
+            .ok()
+            .and_then(|index| self.last_collisions.get(index));
+        match collision {
+            Some(collision) => Some(self.to_kinematic_collision(collision)),
+            None => {
+                log::error!(
+                    "Slide collision index {} out of range, the last movement had {} collisions",
+                    index,
+                    self.last_collisions.len()
+                );
+                None
+            }
+        }
+    }
+
+    #[func]
+    /// The last collision of the last movement, or null if it had none
+    fn get_last_slide_collision(&self) -> Option<Gd<RapierKinematicCollision3D>> {
+        self.last_collisions
+            .last()
+            .map(|collision| self.to_kinematic_collision(collision))
+    }
+
     // void apply_floor_snap() // won't do - unimportant?
-    // float get_floor_angle(up_direction: Vector3 = Vector3(0, 1, 0)) // IMPORTANT
-    // Vector3 get_floor_normal() // IMPORTANT
-    // KinematicCollision3D get_last_slide_collision() // useful but tricky
+
+    #[signal]
+    /// Emitted when this body starts touching another body
+    fn body_entered(body: Gd<Node3D>);
+
+    #[signal]
+    /// Emitted when this body stops touching another body
+    fn body_exited(body: Gd<Node3D>);
+
+    #[signal]
+    /// Emitted when a collision shape of another body starts touching a collision shape of this body
+    fn contact_started(collider: Gd<Node3D>, local_collider: Gd<Node3D>);
+
+    #[signal]
+    /// Emitted when a collision shape of another body stops touching a collision shape of this body
+    fn contact_ended(collider: Gd<Node3D>, local_collider: Gd<Node3D>);
+
+    #[signal]
+    /// Emitted every step the contact force with another body exceeds `contact_force_threshold`
+    fn contact_force(body: Gd<Node3D>, total_force: Vector3);
+}
+
+impl RapierKinematicCharacter3D {
+    pub fn platform_leave(&self) -> PlatformLeave {
+        match self.platform_on_leave {
+            PlatformOnLeave::AddVelocity => PlatformLeave::AddVelocity,
+            PlatformOnLeave::AddUpwardVelocity => PlatformLeave::AddUpwardVelocity,
+            PlatformOnLeave::DoNothing => PlatformLeave::DoNothing,
+        }
+    }
+
+    /// Autostep settings of the controller, None if disabled
+    pub fn get_autostep(&self) -> Option<CharacterAutostep> {
+        if self.autostep_max_height <= 0.0 {
+            return None;
+        }
+        let length = |value: f32| match self.autostep_length_mode {
+            LengthMode::Absolute => CharacterLength::Absolute(value),
+            LengthMode::Relative => CharacterLength::Relative(value),
+        };
+        Some(CharacterAutostep {
+            max_height: length(self.autostep_max_height),
+            min_width: length(self.autostep_min_width.max(0.0)),
+            include_dynamic_bodies: self.autostep_include_dynamic_bodies,
+        })
+    }
+
+    /// State of the platform under the character, kept by the world so that snapshots include it
+    fn platform(&self) -> Option<PlatformState> {
+        let singleton = get_singleton()?;
+        let raw = self.get_handle_raw()?;
+        let gr3d = singleton.bind();
+        let world = gr3d.get_world(self.get_world_id())?;
+        world.physics.character_platforms.get(&raw).cloned()
+    }
+
+    fn to_kinematic_collision(&self, slide: &SlideCollision) -> Gd<RapierKinematicCollision3D> {
+        let owner = match (get_singleton(), &slide.collider) {
+            (Some(singleton), Some(collider)) => singleton
+                .bind()
+                .get_collider_owner(self.get_world_id(), collider),
+            _ => None,
+        };
+        RapierKinematicCollision3D::from_collision(&slide.collision, owner)
+    }
 }
@@ -0,0 +1,100 @@
+use godot::classes::RefCounted;
+use godot::prelude::*;
+use rapier3d::control::CharacterCollision;
+
+use crate::interface::ColliderOwner;
+use crate::utils::vector_to_godot;
+
+/*
+    Result of RapierKinematicCharacter3D.get_slide_collision, describing one of the hits
+    of the character's last movement. Values are in world space
+*/
+
+#[derive(GodotClass)]
+#[class(no_init, base=RefCounted)]
+pub struct RapierKinematicCollision3D {
+    collider: Option<Gd<Node3D>>,
+    collider_shape: Option<Gd<Node3D>>,
+    position: Vector3,
+    normal: Vector3,
+    toi: f32,
+    translation_applied: Vector3,
+    translation_remaining: Vector3,
+    base: Base<RefCounted>,
+}
+
+impl RapierKinematicCollision3D {
+    /// The owner is None if the hit collider has been removed since the movement
+    pub fn from_collision(
+        collision: &CharacterCollision,
+        owner: Option<ColliderOwner>,
+    ) -> Gd<Self> {
+        let (collider, collider_shape) = match owner {
+            Some(owner) => (Some(owner.node), Some(owner.collider)),
+            None => (None, None),
+        };
+
+        // The character is the cast shape, so witness and normal 1 belong to the hit collider
+        Gd::from_init_fn(|base| Self {
+            collider,
+            collider_shape,
+            position: vector_to_godot(collision.hit.witness1.coords),
+            normal: vector_to_godot(collision.hit.normal1.into_inner()),
+            toi: collision.hit.time_of_impact,
+            translation_applied: vector_to_godot(collision.translation_applied),
+            translation_remaining: vector_to_godot(collision.translation_remaining),
+            base,
+        })
+    }
+}
+
+#[godot_api]
+impl RapierKinematicCollision3D {
+    #[func]
+    /// The body or area owning the hit collision shape
+    fn get_collider(&self) -> Option<Gd<Node3D>> {
+        self.collider.clone()
+    }
+
+    #[func]
+    /// The hit collision shape
+    fn get_collider_shape(&self) -> Option<Gd<Node3D>> {
+        self.collider_shape.clone()
+    }
+
+    #[func]
+    /// The point of contact, on the surface of the hit collider
+    fn get_position(&self) -> Vector3 {
+        self.position
+    }
+
+    #[func]
+    /// The normal of the hit collider's surface at the point of contact
+    fn get_normal(&self) -> Vector3 {
+        self.normal
+    }
+
+    #[func]
+    /// The angle between the normal and the given up direction, in radians
+    fn get_angle(&self, up_direction: Vector3) -> f32 {
+        self.normal.angle_to(up_direction)
+    }
+
+    #[func]
+    /// Distance the character travelled in the direction of the hit before touching the collider
+    fn get_toi(&self) -> f32 {
+        self.toi
+    }
+
+    #[func]
+    /// The part of the movement that had been applied when the hit happened
+    fn get_translation_applied(&self) -> Vector3 {
+        self.translation_applied
+    }
+
+    #[func]
+    /// The part of the movement that was left to apply when the hit happened
+    fn get_translation_remaining(&self) -> Vector3 {
+        self.translation_remaining
+    }
+}
@@ -1,15 +1,24 @@
 mod area;
 mod collision_shape;
 mod common;
+mod joint;
 mod kinematic_character;
+mod kinematic_collision;
 mod pid_character;
 mod rigid_body;
 mod static_body;
 
 pub use area::RapierArea3D;
 pub use collision_shape::RapierCollisionShape3D;
-pub use common::{generate_cuid, IRapierObject, Identifiable};
-pub use kinematic_character::RapierKinematicCharacter3D;
+pub use common::{
+    generate_cuid, is_joint, Collidable, ColliderSettings, IRapierObject, Identifiable, Jointable,
+};
+pub use joint::{
+    RapierFixedJoint3D, RapierPrismaticJoint3D, RapierRevoluteJoint3D, RapierRopeJoint3D,
+    RapierSphericalJoint3D, RapierSpringJoint3D,
+};
+pub use kinematic_character::{MotionMode, RapierKinematicCharacter3D, SlideCollision};
+pub use kinematic_collision::RapierKinematicCollision3D;
 pub use pid_character::RapierPIDCharacter3D;
 pub use rigid_body::RapierRigidBody3D;
 pub use static_body::RapierStaticBody3D;
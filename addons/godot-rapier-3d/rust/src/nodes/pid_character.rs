@@ -4,9 +4,10 @@ use godot::prelude::*;
 use rapier3d::control::PidController;
 use rapier3d::prelude::*;
 
-use super::common::{Controllable, Forceable};
+use super::common::{impl_force_funcs, Collidable, Controllable, Forceable};
 use crate::interface::{get_runtime, get_singleton, get_tree};
 use crate::nodes::IRapierObject;
+use crate::resources::{watch_physics_material, RapierPhysicsMaterial};
 use crate::utils::vector_to_point;
 
 #[derive(GodotClass)]
@@ -14,6 +15,9 @@ use crate::utils::vector_to_point;
 pub struct RapierPIDCharacter3D {
     #[var(usage_flags = [EDITOR, STORAGE, READ_ONLY])]
     pub handle: Array<u32>,
+    #[export]
+    /// The physics world this node is added to when it enters the tree, see GR3D.create_world
+    pub world_id: i64,
 
     // TODO why aren't descriptions working? https://github.com/godot-rust/gdext/issues/1059
     #[export]
@@ -49,6 +53,27 @@ pub struct RapierPIDCharacter3D {
     pub floor_check_ray_length: f32,
 
     pub controller: PidController,
+    #[export(flags_3d_physics)]
+    #[var(get, set = set_collision_layer)]
+    /// The physics layers this body is in. Collision shapes may override this
+    pub collision_layer: u32,
+    #[export(flags_3d_physics)]
+    #[var(get, set = set_collision_mask)]
+    /// The physics layers this body scans for collisions. Collision shapes may override this
+    pub collision_mask: u32,
+    #[export]
+    /// Emit `contact_force` signals when the contact force with another body exceeds the threshold
+    pub report_contact_forces: bool,
+    #[export]
+    pub contact_force_threshold: f32,
+    #[export]
+    #[var(get, set = set_physics_material)]
+    /// Friction, restitution and density of this body's colliders. Collision shapes may override this
+    pub physics_material: Option<Gd<RapierPhysicsMaterial>>,
+    #[export]
+    #[var(get, set = set_gravity_scale)]
+    /// Multiplier of the gravity applied to this body, including the gravity of areas
+    pub gravity_scale: f32,
     base: Base<Node3D>,
 }
 
@@ -57,6 +82,7 @@ impl INode3D for RapierPIDCharacter3D {
     fn init(base: Base<Node3D>) -> Self {
         Self {
             handle: Array::new(),
+            world_id: 0,
             lin_kp: 60.0,
             lin_ki: 1.0,
             lin_kd: 0.8,
@@ -65,6 +91,12 @@ impl INode3D for RapierPIDCharacter3D {
             ang_kd: 0.8,
             floor_check_ray_length: 0.1,
             controller: PidController::default(),
+            collision_layer: 1,
+            collision_mask: 1,
+            report_contact_forces: false,
+            contact_force_threshold: 0.0,
+            physics_material: None,
+            gravity_scale: 1.0,
             base,
         }
     }
@@ -96,6 +128,38 @@ impl RapierPIDCharacter3D {
         self.sync()
     }
 
+    #[func]
+    fn set_collision_layer(&mut self, layer: u32) {
+        self.collision_layer = layer;
+        self.on_collision_groups_changed();
+    }
+
+    #[func]
+    fn set_collision_mask(&mut self, mask: u32) {
+        self.collision_mask = mask;
+        self.on_collision_groups_changed();
+    }
+
+    #[func]
+    fn set_physics_material(&mut self, material: Option<Gd<RapierPhysicsMaterial>>) {
+        watch_physics_material(&self.to_gd().upcast(), &self.physics_material, &material);
+        self.physics_material = material;
+        self.on_physics_material_changed();
+    }
+
+    #[func]
+    fn _on_physics_material_changed(&self) {
+        self.on_physics_material_changed();
+    }
+
+    #[func]
+    fn set_gravity_scale(&mut self, scale: f32) {
+        self.gravity_scale = scale;
+        if self.base().is_inside_tree() {
+            self.on_reconfigure();
+        }
+    }
+
     #[func]
     fn move_by_amount(&self, amount: Vector3) {
         self.on_move_by_amount(amount);
@@ -128,9 +192,12 @@ impl RapierPIDCharacter3D {
                 return false;
             }
             let bind = singleton.bind();
-            let bodies = &bind.world.physics.bodies;
-            let colliders = &bind.world.physics.colliders;
-            let query_pipeline = &bind.world.physics.query_pipeline;
+            let Some(world) = bind.get_world(self.get_world_id()) else {
+                return false;
+            };
+            let bodies = &world.physics.bodies;
+            let colliders = &world.physics.colliders;
+            let query_pipeline = &world.physics.query_pipeline;
 
             let handle = RigidBodyHandle::from_raw_parts(self.handle.at(0), self.handle.at(1));
             let body = &bodies[handle];
@@ -194,4 +261,26 @@ impl RapierPIDCharacter3D {
             return false;
         }
     }
+
+    #[signal]
+    /// Emitted when this body starts touching another body
+    fn body_entered(body: Gd<Node3D>);
+
+    #[signal]
+    /// Emitted when this body stops touching another body
+    fn body_exited(body: Gd<Node3D>);
+
+    #[signal]
+    /// Emitted when a collision shape of another body starts touching a collision shape of this body
+    fn contact_started(collider: Gd<Node3D>, local_collider: Gd<Node3D>);
+
+    #[signal]
+    /// Emitted when a collision shape of another body stops touching a collision shape of this body
+    fn contact_ended(collider: Gd<Node3D>, local_collider: Gd<Node3D>);
+
+    #[signal]
+    /// Emitted every step the contact force with another body exceeds `contact_force_threshold`
+    fn contact_force(body: Gd<Node3D>, total_force: Vector3);
 }
+
+impl_force_funcs!(RapierPIDCharacter3D);
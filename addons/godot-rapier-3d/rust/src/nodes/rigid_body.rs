@@ -3,15 +3,40 @@ use godot::classes::{INode3D, Node3D};
 use godot::prelude::*;
 use rapier3d::control::PidController;
 
-use super::common::Forceable;
+use super::common::{impl_force_funcs, Collidable, Forceable};
 use crate::nodes::IRapierObject;
+use crate::resources::{watch_physics_material, RapierPhysicsMaterial};
 
 #[derive(GodotClass)]
 #[class(tool, base=Node3D)]
 pub struct RapierRigidBody3D {
     #[var(usage_flags = [EDITOR, STORAGE, READ_ONLY])]
     pub handle: Array<u32>,
+    #[export]
+    /// The physics world this node is added to when it enters the tree, see GR3D.create_world
+    pub world_id: i64,
     pub controller: PidController,
+    #[export(flags_3d_physics)]
+    #[var(get, set = set_collision_layer)]
+    /// The physics layers this body is in. Collision shapes may override this
+    pub collision_layer: u32,
+    #[export(flags_3d_physics)]
+    #[var(get, set = set_collision_mask)]
+    /// The physics layers this body scans for collisions. Collision shapes may override this
+    pub collision_mask: u32,
+    #[export]
+    /// Emit `contact_force` signals when the contact force with another body exceeds the threshold
+    pub report_contact_forces: bool,
+    #[export]
+    pub contact_force_threshold: f32,
+    #[export]
+    #[var(get, set = set_physics_material)]
+    /// Friction, restitution and density of this body's colliders. Collision shapes may override this
+    pub physics_material: Option<Gd<RapierPhysicsMaterial>>,
+    #[export]
+    #[var(get, set = set_gravity_scale)]
+    /// Multiplier of the gravity applied to this body, including the gravity of areas
+    pub gravity_scale: f32,
     base: Base<Node3D>,
 }
 
@@ -20,7 +45,14 @@ impl INode3D for RapierRigidBody3D {
     fn init(base: Base<Node3D>) -> Self {
         Self {
             handle: Array::new(),
+            world_id: 0,
             controller: PidController::default(),
+            collision_layer: 1,
+            collision_mask: 1,
+            report_contact_forces: false,
+            contact_force_threshold: 0.0,
+            physics_material: None,
+            gravity_scale: 1.0,
             base,
         }
     }
@@ -52,8 +84,62 @@ impl RapierRigidBody3D {
         self.sync()
     }
 
+    #[func]
+    fn set_collision_layer(&mut self, layer: u32) {
+        self.collision_layer = layer;
+        self.on_collision_groups_changed();
+    }
+
+    #[func]
+    fn set_collision_mask(&mut self, mask: u32) {
+        self.collision_mask = mask;
+        self.on_collision_groups_changed();
+    }
+
+    #[func]
+    fn set_physics_material(&mut self, material: Option<Gd<RapierPhysicsMaterial>>) {
+        watch_physics_material(&self.to_gd().upcast(), &self.physics_material, &material);
+        self.physics_material = material;
+        self.on_physics_material_changed();
+    }
+
+    #[func]
+    fn _on_physics_material_changed(&self) {
+        self.on_physics_material_changed();
+    }
+
+    #[func]
+    fn set_gravity_scale(&mut self, scale: f32) {
+        self.gravity_scale = scale;
+        if self.base().is_inside_tree() {
+            self.on_reconfigure();
+        }
+    }
+
     #[func]
     fn get_real_velocity(&self) -> Vector3 {
         self.get_body_state().linvel
     }
+
+    #[signal]
+    /// Emitted when this body starts touching another body
+    fn body_entered(body: Gd<Node3D>);
+
+    #[signal]
+    /// Emitted when this body stops touching another body
+    fn body_exited(body: Gd<Node3D>);
+
+    #[signal]
+    /// Emitted when a collision shape of another body starts touching a collision shape of this body
+    fn contact_started(collider: Gd<Node3D>, local_collider: Gd<Node3D>);
+
+    #[signal]
+    /// Emitted when a collision shape of another body stops touching a collision shape of this body
+    fn contact_ended(collider: Gd<Node3D>, local_collider: Gd<Node3D>);
+
+    #[signal]
+    /// Emitted every step the contact force with another body exceeds `contact_force_threshold`
+    fn contact_force(body: Gd<Node3D>, total_force: Vector3);
 }
+
+impl_force_funcs!(RapierRigidBody3D);
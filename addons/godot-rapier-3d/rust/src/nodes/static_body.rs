@@ -2,7 +2,8 @@ use godot::classes::notify::Node3DNotification;
 use godot::classes::{INode3D, Node3D};
 use godot::prelude::*;
 
-use crate::nodes::{generate_cuid, IRapierObject};
+use crate::nodes::{generate_cuid, Collidable, IRapierObject};
+use crate::resources::{watch_physics_material, RapierPhysicsMaterial};
 
 #[derive(GodotClass)]
 #[class(tool, base=Node3D)]
@@ -11,6 +12,26 @@ pub struct RapierStaticBody3D {
     pub cuid: GString,
     #[var(usage_flags = [EDITOR, STORAGE, READ_ONLY])]
     pub handle: Array<u32>,
+    #[export]
+    /// The physics world this node is added to when it enters the tree, see GR3D.create_world
+    pub world_id: i64,
+    #[export(flags_3d_physics)]
+    #[var(get, set = set_collision_layer)]
+    /// The physics layers this body is in. Collision shapes may override this
+    pub collision_layer: u32,
+    #[export(flags_3d_physics)]
+    #[var(get, set = set_collision_mask)]
+    /// The physics layers this body scans for collisions. Collision shapes may override this
+    pub collision_mask: u32,
+    #[export]
+    /// Emit `contact_force` signals when the contact force with another body exceeds the threshold
+    pub report_contact_forces: bool,
+    #[export]
+    pub contact_force_threshold: f32,
+    #[export]
+    #[var(get, set = set_physics_material)]
+    /// Friction, restitution and density of this body's colliders. Collision shapes may override this
+    pub physics_material: Option<Gd<RapierPhysicsMaterial>>,
     base: Base<Node3D>,
 }
 
@@ -20,6 +41,12 @@ impl INode3D for RapierStaticBody3D {
         Self {
             cuid: generate_cuid(),
             handle: Array::new(),
+            world_id: 0,
+            collision_layer: 1,
+            collision_mask: 1,
+            report_contact_forces: false,
+            contact_force_threshold: 0.0,
+            physics_material: None,
             base,
         }
     }
@@ -50,4 +77,48 @@ impl RapierStaticBody3D {
     fn match_rapier(&mut self) {
         self.sync()
     }
+
+    #[func]
+    fn set_collision_layer(&mut self, layer: u32) {
+        self.collision_layer = layer;
+        self.on_collision_groups_changed();
+    }
+
+    #[func]
+    fn set_collision_mask(&mut self, mask: u32) {
+        self.collision_mask = mask;
+        self.on_collision_groups_changed();
+    }
+
+    #[func]
+    fn set_physics_material(&mut self, material: Option<Gd<RapierPhysicsMaterial>>) {
+        watch_physics_material(&self.to_gd().upcast(), &self.physics_material, &material);
+        self.physics_material = material;
+        self.on_physics_material_changed();
+    }
+
+    #[func]
+    fn _on_physics_material_changed(&self) {
+        self.on_physics_material_changed();
+    }
+
+    #[signal]
+    /// Emitted when this body starts touching another body
+    fn body_entered(body: Gd<Node3D>);
+
+    #[signal]
+    /// Emitted when this body stops touching another body
+    fn body_exited(body: Gd<Node3D>);
+
+    #[signal]
+    /// Emitted when a collision shape of another body starts touching a collision shape of this body
+    fn contact_started(collider: Gd<Node3D>, local_collider: Gd<Node3D>);
+
+    #[signal]
+    /// Emitted when a collision shape of another body stops touching a collision shape of this body
+    fn contact_ended(collider: Gd<Node3D>, local_collider: Gd<Node3D>);
+
+    #[signal]
+    /// Emitted every step the contact force with another body exceeds `contact_force_threshold`
+    fn contact_force(body: Gd<Node3D>, total_force: Vector3);
 }
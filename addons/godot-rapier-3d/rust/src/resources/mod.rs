@@ -0,0 +1,7 @@
+mod physics_material;
+mod snapshot;
+
+// Godot resources that configure Rapier objects or store Rapier data go in this module
+
+pub use physics_material::{watch_physics_material, MaterialProperties, RapierPhysicsMaterial};
+pub use snapshot::{RapierSnapshot, SnapshotFormats};
@@ -0,0 +1,211 @@
+use godot::classes::{IResource, Resource};
+use godot::prelude::*;
+use rapier3d::prelude::{CoefficientCombineRule, Collider, ColliderBuilder, Real};
+use serde::{Deserialize, Serialize};
+
+#[derive(GodotConvert, Var, Export, Debug, Clone, Copy, Default)]
+#[godot(via = GString)]
+/// How the coefficients of two touching colliders are combined.
+/// When the colliders use different rules, the one listed last wins
+pub enum CombineRule {
+    #[default]
+    Average,
+    Min,
+    Multiply,
+    Max,
+}
+
+impl From<CombineRule> for CoefficientCombineRule {
+    fn from(rule: CombineRule) -> Self {
+        match rule {
+            CombineRule::Average => CoefficientCombineRule::Average,
+            CombineRule::Min => CoefficientCombineRule::Min,
+            CombineRule::Multiply => CoefficientCombineRule::Multiply,
+            CombineRule::Max => CoefficientCombineRule::Max,
+        }
+    }
+}
+
+#[derive(GodotClass)]
+#[class(tool, base=Resource)]
+/// Surface and mass properties of colliders.
+/// Assign to a RapierCollisionShape3D, or to a body to apply it to all of its collision shapes
+pub struct RapierPhysicsMaterial {
+    #[export]
+    #[var(get, set = set_friction)]
+    /// Friction coefficient, 0 = no friction (ice)
+    pub friction: Real,
+    #[export]
+    #[var(get, set = set_restitution)]
+    /// Restitution coefficient, 0 = no bounce, 1 = perfectly elastic bounce
+    pub restitution: Real,
+    #[export]
+    #[var(get, set = set_friction_combine_rule)]
+    pub friction_combine_rule: CombineRule,
+    #[export]
+    #[var(get, set = set_restitution_combine_rule)]
+    pub restitution_combine_rule: CombineRule,
+    #[export]
+    #[var(get, set = set_density)]
+    /// Mass per unit of volume, used to compute the mass of the collider from its shape
+    pub density: Real,
+    #[export]
+    #[var(get, set = set_use_mass)]
+    /// Use a fixed mass for each collider instead of computing it from the density
+    pub use_mass: bool,
+    #[export]
+    #[var(get, set = set_mass)]
+    /// Mass of each collider (only used when use_mass is enabled)
+    pub mass: Real,
+    base: Base<Resource>,
+}
+
+#[godot_api]
+impl IResource for RapierPhysicsMaterial {
+    fn init(base: Base<Resource>) -> Self {
+        Self {
+            friction: ColliderBuilder::default_friction(),
+            restitution: 0.0,
+            friction_combine_rule: CombineRule::Average,
+            restitution_combine_rule: CombineRule::Average,
+            density: ColliderBuilder::default_density(),
+            use_mass: false,
+            mass: 1.0,
+            base,
+        }
+    }
+}
+
+#[godot_api]
+impl RapierPhysicsMaterial {
+    #[func]
+    fn set_friction(&mut self, friction: Real) {
+        self.friction = friction;
+        self.base_mut().emit_changed();
+    }
+
+    #[func]
+    fn set_restitution(&mut self, restitution: Real) {
+        self.restitution = restitution;
+        self.base_mut().emit_changed();
+    }
+
+    #[func]
+    fn set_friction_combine_rule(&mut self, rule: CombineRule) {
+        self.friction_combine_rule = rule;
+        self.base_mut().emit_changed();
+    }
+
+    #[func]
+    fn set_restitution_combine_rule(&mut self, rule: CombineRule) {
+        self.restitution_combine_rule = rule;
+        self.base_mut().emit_changed();
+    }
+
+    #[func]
+    fn set_density(&mut self, density: Real) {
+        self.density = density;
+        self.base_mut().emit_changed();
+    }
+
+    #[func]
+    fn set_use_mass(&mut self, use_mass: bool) {
+        self.use_mass = use_mass;
+        self.base_mut().emit_changed();
+    }
+
+    #[func]
+    fn set_mass(&mut self, mass: Real) {
+        self.mass = mass;
+        self.base_mut().emit_changed();
+    }
+
+    pub fn properties(&self) -> MaterialProperties {
+        MaterialProperties {
+            friction: self.friction,
+            restitution: self.restitution,
+            friction_combine_rule: self.friction_combine_rule.into(),
+            restitution_combine_rule: self.restitution_combine_rule.into(),
+            mass: match self.use_mass {
+                true => ColliderMass::Mass(self.mass),
+                false => ColliderMass::Density(self.density),
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub enum ColliderMass {
+    Density(Real),
+    Mass(Real),
+}
+
+/// Plain copy of a RapierPhysicsMaterial that can be applied to Rapier colliders
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct MaterialProperties {
+    pub friction: Real,
+    pub restitution: Real,
+    pub friction_combine_rule: CoefficientCombineRule,
+    pub restitution_combine_rule: CoefficientCombineRule,
+    pub mass: ColliderMass,
+}
+
+impl Default for MaterialProperties {
+    /// Rapier's defaults, used by colliders without a material
+    fn default() -> Self {
+        Self {
+            friction: ColliderBuilder::default_friction(),
+            restitution: 0.0,
+            friction_combine_rule: CoefficientCombineRule::Average,
+            restitution_combine_rule: CoefficientCombineRule::Average,
+            mass: ColliderMass::Density(ColliderBuilder::default_density()),
+        }
+    }
+}
+
+impl MaterialProperties {
+    pub fn apply_to_builder(&self, builder: ColliderBuilder) -> ColliderBuilder {
+        let builder = builder
+            .friction(self.friction)
+            .restitution(self.restitution)
+            .friction_combine_rule(self.friction_combine_rule)
+            .restitution_combine_rule(self.restitution_combine_rule);
+
+        match self.mass {
+            ColliderMass::Density(density) => builder.density(density),
+            ColliderMass::Mass(mass) => builder.mass(mass),
+        }
+    }
+
+    pub fn apply_to_collider(&self, collider: &mut Collider) {
+        collider.set_friction(self.friction);
+        collider.set_restitution(self.restitution);
+        collider.set_friction_combine_rule(self.friction_combine_rule);
+        collider.set_restitution_combine_rule(self.restitution_combine_rule);
+
+        match self.mass {
+            ColliderMass::Density(density) => collider.set_density(density),
+            ColliderMass::Mass(mass) => collider.set_mass(mass),
+        }
+    }
+}
+
+/// Moves the `changed` connection of a node from its old material to its new one,
+/// so that editing the resource reconfigures the node's colliders
+pub fn watch_physics_material(
+    node: &Gd<Node3D>,
+    old: &Option<Gd<RapierPhysicsMaterial>>,
+    new: &Option<Gd<RapierPhysicsMaterial>>,
+) {
+    let callable = node.callable("_on_physics_material_changed");
+    if let Some(old) = old {
+        if old.is_connected("changed", &callable) {
+            old.clone().disconnect("changed", &callable);
+        }
+    }
+    if let Some(new) = new {
+        if !new.is_connected("changed", &callable) {
+            new.clone().connect("changed", &callable);
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use godot::classes::file_access::ModeFlags;
+use godot::classes::{
+    FileAccess, IResource, IResourceFormatLoader, IResourceFormatSaver, Resource,
+    ResourceFormatLoader, ResourceFormatSaver, ResourceLoader, ResourceSaver,
+};
+use godot::global::{bytes_to_var, var_to_bytes, Error};
+use godot::prelude::*;
+
+/*
+    .rapiersnap files contain a RapierSnapshot encoded with var_to_bytes as a dictionary,
+    so that the metadata can be read without unpacking the snapshot itself
+*/
+
+pub const SNAPSHOT_EXTENSION: &str = "rapiersnap";
+const FILE_FORMAT_VERSION: i64 = 1;
+
+#[derive(GodotClass)]
+#[class(tool, base=Resource)]
+/// A world snapshot saved as a resource, along with metadata describing when it was taken.
+/// Create one with GR3D.save_snapshot_resource() and save it with ResourceSaver to a .rapiersnap file
+pub struct RapierSnapshot {
+    #[export]
+    /// Snapshot bytes, as returned by GR3D.save_snapshot()
+    pub snapshot: PackedByteArray,
+    #[export]
+    /// Tick the snapshot was taken at
+    pub tick: i64,
+    #[export]
+    /// Simulation time the snapshot was taken at
+    pub time: f64,
+    #[export]
+    /// Number of rigid bodies in the snapshot
+    pub body_count: i64,
+    #[export]
+    /// Path of the scene that was running when the snapshot was taken
+    pub scene_path: GString,
+    base: Base<Resource>,
+}
+
+#[godot_api]
+impl IResource for RapierSnapshot {
+    fn init(base: Base<Resource>) -> Self {
+        Self {
+            snapshot: PackedByteArray::new(),
+            tick: 0,
+            time: 0.0,
+            body_count: 0,
+            scene_path: GString::new(),
+            base,
+        }
+    }
+}
+
+impl RapierSnapshot {
+    fn to_bytes(&self) -> PackedByteArray {
+        let mut dict = Dictionary::new();
+        dict.set("version", FILE_FORMAT_VERSION);
+        dict.set("tick", self.tick);
+        dict.set("time", self.time);
+        dict.set("body_count", self.body_count);
+        dict.set("scene_path", self.scene_path.clone());
+        dict.set("snapshot", self.snapshot.clone());
+        var_to_bytes(&dict.to_variant())
+    }
+
+    fn from_bytes(bytes: &PackedByteArray) -> Option<Gd<Self>> {
+        let dict = bytes_to_var(bytes).try_to::<Dictionary>().ok()?;
+        let version = dict.get("version")?.try_to::<i64>().ok()?;
+        if version != FILE_FORMAT_VERSION {
+            log::error!(
+                "Snapshot file format version {} is not supported (expected {})",
+                version,
+                FILE_FORMAT_VERSION
+            );
+            return None;
+        }
+
+        let mut resource = RapierSnapshot::new_gd();
+        {
+            let mut snapshot = resource.bind_mut();
+            snapshot.tick = dict.get("tick")?.try_to().ok()?;
+            snapshot.time = dict.get("time")?.try_to().ok()?;
+            snapshot.body_count = dict.get("body_count")?.try_to().ok()?;
+            snapshot.scene_path = dict.get("scene_path")?.try_to().ok()?;
+            snapshot.snapshot = dict.get("snapshot")?.try_to().ok()?;
+        }
+        Some(resource)
+    }
+}
+
+#[derive(GodotClass)]
+#[class(tool, init, base=ResourceFormatSaver)]
+/// Saves RapierSnapshot resources to .rapiersnap files
+pub struct RapierSnapshotSaver {
+    base: Base<ResourceFormatSaver>,
+}
+
+#[godot_api]
+impl IResourceFormatSaver for RapierSnapshotSaver {
+    fn save(&mut self, resource: Option<Gd<Resource>>, path: GString, _flags: u32) -> Error {
+        let Some(Ok(resource)) = resource.map(|r| r.try_cast::<RapierSnapshot>()) else {
+            return Error::ERR_INVALID_PARAMETER;
+        };
+        let Some(mut file) = FileAccess::open(&path, ModeFlags::WRITE) else {
+            log::error!("Failed to open {} for writing", path);
+            return FileAccess::get_open_error();
+        };
+        file.store_buffer(&resource.bind().to_bytes());
+        file.close();
+        Error::OK
+    }
+
+    fn recognize(&self, resource: Option<Gd<Resource>>) -> bool {
+        resource.is_some_and(|r| r.try_cast::<RapierSnapshot>().is_ok())
+    }
+
+    fn get_recognized_extensions(&self, resource: Option<Gd<Resource>>) -> PackedStringArray {
+        let mut extensions = PackedStringArray::new();
+        if self.recognize(resource) {
+            extensions.push(SNAPSHOT_EXTENSION);
+        }
+        extensions
+    }
+}
+
+#[derive(GodotClass)]
+#[class(tool, init, base=ResourceFormatLoader)]
+/// Loads .rapiersnap files as RapierSnapshot resources
+pub struct RapierSnapshotLoader {
+    base: Base<ResourceFormatLoader>,
+}
+
+#[godot_api]
+impl IResourceFormatLoader for RapierSnapshotLoader {
+    fn get_recognized_extensions(&self) -> PackedStringArray {
+        PackedStringArray::from(&[GString::from(SNAPSHOT_EXTENSION)])
+    }
+
+    fn handles_type(&self, type_: StringName) -> bool {
+        type_ == RapierSnapshot::class_name().to_string_name()
+    }
+
+    fn get_resource_type(&self, path: GString) -> GString {
+        match path.get_extension().to_lower() == SNAPSHOT_EXTENSION.into() {
+            true => RapierSnapshot::class_name().to_gstring(),
+            false => GString::new(),
+        }
+    }
+
+    fn load(
+        &self,
+        path: GString,
+        _original_path: GString,
+        _use_sub_threads: bool,
+        _cache_mode: i32,
+    ) -> Variant {
+        if !FileAccess::file_exists(&path) {
+            return Error::ERR_FILE_NOT_FOUND.to_variant();
+        }
+        match RapierSnapshot::from_bytes(&FileAccess::get_file_as_bytes(&path)) {
+            Some(resource) => resource.to_variant(),
+            None => {
+                log::error!("Failed to load snapshot file {}", path);
+                Error::ERR_FILE_CORRUPT.to_variant()
+            }
+        }
+    }
+}
+
+/// Saver and loader instances registered with Godot, kept to unregister them on shutdown
+pub struct SnapshotFormats {
+    saver: Gd<RapierSnapshotSaver>,
+    loader: Gd<RapierSnapshotLoader>,
+}
+
+impl SnapshotFormats {
+    pub fn register() -> Self {
+        let formats = Self {
+            saver: RapierSnapshotSaver::new_gd(),
+            loader: RapierSnapshotLoader::new_gd(),
+        };
+        ResourceSaver::singleton().add_resource_format_saver(&formats.saver);
+        ResourceLoader::singleton().add_resource_format_loader(&formats.loader);
+        formats
+    }
+
+    pub fn unregister(self) {
+        ResourceSaver::singleton().remove_resource_format_saver(&self.saver);
+        ResourceLoader::singleton().remove_resource_format_loader(&self.loader);
+    }
+}
@@ -7,18 +7,22 @@ use rapier3d::{
 // Godot transform to Rapier isometry
 pub fn transform_to_isometry(transform: Transform3D) -> Isometry<Real> {
     let pos = transform.origin;
-    let quaternion = transform.basis.get_quaternion();
     Isometry::from_parts(
         Translation::new(pos.x, pos.y, pos.z),
-        Rotation::from_quaternion(RQuaternion::new(
-            quaternion.w,
-            quaternion.x,
-            quaternion.y,
-            quaternion.z,
-        )),
+        quaternion_to_rapier(transform.basis.get_quaternion()),
     )
 }
 
+// Normalized, Rapier rotations are unit quaternions
+pub fn quaternion_to_rapier(quaternion: GQuaternion) -> Rotation<Real> {
+    Rotation::from_quaternion(RQuaternion::new(
+        quaternion.w,
+        quaternion.x,
+        quaternion.y,
+        quaternion.z,
+    ))
+}
+
 pub fn isometry_to_transform(isometry: &Isometry<Real>) -> Transform3D {
     let translation = isometry.translation.vector;
     let rotation = isometry.rotation.quaternion();
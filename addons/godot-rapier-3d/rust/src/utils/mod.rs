@@ -1,5 +1,7 @@
 mod conversion;
 mod logger;
+mod shape;
 
 pub use conversion::*;
 pub use logger::*;
+pub use shape::*;
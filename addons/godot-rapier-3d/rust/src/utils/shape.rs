@@ -0,0 +1,174 @@
+use godot::classes::{
+    BoxShape3D, CapsuleShape3D, ConcavePolygonShape3D, ConvexPolygonShape3D, CylinderShape3D,
+    HeightMapShape3D, Shape3D, SphereShape3D,
+};
+use godot::prelude::*;
+use rapier3d::na::DMatrix;
+use rapier3d::parry::transformation::vhacd::VHACDParameters;
+use rapier3d::prelude::*;
+
+use super::{vector_to_point, vector_to_rapier};
+
+pub const SUPPORTED_SHAPES: &str = "Only primitives, ConvexPolygonShape3D, ConcavePolygonShape3D and HeightMapShape3D are supported";
+
+// Godot shape to Rapier shape
+pub fn shape_to_rapier(shape: &Gd<Shape3D>) -> Option<SharedShape> {
+    match shape.get_class().to_string().as_str() {
+        "SphereShape3D" => {
+            let casted = shape.clone().cast::<SphereShape3D>();
+            Some(SharedShape::ball(casted.get_radius()))
+        }
+        "BoxShape3D" => {
+            let casted = shape.clone().cast::<BoxShape3D>();
+            Some(SharedShape::cuboid(
+                casted.get_size().x / 2.0,
+                casted.get_size().y / 2.0,
+                casted.get_size().z / 2.0,
+            ))
+        }
+        "CapsuleShape3D" => {
+            let casted = shape.clone().cast::<CapsuleShape3D>();
+            Some(SharedShape::capsule_y(
+                casted.get_height() / 4.0,
+                casted.get_radius(),
+            ))
+        }
+        "CylinderShape3D" => {
+            let casted = shape.clone().cast::<CylinderShape3D>();
+            Some(SharedShape::cylinder(
+                casted.get_height() / 2.0,
+                casted.get_radius(),
+            ))
+        }
+        "ConvexPolygonShape3D" => {
+            let casted = shape.clone().cast::<ConvexPolygonShape3D>();
+            let points: Vec<Point<f32>> = casted
+                .get_points()
+                .as_slice()
+                .iter()
+                .map(|point| vector_to_point(&vector_to_rapier(*point)))
+                .collect();
+
+            match SharedShape::convex_hull(&points) {
+                Some(hull) => Some(hull),
+                None => {
+                    log::error!("Failed to create convex hull collider from collision shape");
+                    None
+                }
+            }
+        }
+        "ConcavePolygonShape3D" => {
+            let casted = shape.clone().cast::<ConcavePolygonShape3D>();
+            let (vertices, indices) = concave_to_mesh(&casted);
+
+            match SharedShape::trimesh_with_flags(vertices, indices, TriMeshFlags::empty()) {
+                Ok(trimesh) => Some(trimesh),
+                Err(_) => {
+                    log::error!("Failed to create trimesh collider from collision shape");
+                    None
+                }
+            }
+        }
+        "HeightMapShape3D" => {
+            let casted = shape.clone().cast::<HeightMapShape3D>();
+            heightmap_to_heightfield(&casted)
+        }
+        _ => {
+            log::error!(
+                "Unknown shape class: {}. {}",
+                shape.get_class(),
+                SUPPORTED_SHAPES
+            );
+            None
+        }
+    }
+}
+
+// Godot concave shape to a compound of convex hulls (V-HACD), usable by dynamic bodies
+pub fn shape_to_convex_decomposition(
+    shape: &Gd<Shape3D>,
+    params: &VHACDParameters,
+) -> Option<SharedShape> {
+    match shape.clone().try_cast::<ConcavePolygonShape3D>() {
+        Ok(casted) => {
+            let (vertices, indices) = concave_to_mesh(&casted);
+            Some(SharedShape::convex_decomposition_with_params(
+                &vertices, &indices, params,
+            ))
+        }
+        Err(shape) => {
+            log::error!(
+                "Convex decomposition requires a ConcavePolygonShape3D, got: {}",
+                shape.get_class()
+            );
+            None
+        }
+    }
+}
+
+// Godot faces are a flat list of triangle vertices
+fn concave_to_mesh(shape: &Gd<ConcavePolygonShape3D>) -> (Vec<Point<f32>>, Vec<[u32; 3]>) {
+    let faces = shape.get_faces().to_vec();
+    let mut vertices: Vec<Point<f32>> = vec![];
+    let mut indices: Vec<[u32; 3]> = vec![];
+
+    for (idx, vert) in faces.iter().enumerate() {
+        vertices.push(vector_to_point(&vector_to_rapier(*vert)));
+        if idx % 3 == 0 {
+            indices.push([idx as u32, (idx + 1) as u32, (idx + 2) as u32]);
+        }
+    }
+
+    (vertices, indices)
+}
+
+// Godot heightmaps are centered on the origin with 1 unit between samples, stored row by row along z.
+// Rapier heightfields are centered too, spanning `scale` along x and z
+fn heightmap_to_heightfield(shape: &Gd<HeightMapShape3D>) -> Option<SharedShape> {
+    let width = shape.get_map_width().max(0) as usize;
+    let depth = shape.get_map_depth().max(0) as usize;
+    let data = shape.get_map_data();
+    let data = data.as_slice();
+
+    if width < 2 || depth < 2 || data.len() != width * depth {
+        log::error!(
+            "Invalid HeightMapShape3D: {}x{} map with {} heights",
+            width,
+            depth,
+            data.len()
+        );
+        return None;
+    }
+
+    let heights = DMatrix::from_fn(depth, width, |row, col| data[row * width + col]);
+    let scale = vector![(width - 1) as Real, 1.0, (depth - 1) as Real];
+    Some(SharedShape::heightfield(heights, scale))
+}
+
+/// Number of vertices making up meshes, heightfields and convex hulls. Primitives count as 0
+pub fn shape_vertex_count(shape: &dyn Shape) -> usize {
+    match shape.shape_type() {
+        ShapeType::TriMesh => shape
+            .as_trimesh()
+            .map_or(0, |trimesh| trimesh.vertices().len()),
+        ShapeType::HeightField => shape
+            .as_heightfield()
+            .map_or(0, |heightfield| heightfield.heights().len()),
+        ShapeType::ConvexPolyhedron => shape
+            .as_convex_polyhedron()
+            .map_or(0, |hull| hull.points().len()),
+        ShapeType::Compound => shape.as_compound().map_or(0, |compound| {
+            compound
+                .shapes()
+                .iter()
+                .map(|(_, part)| shape_vertex_count(&**part))
+                .sum()
+        }),
+        _ => 0,
+    }
+}
+
+/// Returns true if the shape is expensive to render/serialize
+pub fn is_expensive_shape(shape: &dyn Shape) -> bool {
+    shape_vertex_count(shape) > crate::config::DEBUG_MAX_VERTEX_COUNT
+}
@@ -2,13 +2,14 @@ use godot::{builtin::Vector3, meta::FromGodot};
 
 use crate::{
     interface::{
-        add_node_to_world, configure_node, move_node, remove_node_from_world, Action, Operation,
+        action_order, add_node_to_world, configure_node, move_node, rotate_node, Action, Operation,
     },
-    World,
+    nodes::is_joint,
 };
 use std::collections::HashMap;
 
-use super::state::{restore_snapshot, PhysicsState};
+use super::command::Command;
+use super::state::PhysicsState;
 
 pub struct WorldBuffer {
     pub buffer: HashMap<usize, BufferStep>,
@@ -19,7 +20,9 @@ pub struct WorldBuffer {
 pub struct BufferStep {
     timestep_id: usize,             // The timestep id of this step
     physics_state: Option<Vec<u8>>, // The state of the physics world at the beginning of this timestep
+    state_hash: Option<u64>,        // Checksum of physics_state, see world::hash
     actions: Vec<Action>,           // List of actions to apply during this timestep
+    commands: Vec<Command>,         // List of commands to apply during this timestep
 }
 
 impl WorldBuffer {
@@ -30,8 +33,12 @@ impl WorldBuffer {
         }
     }
 
-    pub fn default() -> Self {
-        Self::new(1000)
+    /// Changes the amount of ticks kept in the buffer, dropping the oldest ones if it shrinks
+    pub fn set_max_len(&mut self, max_len: usize) {
+        self.max_len = max_len;
+        while self.buffer.len() > self.max_len {
+            self.remove_oldest();
+        }
     }
 
     /// Returns the buffer step at the given timestep.
@@ -46,43 +53,35 @@ impl WorldBuffer {
             .and_then(|step| step.physics_state.clone())
     }
 
-    /// Rolls the given world back to the given timestep and
-    /// rewrites the physics state history from that point forward
-    /// back to the current timestep
-    pub fn corrective_rollback(&mut self, world: &mut World, target: BufferStep) {
-        let current_timestep = world.state.timestep_id.clone();
-        let steps_to_resim = current_timestep - target.timestep_id;
-
-        match true {
-            _ if target.timestep_id >= current_timestep => {
-                log::error!(
-                    "Cannot rollback to a future timestep: {}",
-                    target.timestep_id
-                );
-            }
-            _ if steps_to_resim == 0 => {
-                log::warn!("Corrective rollback to same timestep. No action taken.");
+    /// Returns the checksum of the physics state at the given timestep.
+    pub fn get_state_hash(&self, timestep_id: usize) -> Option<u64> {
+        self.buffer
+            .get(&timestep_id)
+            .and_then(|step| step.state_hash)
+    }
+
+    /// Overwrites the physics state and checksum stored for the given timestep
+    pub fn set_physics_state(
+        &mut self,
+        timestep_id: usize,
+        physics_state: Vec<u8>,
+        state_hash: u64,
+    ) {
+        match self.buffer.get_mut(&timestep_id) {
+            Some(step) => {
+                step.physics_state = Some(physics_state);
+                step.state_hash = Some(state_hash);
             }
-            _ if steps_to_resim > self.max_len => {
-                log::error!(
-                    "Cannot rollback more than the buffer length: {}",
-                    self.max_len
-                );
+            None => {
+                let step = BufferStep {
+                    timestep_id,
+                    physics_state: Some(physics_state),
+                    state_hash: Some(state_hash),
+                    actions: Vec::new(),
+                    commands: Vec::new(),
+                };
+                self.buffer.insert(step.timestep_id, step);
             }
-            _ => match target.physics_state {
-                Some(physics_state) => {
-                    world.state.timestep_id = target.timestep_id;
-                    self.mark_stale_after(target.timestep_id);
-                    restore_snapshot(world, physics_state);
-
-                    for _ in 0..steps_to_resim {
-                        world.step();
-                    }
-                }
-                None => {
-                    log::error!("Provided BufferStep did not have attached physics state");
-                }
-            },
         }
     }
 
@@ -95,62 +94,89 @@ impl WorldBuffer {
             let step = BufferStep {
                 timestep_id,
                 physics_state: None,
+                state_hash: None,
                 actions: vec![action],
+                commands: Vec::new(),
+            };
+            self.buffer.insert(step.timestep_id, step);
+        }
+    }
+
+    /// Adds a command to the buffer at the given timestep
+    /// Creates a new BufferStep if one does not exist
+    pub fn insert_command(&mut self, command: Command, timestep_id: usize) {
+        if let Some(step) = self.buffer.get_mut(&timestep_id) {
+            step.commands.push(command);
+        } else {
+            let step = BufferStep {
+                timestep_id,
+                physics_state: None,
+                state_hash: None,
+                actions: Vec::new(),
+                commands: vec![command],
             };
             self.buffer.insert(step.timestep_id, step);
         }
     }
 
-    /// Executes all actions in the buffer at the given timestep
+    /// Discards all actions and commands queued for the given timestep
+    pub fn clear_actions(&mut self, timestep_id: usize) {
+        if let Some(step) = self.buffer.get_mut(&timestep_id) {
+            step.actions.clear();
+            step.commands.clear();
+        }
+    }
+
+    /// Executes all actions and commands in the buffer at the given timestep.
+    /// They are applied in a single order (see OperationOrder), regardless of the order they were queued in
     pub fn execute_actions(&mut self, timestep_id: usize, physics: &mut PhysicsState) {
-        // TODO SORT ACTIONS FIRST
-
-        if let Some(step) = self.buffer.get(&timestep_id) {
-            for action in step.actions.iter() {
-                let node = action.node.clone();
-                match action.operation {
-                    Operation::AddNode => {
-                        add_node_to_world(node, physics);
-                    }
-                    Operation::RemoveNode => {
-                        remove_node_from_world(node, physics);
-                    }
-                    Operation::ConfigureNode => {
-                        configure_node(node);
-                    }
-                    Operation::MoveNode => {
-                        if let Some(movement) = action.data.get("movement") {
-                            match Vector3::try_from_variant(&movement) {
-                                Ok(desired_movement) => {
-                                    move_node(node, desired_movement, physics);
-                                }
-                                Err(e) => {
-                                    log::error!("MoveNode action invalid 'movement' data: {}", e);
-                                }
-                            }
-                        } else {
-                            log::error!(
-                                "MoveNode action missing 'movement' data: {:?}",
-                                action.data
-                            );
-                        }
-                    }
-                }
+        let Some(step) = self.buffer.get(&timestep_id) else {
+            return;
+        };
+
+        // Joints go last so that the bodies they connect have already been added this step
+        let mut queued: Vec<_> = step
+            .actions
+            .iter()
+            .map(|action| {
+                let joint = action.node.is_instance_valid() && is_joint(&action.node);
+                ((joint, action_order(action)), Queued::Action(action))
+            })
+            .chain(step.commands.iter().map(|command| {
+                let payload = bincode::serialize(command).unwrap_or_default();
+                let order = (command.cuid().to_string(), command.order(), payload);
+                ((false, order), Queued::Command(command))
+            }))
+            .collect();
+        queued.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        for (_, queued) in queued {
+            match queued {
+                Queued::Action(action) => execute_action(action, physics),
+                Queued::Command(command) => command.execute(physics),
             }
         }
     }
 
     /// Called whenever the world is stepped.
     /// Adds the next timestep's BufferStep with empty actions list.
-    pub fn on_world_stepped(&mut self, next_timestep_id: usize, resulting_state: Option<Vec<u8>>) {
+    pub fn on_world_stepped(
+        &mut self,
+        next_timestep_id: usize,
+        resulting_state: Option<Vec<u8>>,
+        state_hash: u64,
+    ) {
         if let Some(phx_state) = resulting_state {
             if let Some(existing) = self.buffer.get_mut(&next_timestep_id) {
                 existing.physics_state = Some(phx_state);
+                existing.state_hash = Some(state_hash);
             } else {
                 let step = BufferStep {
                     timestep_id: next_timestep_id,
                     physics_state: Some(phx_state),
+                    state_hash: Some(state_hash),
                     actions: Vec::new(),
+                    commands: Vec::new(),
                 };
                 self.buffer.insert(step.timestep_id, step);
             }
@@ -162,13 +188,14 @@ impl WorldBuffer {
         }
     }
 
-    /// Removes all inner physics states from BufferSteps after the given timestep
+    /// Removes all inner physics states (and their checksums) from BufferSteps after the given timestep
     pub fn mark_stale_after(&mut self, timestep_id: usize) {
         let keys: Vec<usize> = self.buffer.keys().cloned().collect();
         for key in keys {
             if key > timestep_id {
                 if let Some(step) = self.buffer.get_mut(&key) {
                     step.physics_state = None;
+                    step.state_hash = None;
                 }
             }
         }
@@ -187,3 +214,45 @@ impl WorldBuffer {
         self.buffer.remove(&oldest);
     }
 }
+
+/// An action or a command, in the order they are executed in
+enum Queued<'a> {
+    Action(&'a Action),
+    Command(&'a Command),
+}
+
+fn execute_action(action: &Action, physics: &mut PhysicsState) {
+    // Nodes may have been freed since the action was buffered (e.g. when resimulating)
+    if !action.node.is_instance_valid() {
+        return;
+    }
+
+    let node = action.node.clone();
+    match action.operation {
+        Operation::AddNode => {
+            add_node_to_world(node, physics);
+        }
+        Operation::ConfigureNode => {
+            configure_node(node, physics);
+        }
+        Operation::MoveNode => {
+            if let Some(movement) = action.data.get("movement") {
+                match Vector3::try_from_variant(&movement) {
+                    Ok(desired_movement) => {
+                        move_node(node, desired_movement, physics);
+                    }
+                    Err(e) => {
+                        log::error!("MoveNode action invalid 'movement' data: {}", e);
+                    }
+                }
+            } else {
+                log::error!("MoveNode action missing 'movement' data: {:?}", action.data);
+            }
+        }
+        Operation::RotateNode => {
+            rotate_node(node, &action.data, physics);
+        }
+        // Removals and forces are queued as commands, see interface::ingest_action
+        _ => log::error!("'{:?}' cannot be executed as an action", action.operation),
+    }
+}
@@ -0,0 +1,301 @@
+use rapier3d::control::{
+    CharacterCollision, EffectiveCharacterMovement, KinematicCharacterController,
+};
+use rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::state::PhysicsState;
+
+/*
+    Kinematic character movement: the controller slides the character's shape along the colliders in its way,
+    pushes the dynamic bodies it hits, and the resulting translation is applied during the next step.
+
+    Characters standing on a moving body (a platform) are carried by it. The platform is the body under the floor
+    the character hit during its last move, its displacement over one step is added to the desired movement.
+    The platform state changes the following moves, so it is kept in the physics state (and its snapshots)
+
+    Characters are rotated through their next kinematic rotation, like their translation. Rotations requested
+    during a step are applied on top of each other, in the order of the actions
+*/
+
+/// What a character does with the velocity of the platform it leaves, like CharacterBody3D.platform_on_leave
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlatformLeave {
+    AddVelocity,       // Keeps moving with the platform's velocity until it lands
+    AddUpwardVelocity, // Same, but the downward part of the platform's velocity is ignored
+    DoNothing,
+}
+
+/// Platform a character stands on, kept between moves in PhysicsState.character_platforms
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct PlatformState {
+    pub body: Option<RigidBodyHandle>,
+    pub velocity: Vector<Real>, // Velocity of the platform where the character stands, kept after leaving it
+    pub inherited_velocity: Vector<Real>, // Added to the movement while airborne after leaving a platform
+}
+
+/// A rotation requested for a character
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CharacterRotation {
+    RotateBy(Rotation<Real>), // In world space
+    Face(Vector<Real>), // Turns around the character's up axis until its forward axis (-Z) points in this direction
+}
+
+/// Outcome of moving a character once
+pub struct CharacterMove {
+    pub movement: EffectiveCharacterMovement,
+    pub collisions: Vec<CharacterCollision>,
+    pub stepped_up: bool, // The character climbed a step thanks to its autostep settings
+}
+
+/// Controller of a floating character (e.g. flying or swimming): it doesn't snap to the ground, climb steps
+/// or walk up slopes, every surface is a wall it slides along
+pub fn floating_controller(
+    controller: KinematicCharacterController,
+) -> KinematicCharacterController {
+    KinematicCharacterController {
+        autostep: None,
+        snap_to_ground: None,
+        max_slope_climb_angle: 0.0,
+        ..controller
+    }
+}
+
+/// Moves the character body with the given handle by up to desired_movement, colliding with everything but itself.
+/// Floating characters are never grounded, see floating_controller, and have no platform.
+/// Returns None if the body doesn't exist or has no collider
+pub fn move_character(
+    controller: &KinematicCharacterController,
+    floating: bool,
+    on_leave: PlatformLeave,
+    handle: RigidBodyHandle,
+    desired_movement: Vector<Real>,
+    physics: &mut PhysicsState,
+) -> Option<CharacterMove> {
+    let body = physics.bodies.get(handle)?;
+    let collider = physics.colliders.get(*body.colliders().first()?)?;
+    let mass = body.mass();
+    let filter = QueryFilter::new().exclude_rigid_body(handle);
+    let dt = physics.integration_parameters.dt;
+    let raw = handle.into_raw_parts();
+    let mut platform = physics
+        .character_platforms
+        .get(&raw)
+        .cloned()
+        .unwrap_or_default();
+
+    let carried = match platform.body.and_then(|body| physics.bodies.get(body)) {
+        Some(platform_body) => {
+            let (displacement, velocity) =
+                platform_motion(platform_body, &collider.position().translation.vector, dt);
+            platform.velocity = velocity;
+            displacement
+        }
+        None => platform.inherited_velocity * dt,
+    };
+    let desired_movement = desired_movement + carried;
+
+    let mut collisions = vec![];
+    // The controller only uses dt to carry characters along the kinematic bodies they hit, which would add up
+    // with the platform displacement above
+    let mut movement = controller.move_shape(
+        0.0,
+        &physics.bodies,
+        &physics.colliders,
+        &physics.query_pipeline,
+        collider.shape(),
+        collider.position(),
+        desired_movement,
+        filter,
+        |c| collisions.push(c),
+    );
+
+    // Apply impulses to other rigidbodies that were contacted
+    controller.solve_character_collision_impulses(
+        dt,
+        &mut physics.bodies,
+        &physics.colliders,
+        &physics.query_pipeline,
+        collider.shape(),
+        mass,
+        &*collisions,
+        filter,
+    );
+
+    if floating {
+        movement.grounded = false;
+        movement.is_sliding_down_slope = false;
+    }
+    update_platform(
+        controller,
+        floating,
+        on_leave,
+        &mut platform,
+        &movement,
+        &collisions,
+        physics,
+    );
+    physics.character_platforms.insert(raw, platform);
+
+    let body = &mut physics.bodies[handle];
+    let pose = body.position();
+    body.set_next_kinematic_translation(pose.translation.vector + movement.translation);
+
+    let stepped_up = stepped_up(controller, desired_movement, &movement, &collisions);
+    Some(CharacterMove {
+        movement,
+        collisions,
+        stepped_up,
+    })
+}
+
+/// Rotates the character body with the given handle, the rotation isn't swept so it can overlap nearby colliders
+pub fn rotate_character(
+    handle: RigidBodyHandle,
+    rotation: CharacterRotation,
+    physics: &mut PhysicsState,
+) {
+    let Some(body) = physics.bodies.get_mut(handle) else {
+        return;
+    };
+    let current = body.next_position().rotation;
+    let next = match rotation {
+        CharacterRotation::RotateBy(rotation) => rotation * current,
+        CharacterRotation::Face(direction) => {
+            let up = current * Vector::y_axis();
+            let forward = direction - *up * direction.dot(&up);
+            if forward.norm() <= Real::EPSILON {
+                return; // Facing along the up axis, no turn points there
+            }
+            // face_towards points the +Z axis at the direction, Godot's forward is -Z
+            Rotation::face_towards(&-forward, &up)
+        }
+    };
+    body.set_next_kinematic_rotation(next);
+}
+
+/// Rotates the character body with the given handle so that its up axis (+Y) follows the floor normal after a move,
+/// or the controller's up direction while airborne. Its rotation is kept if it stands on a floor it didn't hit
+pub fn align_character(
+    controller: &KinematicCharacterController,
+    result: &CharacterMove,
+    handle: RigidBodyHandle,
+    physics: &mut PhysicsState,
+) {
+    let target = match floor_collision(controller, &result.collisions) {
+        Some(floor) => floor.hit.normal1,
+        None if result.movement.grounded => return,
+        None => controller.up,
+    };
+    let Some(body) = physics.bodies.get_mut(handle) else {
+        return;
+    };
+    let current = body.next_position().rotation;
+    let up = current * Vector::y_axis();
+    let alignment = Rotation::rotation_between_axis(&up, &target).unwrap_or_else(|| {
+        // Upside down, any half turn around a horizontal axis works
+        Rotation::from_axis_angle(&(current * Vector::x_axis()), std::f32::consts::PI)
+    });
+    body.set_next_kinematic_rotation(alignment * current);
+}
+
+/// The collision whose normal is closest to the controller's up direction, among those it can walk on
+pub fn floor_collision<'a>(
+    controller: &KinematicCharacterController,
+    collisions: impl IntoIterator<Item = &'a CharacterCollision>,
+) -> Option<&'a CharacterCollision> {
+    let up = controller.up;
+    collisions
+        .into_iter()
+        .filter(|collision| up.angle(&collision.hit.normal1) <= controller.max_slope_climb_angle)
+        .max_by(|a, b| a.hit.normal1.dot(&up).total_cmp(&b.hit.normal1.dot(&up)))
+}
+
+/// Normal of the floor hit during a movement, or the up direction if the floor was not hit (e.g. when standing still)
+pub fn floor_normal<'a>(
+    controller: &KinematicCharacterController,
+    collisions: impl IntoIterator<Item = &'a CharacterCollision>,
+) -> Vector<Real> {
+    match floor_collision(controller, collisions) {
+        Some(floor) => floor.hit.normal1.into_inner(),
+        None => controller.up.into_inner(),
+    }
+}
+
+/// Rapier doesn't report steps, but only a step can lift the character above its desired movement
+/// after hitting a wall, which it otherwise slides along or stops at
+fn stepped_up(
+    controller: &KinematicCharacterController,
+    desired_movement: Vector<Real>,
+    movement: &EffectiveCharacterMovement,
+    collisions: &[CharacterCollision],
+) -> bool {
+    if controller.autostep.is_none() {
+        return false;
+    }
+
+    let up = controller.up;
+    let rise = movement.translation.dot(&up) - desired_movement.dot(&up);
+    let hit_wall = collisions.iter().any(|collision| {
+        let normal = collision.hit.normal1;
+        let is_ceiling = normal.dot(&up) < 0.0; // Same classification as Rapier's autostep
+        up.angle(&normal) >= controller.max_slope_climb_angle && !is_ceiling
+    });
+
+    hit_wall && rise > 1.0e-4 // Above floating point noise
+}
+
+/// Displacement over dt of the point of the body at the given position, and the body's velocity there
+fn platform_motion(
+    body: &RigidBody,
+    position: &Vector<Real>,
+    dt: Real,
+) -> (Vector<Real>, Vector<Real>) {
+    let arm = position - body.center_of_mass().coords;
+    let rotated = Rotation::new(*body.angvel() * dt) * arm;
+    let displacement = body.linvel() * dt + (rotated - arm);
+    let velocity = body.linvel() + body.angvel().cross(&arm);
+    (displacement, velocity)
+}
+
+/// Finds the platform under the character after a move, and applies on_leave when the character left its platform
+fn update_platform(
+    controller: &KinematicCharacterController,
+    floating: bool,
+    on_leave: PlatformLeave,
+    platform: &mut PlatformState,
+    movement: &EffectiveCharacterMovement,
+    collisions: &[CharacterCollision],
+    physics: &PhysicsState,
+) {
+    let up = controller.up;
+    let floor = floor_collision(controller, collisions);
+    let moving_body = |collider: ColliderHandle| {
+        let body = physics.colliders.get(collider)?.parent()?;
+        (!physics.bodies.get(body)?.is_fixed()).then_some(body)
+    };
+
+    let previous = platform.body;
+    platform.body = match (floating, floor) {
+        (true, _) => None,
+        (false, Some(floor)) => moving_body(floor.handle),
+        // Standing still, the floor isn't hit but the character is still on it
+        (false, None) if movement.grounded => {
+            previous.filter(|body| physics.bodies.contains(*body))
+        }
+        (false, None) => None,
+    };
+
+    if movement.grounded || platform.body.is_some() {
+        platform.inherited_velocity = Vector::zeros();
+    } else if previous.is_some() {
+        platform.inherited_velocity = match on_leave {
+            PlatformLeave::AddVelocity => platform.velocity,
+            PlatformLeave::AddUpwardVelocity => {
+                let downward = platform.velocity.dot(&up).min(0.0);
+                platform.velocity - *up * downward
+            }
+            PlatformLeave::DoNothing => Vector::zeros(),
+        };
+    }
+}
@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+
+use super::descriptor::{BodyDescriptor, ColliderDescriptor};
+use super::objects::{apply_force, insert_body, insert_collider, remove_object, BodyForce};
+use super::settings::WorldParameter;
+use super::state::PhysicsState;
+
+/*
+    Commands are the Godot independent counterpart of interface::Action.
+    They are queued in the world buffer like actions, so they are replayed when resimulating
+*/
+
+#[derive(Serialize, Deserialize, Clone)]
+pub enum Command {
+    AddBody(BodyDescriptor),
+    AddSensor(ColliderDescriptor), // A collider without a parent body, like the colliders of an area
+    Remove { cuid: String },
+    Force { cuid: String, force: BodyForce },
+    SetParameter(WorldParameter), // Applies to the whole world, executed before the commands of any object
+}
+
+impl Command {
+    pub fn cuid(&self) -> &str {
+        match self {
+            Command::AddBody(body) => &body.cuid,
+            Command::AddSensor(collider) => &collider.cuid,
+            Command::Remove { cuid } | Command::Force { cuid, .. } => cuid,
+            Command::SetParameter(_) => "",
+        }
+    }
+
+    pub fn order(&self) -> OperationOrder {
+        match self {
+            Command::AddBody(_) | Command::AddSensor(_) | Command::SetParameter(_) => {
+                OperationOrder::Add
+            }
+            Command::Force { force, .. } => force.order(),
+            Command::Remove { .. } => OperationOrder::Remove,
+        }
+    }
+
+    pub fn execute(&self, physics: &mut PhysicsState) {
+        match self {
+            Command::AddBody(body) => {
+                insert_body(body, physics);
+            }
+            Command::AddSensor(collider) => {
+                insert_collider(collider, None, physics);
+            }
+            Command::Remove { cuid } => remove_object(cuid, physics),
+            Command::Force { cuid, force } => apply_force(cuid, force, physics),
+            Command::SetParameter(parameter) => parameter.apply(physics),
+        }
+    }
+}
+
+/// Order in which the operations queued for a single object are applied.
+/// Shared by actions and commands, so an operation is applied at the same point whichever way it was queued.
+/// Every operation has its own place so that two different operations never compare equal
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub enum OperationOrder {
+    Add,
+    Configure,
+    Move,
+    Rotate,
+    ResetForces,
+    SetLinearVelocity,
+    SetAngularVelocity,
+    ApplyImpulse,
+    ApplyImpulseAtPoint,
+    ApplyTorqueImpulse,
+    AddForce,
+    AddTorque,
+    Remove,
+}
@@ -0,0 +1,402 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fmt;
+
+use super::state::DeserializedPhysicsSnapshot;
+
+/*
+    Delta snapshots only contain what changed since a baseline snapshot both peers already have
+
+    Every part of the snapshot (bodies, colliders, broad phase, narrow phase, islands, joints, lookup table, world
+    parameters, gravity overrides, character platforms) that changed is serialized and XORed with the same part of the baseline, so the
+    bytes that did not change become zeros. The delta is then LZ4 compressed, which removes the runs of zeros.
+    Parts are restored whole and byte for byte, the receiver resumes the exact same simulation
+
+    version (u32) | CRC32 of the compressed payload (u32) | LZ4 compressed bincode payload
+
+    All integers are little endian
+*/
+
+pub const DELTA_FORMAT_VERSION: u32 = 6;
+const HEADER_LEN: usize = 8;
+
+/// Serialized bytes of a part XORed with the serialized bytes of the same part in the baseline
+#[derive(Serialize, Deserialize)]
+pub struct XorPatch(Vec<u8>);
+
+#[derive(Serialize, Deserialize)]
+pub struct DeltaSnapshot {
+    pub base_tick: usize,
+    pub timestep_id: usize,
+    pub bodies: Option<XorPatch>,
+    pub colliders: Option<XorPatch>,
+    pub broad_phase: Option<XorPatch>,
+    pub narrow_phase: Option<XorPatch>,
+    pub island_manager: Option<XorPatch>,
+    pub impulse_joints: Option<XorPatch>,
+    pub multibody_joints: Option<XorPatch>,
+    pub lookup_table: Option<XorPatch>,
+    pub gravity: Option<XorPatch>,
+    pub integration_parameters: Option<XorPatch>,
+    pub gravity_overrides: Option<XorPatch>,
+    pub character_platforms: Option<XorPatch>,
+}
+
+/// Reasons a delta snapshot can not be created or applied
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeltaError {
+    InvalidDelta,
+    UnsupportedVersion { version: u32 },
+    ChecksumMismatch,
+    MissingTick { tick: usize },
+    BaselineMismatch { expected: usize, got: usize },
+}
+
+impl DeltaError {
+    /// Stable identifier exposed to Godot
+    pub fn code(&self) -> &'static str {
+        match self {
+            DeltaError::InvalidDelta => "INVALID_DELTA",
+            DeltaError::UnsupportedVersion { .. } => "UNSUPPORTED_VERSION",
+            DeltaError::ChecksumMismatch => "CHECKSUM_MISMATCH",
+            DeltaError::MissingTick { .. } => "MISSING_TICK",
+            DeltaError::BaselineMismatch { .. } => "BASELINE_MISMATCH",
+        }
+    }
+}
+
+impl fmt::Display for DeltaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeltaError::InvalidDelta => write!(f, "Delta snapshot could not be unpacked"),
+            DeltaError::UnsupportedVersion { version } => write!(
+                f,
+                "Delta snapshot format version {} is not supported (expected {})",
+                version, DELTA_FORMAT_VERSION
+            ),
+            DeltaError::ChecksumMismatch => {
+                write!(
+                    f,
+                    "Delta snapshot checksum does not match, the data is corrupted"
+                )
+            }
+            DeltaError::MissingTick { tick } => write!(f, "Tick {} is not in the buffer", tick),
+            DeltaError::BaselineMismatch { expected, got } => write!(
+                f,
+                "Delta snapshot was created against tick {} but applied to tick {}",
+                got, expected
+            ),
+        }
+    }
+}
+
+/// Encodes the changes needed to turn the base snapshot into the target snapshot
+pub fn create_delta(
+    base: &DeserializedPhysicsSnapshot,
+    target: &DeserializedPhysicsSnapshot,
+) -> bincode::Result<Vec<u8>> {
+    let delta = DeltaSnapshot {
+        base_tick: base.timestep_id,
+        timestep_id: target.timestep_id,
+        bodies: diff(&base.bodies, &target.bodies)?,
+        colliders: diff(&base.colliders, &target.colliders)?,
+        broad_phase: diff(&base.broad_phase, &target.broad_phase)?,
+        narrow_phase: diff(&base.narrow_phase, &target.narrow_phase)?,
+        island_manager: diff(&base.island_manager, &target.island_manager)?,
+        impulse_joints: diff(&base.impulse_joints, &target.impulse_joints)?,
+        multibody_joints: diff(&base.multibody_joints, &target.multibody_joints)?,
+        lookup_table: diff(&base.lookup_table, &target.lookup_table)?,
+        gravity: diff(&base.gravity, &target.gravity)?,
+        integration_parameters: diff(&base.integration_parameters, &target.integration_parameters)?,
+        gravity_overrides: diff(&base.gravity_overrides, &target.gravity_overrides)?,
+        character_platforms: diff(&base.character_platforms, &target.character_platforms)?,
+    };
+
+    let payload = lz4_flex::compress_prepend_size(&bincode::serialize(&delta)?);
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(&DELTA_FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+    bytes.extend_from_slice(&payload);
+    Ok(bytes)
+}
+
+/// Rebuilds the target snapshot from the base snapshot and a delta created against it
+pub fn apply_delta(
+    mut base: DeserializedPhysicsSnapshot,
+    bytes: &[u8],
+) -> Result<DeserializedPhysicsSnapshot, DeltaError> {
+    let delta = unpack_delta(bytes)?;
+    if delta.base_tick != base.timestep_id {
+        return Err(DeltaError::BaselineMismatch {
+            expected: base.timestep_id,
+            got: delta.base_tick,
+        });
+    }
+
+    base.timestep_id = delta.timestep_id;
+    patch(&mut base.bodies, delta.bodies)?;
+    patch(&mut base.colliders, delta.colliders)?;
+    patch(&mut base.broad_phase, delta.broad_phase)?;
+    patch(&mut base.narrow_phase, delta.narrow_phase)?;
+    patch(&mut base.island_manager, delta.island_manager)?;
+    patch(&mut base.impulse_joints, delta.impulse_joints)?;
+    patch(&mut base.multibody_joints, delta.multibody_joints)?;
+    patch(&mut base.lookup_table, delta.lookup_table)?;
+    patch(&mut base.gravity, delta.gravity)?;
+    patch(
+        &mut base.integration_parameters,
+        delta.integration_parameters,
+    )?;
+    patch(&mut base.gravity_overrides, delta.gravity_overrides)?;
+    patch(&mut base.character_platforms, delta.character_platforms)?;
+
+    Ok(base)
+}
+
+/// Validates the header of a delta and decompresses it
+fn unpack_delta(bytes: &[u8]) -> Result<DeltaSnapshot, DeltaError> {
+    if bytes.len() < HEADER_LEN {
+        return Err(DeltaError::InvalidDelta);
+    }
+    let version = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    if version != DELTA_FORMAT_VERSION {
+        return Err(DeltaError::UnsupportedVersion { version });
+    }
+    let checksum = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+    let payload = &bytes[HEADER_LEN..];
+    if crc32fast::hash(payload) != checksum {
+        return Err(DeltaError::ChecksumMismatch);
+    }
+
+    let payload = lz4_flex::decompress_size_prepended(payload).map_err(|e| {
+        log::error!("Failed to decompress delta snapshot: {:?}", e);
+        DeltaError::InvalidDelta
+    })?;
+    bincode::deserialize(&payload).map_err(|e| {
+        log::error!("Failed to unpack delta snapshot: {:?}", e);
+        DeltaError::InvalidDelta
+    })
+}
+
+/// XORs the target bytes with the base bytes, the base is treated as zeros past its end
+fn xor(base: &[u8], target: &[u8]) -> Vec<u8> {
+    let base = base.iter().chain(std::iter::repeat(&0));
+    target.iter().zip(base).map(|(a, b)| a ^ b).collect()
+}
+
+/// Returns a patch turning the base into the target, None if they serialize to the same bytes
+fn diff<T: Serialize>(base: &T, target: &T) -> bincode::Result<Option<XorPatch>> {
+    let base = bincode::serialize(base)?;
+    let target = bincode::serialize(target)?;
+    match base == target {
+        true => Ok(None),
+        false => Ok(Some(XorPatch(xor(&base, &target)))),
+    }
+}
+
+/// Replaces the value with the result of the patch, if any
+fn patch<T: Serialize + DeserializeOwned>(
+    value: &mut T,
+    patch: Option<XorPatch>,
+) -> Result<(), DeltaError> {
+    if let Some(XorPatch(bytes)) = patch {
+        let invalid = |e| {
+            log::error!("Failed to patch delta snapshot: {:?}", e);
+            DeltaError::InvalidDelta
+        };
+        let base = bincode::serialize(value).map_err(invalid)?;
+        *value = bincode::deserialize(&xor(&base, &bytes)).map_err(invalid)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::lookup::HandleKind;
+    use crate::World;
+    use rapier3d::prelude::*;
+
+    /// A floor and a stack of falling balls, registered in the lookup table like Godot nodes would be
+    fn falling_balls(count: usize) -> World {
+        let mut world = World::new_empty();
+        let physics = &mut world.physics;
+        physics
+            .colliders
+            .insert(ColliderBuilder::cuboid(50.0, 0.5, 50.0).translation(vector![0.0, -0.5, 0.0]));
+        for idx in 0..count {
+            add_ball(&mut world, idx);
+        }
+        world
+    }
+
+    fn add_ball(world: &mut World, idx: usize) {
+        let physics = &mut world.physics;
+        let body = RigidBodyBuilder::dynamic().translation(vector![
+            idx as Real * 2.0,
+            2.0 + idx as Real,
+            0.0
+        ]);
+        let handle = physics.bodies.insert(body);
+        physics.colliders.insert_with_parent(
+            ColliderBuilder::ball(0.5),
+            handle,
+            &mut physics.bodies,
+        );
+
+        let cuid = format!("ball{}", idx);
+        let raw = handle.into_raw_parts();
+        physics
+            .lookup_table
+            .godot_to_rapier
+            .insert(cuid.clone(), raw);
+        physics
+            .lookup_table
+            .rapier_to_godot
+            .insert((HandleKind::RigidBody, raw), cuid);
+    }
+
+    fn step(world: &mut World, count: usize) {
+        for _ in 0..count {
+            world.step();
+        }
+    }
+
+    fn buffered(world: &World, tick: usize) -> DeserializedPhysicsSnapshot {
+        let bytes = world.buffer.get_physics_state(tick).unwrap();
+        crate::world::state::unpack_snapshot(bytes).unwrap()
+    }
+
+    fn bytes<T: Serialize>(value: &T) -> Vec<u8> {
+        bincode::serialize(value).unwrap()
+    }
+
+    /// Every part of the snapshot serializes to the same bytes
+    fn assert_same_state(a: &DeserializedPhysicsSnapshot, b: &DeserializedPhysicsSnapshot) {
+        assert_eq!(a.timestep_id, b.timestep_id);
+        assert_eq!(bytes(&a.bodies), bytes(&b.bodies));
+        assert_eq!(bytes(&a.colliders), bytes(&b.colliders));
+        assert_eq!(bytes(&a.broad_phase), bytes(&b.broad_phase));
+        assert_eq!(bytes(&a.narrow_phase), bytes(&b.narrow_phase));
+        assert_eq!(bytes(&a.island_manager), bytes(&b.island_manager));
+        assert_eq!(bytes(&a.impulse_joints), bytes(&b.impulse_joints));
+        assert_eq!(a.lookup_table, b.lookup_table);
+    }
+
+    #[test]
+    fn round_trip_rebuilds_target() {
+        let mut world = falling_balls(4);
+        step(&mut world, 30);
+
+        let delta = world.get_delta_snapshot(10, 30).unwrap();
+        let rebuilt = apply_delta(buffered(&world, 10), &delta).unwrap();
+        assert_same_state(&rebuilt, &buffered(&world, 30));
+    }
+
+    #[test]
+    fn round_trip_with_added_body() {
+        let mut world = falling_balls(2);
+        step(&mut world, 5);
+        add_ball(&mut world, 2);
+        step(&mut world, 5);
+
+        let delta = world.get_delta_snapshot(3, 10).unwrap();
+        let rebuilt = apply_delta(buffered(&world, 3), &delta).unwrap();
+        assert_same_state(&rebuilt, &buffered(&world, 10));
+    }
+
+    #[test]
+    fn unchanged_bodies_are_not_encoded() {
+        let mut world = falling_balls(4);
+        step(&mut world, 400); // Let every ball land and fall asleep
+
+        let delta = unpack_delta(&world.get_delta_snapshot(398, 400).unwrap()).unwrap();
+        assert!(delta.bodies.is_none());
+        assert!(delta.lookup_table.is_none());
+    }
+
+    #[test]
+    fn applying_delta_restores_world() {
+        let mut world = falling_balls(3);
+        step(&mut world, 20);
+        let delta = world.get_delta_snapshot(5, 15).unwrap();
+
+        assert_eq!(world.apply_delta_snapshot(5, &delta), Ok(15));
+        assert_eq!(world.state.timestep_id, 15);
+        assert_eq!(world.get_state_hash(None), world.get_state_hash(Some(15)));
+    }
+
+    #[test]
+    fn applying_delta_marks_later_ticks_stale() {
+        let mut world = falling_balls(3);
+        step(&mut world, 20);
+        let delta = world.get_delta_snapshot(5, 15).unwrap();
+
+        assert_eq!(world.apply_delta_snapshot(5, &delta), Ok(15));
+        assert!(world.buffer.get_physics_state(15).is_some());
+        assert!(world.buffer.get_physics_state(16).is_none());
+        assert_eq!(world.get_state_hash(Some(16)), None);
+    }
+
+    #[test]
+    fn delta_is_smaller_than_snapshot() {
+        // Every ball is still falling or bouncing, so every body changes each tick
+        let mut world = falling_balls(50);
+        step(&mut world, 60);
+        let snapshot = world.buffer.get_physics_state(60).unwrap();
+        let delta = world.get_delta_snapshot(59, 60).unwrap();
+        assert!(
+            delta.len() * 10 < snapshot.len(),
+            "delta of {} bytes for a snapshot of {} bytes",
+            delta.len(),
+            snapshot.len()
+        );
+    }
+
+    #[test]
+    fn rejects_corrupted_delta() {
+        let mut world = falling_balls(1);
+        step(&mut world, 5);
+        let mut delta = world.get_delta_snapshot(1, 5).unwrap();
+        let last = delta.len() - 1;
+        delta[last] ^= 0xff;
+
+        assert_eq!(
+            apply_delta(buffered(&world, 1), &delta).err(),
+            Some(DeltaError::ChecksumMismatch)
+        );
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let mut world = falling_balls(1);
+        step(&mut world, 5);
+        let mut delta = world.get_delta_snapshot(1, 5).unwrap();
+        delta[0..4].copy_from_slice(&(DELTA_FORMAT_VERSION + 1).to_le_bytes());
+
+        assert_eq!(
+            apply_delta(buffered(&world, 1), &delta).err(),
+            Some(DeltaError::UnsupportedVersion {
+                version: DELTA_FORMAT_VERSION + 1
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_other_baselines() {
+        let mut world = falling_balls(1);
+        step(&mut world, 5);
+        let delta = world.get_delta_snapshot(1, 5).unwrap();
+
+        assert_eq!(
+            apply_delta(buffered(&world, 2), &delta).err(),
+            Some(DeltaError::BaselineMismatch {
+                expected: 2,
+                got: 1
+            })
+        );
+        assert_eq!(
+            world.get_delta_snapshot(1, 50).err(),
+            Some(DeltaError::MissingTick { tick: 50 })
+        );
+    }
+}
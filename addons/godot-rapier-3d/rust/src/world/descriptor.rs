@@ -0,0 +1,120 @@
+use rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::gravity::GravityOverride;
+use crate::resources::MaterialProperties;
+
+/*
+    Plain Rust descriptions of the physics objects added to the world, independent of Godot.
+    Nodes are converted to descriptors when they are added, tests build worlds from descriptors directly
+*/
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum BodyKind {
+    Dynamic,
+    Fixed,
+    KinematicPositionBased,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BodyDescriptor {
+    pub cuid: String,
+    pub kind: BodyKind,
+    pub position: Isometry<Real>,
+    pub ccd: bool,
+    pub gravity_scale: Real,
+    pub colliders: Vec<ColliderDescriptor>,
+}
+
+impl BodyDescriptor {
+    pub fn new(cuid: &str, kind: BodyKind, position: Isometry<Real>) -> Self {
+        Self {
+            cuid: cuid.to_string(),
+            kind,
+            position,
+            ccd: false,
+            gravity_scale: 1.0,
+            colliders: Vec::new(),
+        }
+    }
+
+    pub fn with_collider(mut self, collider: ColliderDescriptor) -> Self {
+        self.colliders.push(collider);
+        self
+    }
+
+    pub fn builder(&self) -> RigidBodyBuilder {
+        let builder = match self.kind {
+            BodyKind::Dynamic => RigidBodyBuilder::dynamic(),
+            BodyKind::Fixed => RigidBodyBuilder::fixed(),
+            BodyKind::KinematicPositionBased => RigidBodyBuilder::kinematic_position_based(),
+        };
+        builder
+            .position(self.position)
+            .ccd_enabled(self.ccd)
+            .gravity_scale(self.gravity_scale)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ColliderDescriptor {
+    pub cuid: String,
+    pub shape: SharedShape,
+    pub position: Isometry<Real>, // Relative to the parent body
+    pub groups: InteractionGroups,
+    pub material: MaterialProperties,
+    pub sensor: bool,
+    pub contact_force_threshold: Option<Real>, // None = contact force events disabled
+    pub gravity: Option<GravityOverride>,      // Only used by sensors, see world::gravity
+}
+
+impl ColliderDescriptor {
+    pub fn new(cuid: &str, shape: SharedShape) -> Self {
+        Self {
+            cuid: cuid.to_string(),
+            shape,
+            position: Isometry::identity(),
+            groups: InteractionGroups::all(),
+            material: MaterialProperties::default(),
+            sensor: false,
+            contact_force_threshold: None,
+            gravity: None,
+        }
+    }
+
+    pub fn with_position(mut self, position: Isometry<Real>) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn active_events(&self) -> ActiveEvents {
+        Self::active_events_for(self.contact_force_threshold)
+    }
+
+    /// Events reported by a collider, contact forces only when it has a threshold
+    pub fn active_events_for(contact_force_threshold: Option<Real>) -> ActiveEvents {
+        match contact_force_threshold {
+            Some(_) => ActiveEvents::COLLISION_EVENTS | ActiveEvents::CONTACT_FORCE_EVENTS,
+            None => ActiveEvents::COLLISION_EVENTS,
+        }
+    }
+
+    pub fn builder(&self) -> ColliderBuilder {
+        let mut builder = self
+            .material
+            .apply_to_builder(ColliderBuilder::new(self.shape.clone()).position(self.position))
+            .sensor(self.sensor)
+            .collision_groups(self.groups)
+            .solver_groups(self.groups)
+            .active_events(self.active_events())
+            .contact_force_event_threshold(self.contact_force_threshold.unwrap_or(0.0));
+
+        if self.sensor {
+            // Areas have no parent body so they are treated as fixed. Let them detect kinematic characters too
+            builder = builder.active_collision_types(
+                ActiveCollisionTypes::default() | ActiveCollisionTypes::KINEMATIC_FIXED,
+            );
+        }
+        builder
+    }
+}
@@ -0,0 +1,292 @@
+use rapier3d::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::lookup::{HandleKind, LookupTable};
+use super::state::DeserializedPhysicsSnapshot;
+
+/*
+    Compares two snapshots object by object to pinpoint where two simulations diverged
+
+    Objects are matched by cuid rather than by Rapier handle, since handles of the same
+    object may differ between peers
+*/
+
+/// Cuids of the objects that only exist in one of the snapshots
+#[derive(Default, Debug)]
+pub struct MissingObjects {
+    pub bodies: Vec<String>,
+    pub colliders: Vec<String>,
+    pub joints: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum FieldValue {
+    Vector(Vector<Real>),
+    Rotation(Rotation<Real>),
+    Bool(bool),
+}
+
+/// A single differing field of a body. Magnitude is the distance between both values
+/// (length of the difference for vectors, angle in radians for rotations, 1 for booleans)
+#[derive(Debug)]
+pub struct FieldDiff {
+    pub name: &'static str,
+    pub a: FieldValue,
+    pub b: FieldValue,
+    pub magnitude: Real,
+}
+
+#[derive(Debug)]
+pub struct BodyDiff {
+    pub cuid: String,
+    pub fields: Vec<FieldDiff>,
+}
+
+#[derive(Default, Debug)]
+pub struct SnapshotDiff {
+    pub only_in_a: MissingObjects,
+    pub only_in_b: MissingObjects,
+    pub bodies: Vec<BodyDiff>, // Sorted by cuid, only bodies with at least one differing field
+}
+
+impl SnapshotDiff {
+    pub fn is_identical(&self) -> bool {
+        self.bodies.is_empty()
+            && self.only_in_a.bodies.is_empty()
+            && self.only_in_a.colliders.is_empty()
+            && self.only_in_a.joints.is_empty()
+            && self.only_in_b.bodies.is_empty()
+            && self.only_in_b.colliders.is_empty()
+            && self.only_in_b.joints.is_empty()
+    }
+}
+
+pub fn diff_snapshots(
+    a: &DeserializedPhysicsSnapshot,
+    b: &DeserializedPhysicsSnapshot,
+) -> SnapshotDiff {
+    let mut diff = SnapshotDiff::default();
+
+    let colliders_a = cuids(&a.lookup_table, HandleKind::Collider);
+    let colliders_b = cuids(&b.lookup_table, HandleKind::Collider);
+    diff.only_in_a.colliders = difference(&colliders_a, &colliders_b);
+    diff.only_in_b.colliders = difference(&colliders_b, &colliders_a);
+
+    let joints_a = cuids(&a.lookup_table, HandleKind::ImpulseJoint);
+    let joints_b = cuids(&b.lookup_table, HandleKind::ImpulseJoint);
+    diff.only_in_a.joints = difference(&joints_a, &joints_b);
+    diff.only_in_b.joints = difference(&joints_b, &joints_a);
+
+    let bodies_a = bodies_by_cuid(a);
+    let bodies_b = bodies_by_cuid(b);
+    let body_cuids_a: BTreeSet<&String> = bodies_a.keys().copied().collect();
+    let body_cuids_b: BTreeSet<&String> = bodies_b.keys().copied().collect();
+    diff.only_in_a.bodies = difference(&body_cuids_a, &body_cuids_b);
+    diff.only_in_b.bodies = difference(&body_cuids_b, &body_cuids_a);
+
+    for (cuid, body_a) in &bodies_a {
+        if let Some(body_b) = bodies_b.get(cuid) {
+            let fields = diff_bodies(body_a, body_b);
+            if !fields.is_empty() {
+                diff.bodies.push(BodyDiff {
+                    cuid: cuid.to_string(),
+                    fields,
+                });
+            }
+        }
+    }
+
+    diff
+}
+
+fn diff_bodies(a: &RigidBody, b: &RigidBody) -> Vec<FieldDiff> {
+    let mut fields = vec![];
+
+    let vectors = [
+        ("translation", a.translation(), b.translation()),
+        ("linvel", a.linvel(), b.linvel()),
+        ("angvel", a.angvel(), b.angvel()),
+    ];
+    for (name, value_a, value_b) in vectors {
+        if value_a != value_b {
+            fields.push(FieldDiff {
+                name,
+                a: FieldValue::Vector(*value_a),
+                b: FieldValue::Vector(*value_b),
+                magnitude: (value_a - value_b).norm(),
+            });
+        }
+    }
+
+    if a.rotation() != b.rotation() {
+        fields.push(FieldDiff {
+            name: "rotation",
+            a: FieldValue::Rotation(*a.rotation()),
+            b: FieldValue::Rotation(*b.rotation()),
+            magnitude: a.rotation().angle_to(b.rotation()),
+        });
+    }
+
+    if a.is_sleeping() != b.is_sleeping() {
+        fields.push(FieldDiff {
+            name: "sleeping",
+            a: FieldValue::Bool(a.is_sleeping()),
+            b: FieldValue::Bool(b.is_sleeping()),
+            magnitude: 1.0,
+        });
+    }
+
+    fields
+}
+
+/// Bodies registered in the snapshot's lookup table, keyed by cuid
+fn bodies_by_cuid(snapshot: &DeserializedPhysicsSnapshot) -> BTreeMap<&String, &RigidBody> {
+    snapshot
+        .lookup_table
+        .sorted_handles(HandleKind::RigidBody)
+        .into_iter()
+        .filter_map(|(cuid, raw)| {
+            let handle = RigidBodyHandle::from_raw_parts(raw.0, raw.1);
+            snapshot.bodies.get(handle).map(|body| (cuid, body))
+        })
+        .collect()
+}
+
+fn cuids(lookup_table: &LookupTable, kind: HandleKind) -> BTreeSet<&String> {
+    lookup_table
+        .sorted_handles(kind)
+        .into_iter()
+        .map(|(cuid, _)| cuid)
+        .collect()
+}
+
+/// Cuids in a but not in b, sorted
+fn difference(a: &BTreeSet<&String>, b: &BTreeSet<&String>) -> Vec<String> {
+    a.difference(b).map(|cuid| cuid.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::command::Command;
+    use crate::world::descriptor::{BodyDescriptor, BodyKind, ColliderDescriptor};
+    use crate::world::state::{pack_snapshot, unpack_snapshot};
+    use crate::World;
+
+    fn ball(world: &mut World, cuid: &str, x: Real) {
+        Command::AddBody(
+            BodyDescriptor::new(cuid, BodyKind::Dynamic, Isometry::translation(x, 2.0, 0.0))
+                .with_collider(ColliderDescriptor::new(
+                    &format!("{}_shape", cuid),
+                    SharedShape::ball(0.5),
+                )),
+        )
+        .execute(&mut world.physics);
+    }
+
+    fn joint(world: &mut World, cuid: &str, body1: &str, body2: &str) {
+        let physics = &mut world.physics;
+        let handle = |cuid: &str| {
+            let raw = physics.lookup_table.get_rapier_handle(cuid).unwrap();
+            RigidBodyHandle::from_raw_parts(raw.0, raw.1)
+        };
+        let (body1, body2) = (handle(body1), handle(body2));
+        let joint = physics
+            .impulse_joints
+            .insert(body1, body2, FixedJointBuilder::new(), true);
+        physics
+            .lookup_table
+            .insert(cuid, HandleKind::ImpulseJoint, joint.into_raw_parts());
+    }
+
+    fn snapshot(world: &World) -> DeserializedPhysicsSnapshot {
+        unpack_snapshot(pack_snapshot(world).unwrap()).unwrap()
+    }
+
+    type Change = fn(&mut RigidBody);
+
+    /// A world with a single ball "ball"
+    fn ball_world() -> World {
+        let mut world = World::new_empty();
+        ball(&mut world, "ball", 0.0);
+        world
+    }
+
+    #[test]
+    fn identical_snapshots() {
+        let world = ball_world();
+        assert!(diff_snapshots(&snapshot(&world), &snapshot(&world)).is_identical());
+    }
+
+    #[test]
+    fn lists_objects_only_in_one_snapshot() {
+        let mut a = ball_world();
+        ball(&mut a, "a_only", 3.0);
+        joint(&mut a, "a_joint", "ball", "a_only");
+        let mut b = ball_world();
+        ball(&mut b, "b_only", 6.0);
+        joint(&mut b, "b_joint", "ball", "b_only");
+
+        let diff = diff_snapshots(&snapshot(&a), &snapshot(&b));
+        assert!(!diff.is_identical());
+        assert!(diff.bodies.is_empty());
+        assert_eq!(diff.only_in_a.bodies, ["a_only"]);
+        assert_eq!(diff.only_in_a.colliders, ["a_only_shape"]);
+        assert_eq!(diff.only_in_a.joints, ["a_joint"]);
+        assert_eq!(diff.only_in_b.bodies, ["b_only"]);
+        assert_eq!(diff.only_in_b.colliders, ["b_only_shape"]);
+        assert_eq!(diff.only_in_b.joints, ["b_joint"]);
+    }
+
+    #[test]
+    fn reports_each_body_field_and_its_magnitude() {
+        let a = snapshot(&ball_world());
+        // Field name, change applied to the body of b, expected magnitude
+        let changes: [(&str, Change, Real); 5] = [
+            (
+                "translation",
+                |body| body.set_translation(vector![0.0, 2.0, 0.5], false),
+                0.5,
+            ),
+            (
+                "rotation",
+                |body| body.set_rotation(Rotation::new(vector![0.0, 0.25, 0.0]), false),
+                0.25,
+            ),
+            (
+                "linvel",
+                |body| body.set_linvel(vector![3.0, 4.0, 0.0], false),
+                5.0,
+            ),
+            (
+                "angvel",
+                |body| body.set_angvel(vector![0.0, 0.0, 2.0], false),
+                2.0,
+            ),
+            ("sleeping", |body| body.sleep(), 1.0),
+        ];
+
+        for (name, change, magnitude) in changes {
+            let mut world = ball_world();
+            let raw = world
+                .physics
+                .lookup_table
+                .get_rapier_handle("ball")
+                .unwrap();
+            change(&mut world.physics.bodies[RigidBodyHandle::from_raw_parts(raw.0, raw.1)]);
+            let diff = diff_snapshots(&a, &snapshot(&world));
+
+            assert_eq!(diff.bodies.len(), 1, "{}", name);
+            assert_eq!(diff.bodies[0].cuid, "ball");
+            let fields = &diff.bodies[0].fields;
+            assert_eq!(fields.len(), 1, "{}: {:?}", name, fields);
+            assert_eq!(fields[0].name, name);
+            assert!(
+                (fields[0].magnitude - magnitude).abs() < 1.0e-5,
+                "{}: magnitude {}",
+                name,
+                fields[0].magnitude
+            );
+        }
+    }
+}
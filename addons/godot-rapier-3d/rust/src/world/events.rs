@@ -0,0 +1,170 @@
+use rapier3d::crossbeam::channel::{unbounded, Receiver};
+use rapier3d::prelude::*;
+
+use super::lookup::HandleKind;
+use super::state::PhysicsState;
+
+/*
+
+  Collects the collision and contact force events Rapier emits during a step
+  and maps the Rapier handles involved back to UIDs
+
+*/
+
+pub struct EventCollector {
+    pub handler: ChannelEventCollector,
+    collision_recv: Receiver<CollisionEvent>,
+    contact_force_recv: Receiver<ContactForceEvent>,
+}
+
+/// One side of a collision, identified by the UIDs of the collider and the body it is attached to
+#[derive(Clone, Debug)]
+pub struct EventCollider {
+    pub collider_uid: String,
+    pub body_uid: Option<String>, // None for colliders without a parent body (e.g. areas)
+}
+
+#[derive(Clone, Debug)]
+pub enum PhysicsEvent {
+    CollisionStarted {
+        collider1: EventCollider,
+        collider2: EventCollider,
+        sensor: bool,
+    },
+    CollisionStopped {
+        collider1: EventCollider,
+        collider2: EventCollider,
+        sensor: bool,
+    },
+    ContactForce {
+        collider1: EventCollider,
+        collider2: EventCollider,
+        total_force: Vector<Real>,
+    },
+}
+
+impl EventCollider {
+    /// Maps a collider handle to the UIDs of the collider and its parent body.
+    /// Returns None if the collider is not in the lookup table
+    pub fn from_handle(handle: ColliderHandle, physics: &PhysicsState) -> Option<Self> {
+        let raw_handle = handle.into_raw_parts();
+        let collider_uid = physics
+            .lookup_table
+            .get_godot_uid(HandleKind::Collider, &raw_handle)?
+            .to_string();
+
+        let body_uid = physics
+            .colliders
+            .get(handle)
+            .and_then(|collider| collider.parent())
+            .and_then(|parent| {
+                physics
+                    .lookup_table
+                    .get_godot_uid(HandleKind::RigidBody, &parent.into_raw_parts())
+                    .map(str::to_string)
+            });
+
+        Some(Self {
+            collider_uid,
+            body_uid,
+        })
+    }
+}
+
+/// The collider pairs that are currently touching and report collision events,
+/// i.e. the pairs a CollisionStarted was emitted for without a CollisionStopped since
+pub fn touching_pairs(physics: &PhysicsState) -> Vec<(EventCollider, EventCollider)> {
+    let reports_events = |handle: ColliderHandle| {
+        physics
+            .colliders
+            .get(handle)
+            .is_some_and(|c| c.active_events().contains(ActiveEvents::COLLISION_EVENTS))
+    };
+
+    let intersections = physics
+        .narrow_phase
+        .intersection_pairs()
+        .filter(|(_, _, intersecting)| *intersecting)
+        .map(|(h1, h2, _)| (h1, h2));
+    let contacts = physics
+        .narrow_phase
+        .contact_pairs()
+        .filter(|pair| pair.has_any_active_contact)
+        .map(|pair| (pair.collider1, pair.collider2));
+
+    intersections
+        .chain(contacts)
+        .filter(|(h1, h2)| reports_events(*h1) || reports_events(*h2))
+        .filter_map(|(h1, h2)| {
+            Some((
+                EventCollider::from_handle(h1, physics)?,
+                EventCollider::from_handle(h2, physics)?,
+            ))
+        })
+        .collect()
+}
+
+impl Default for EventCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventCollector {
+    pub fn new() -> Self {
+        let (collision_send, collision_recv) = unbounded();
+        let (contact_force_send, contact_force_recv) = unbounded();
+        Self {
+            handler: ChannelEventCollector::new(collision_send, contact_force_send),
+            collision_recv,
+            contact_force_recv,
+        }
+    }
+
+    /// Drains all events received since the last call.
+    /// Events involving colliders that are no longer in the lookup table are dropped
+    pub fn collect(&self, physics: &PhysicsState) -> Vec<PhysicsEvent> {
+        let mut events = Vec::new();
+
+        while let Ok(event) = self.collision_recv.try_recv() {
+            let (handle1, handle2, flags, started) = match event {
+                CollisionEvent::Started(h1, h2, flags) => (h1, h2, flags, true),
+                CollisionEvent::Stopped(h1, h2, flags) => (h1, h2, flags, false),
+            };
+
+            if let (Some(collider1), Some(collider2)) = (
+                EventCollider::from_handle(handle1, physics),
+                EventCollider::from_handle(handle2, physics),
+            ) {
+                let sensor = flags.contains(CollisionEventFlags::SENSOR);
+                events.push(match started {
+                    true => PhysicsEvent::CollisionStarted {
+                        collider1,
+                        collider2,
+                        sensor,
+                    },
+                    false => PhysicsEvent::CollisionStopped {
+                        collider1,
+                        collider2,
+                        sensor,
+                    },
+                });
+            }
+        }
+
+        while let Ok(event) = self.contact_force_recv.try_recv() {
+            if let (Some(collider1), Some(collider2)) = (
+                EventCollider::from_handle(event.collider1, physics),
+                EventCollider::from_handle(event.collider2, physics),
+            ) {
+                events.push(PhysicsEvent::ContactForce {
+                    collider1,
+                    collider2,
+                    total_force: event.total_force,
+                });
+            }
+        }
+
+        events
+    }
+}
@@ -0,0 +1,122 @@
+use rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use super::state::PhysicsState;
+
+/*
+    Areas can override the gravity of the dynamic bodies inside them.
+    Overrides are stored per area collider so that they are part of snapshots, and applied before every step
+    as a velocity change on top of the world gravity that the pipeline applies
+*/
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum GravityMode {
+    Add,     // Added to the world gravity and to the gravity of lower priority areas
+    Replace, // Replaces the world gravity, lower priority areas are ignored
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct GravityOverride {
+    pub area: String, // A body inside several colliders of the same area is only affected once
+    pub mode: GravityMode,
+    pub priority: i32,       // Higher priority areas are applied first
+    pub point: bool,         // Pull towards center instead of along direction
+    pub center: Point<Real>, // Origin of the area, shared by all of its colliders
+    pub direction: Vector<Real>,
+    pub strength: Real,
+}
+
+impl GravityOverride {
+    fn gravity_at(&self, position: &Point<Real>) -> Vector<Real> {
+        match self.point {
+            true => (self.center - position)
+                .try_normalize(Real::EPSILON)
+                .map(|direction| direction * self.strength)
+                .unwrap_or_else(Vector::zeros),
+            false => self.direction * self.strength,
+        }
+    }
+}
+
+/// Total gravity of a body inside the given areas, which must be sorted by priority
+fn combined_gravity(
+    world_gravity: Vector<Real>,
+    areas: &[&GravityOverride],
+    position: &Point<Real>,
+) -> Vector<Real> {
+    let mut gravity = Vector::zeros();
+    for area in areas {
+        gravity += area.gravity_at(position);
+        if area.mode == GravityMode::Replace {
+            return gravity;
+        }
+    }
+    world_gravity + gravity
+}
+
+/// Changes the velocity of every awake dynamic body inside a gravity area by the difference between
+/// its overridden gravity and the world gravity, over one step
+pub fn apply_gravity_overrides(physics: &mut PhysicsState) {
+    if physics.gravity_overrides.is_empty() {
+        return;
+    }
+
+    // Raw body handle -> area cuid -> override. Ordered maps keep this deterministic
+    let mut affected: BTreeMap<(u32, u32), BTreeMap<&str, &GravityOverride>> = BTreeMap::new();
+    for (raw, gravity) in &physics.gravity_overrides {
+        let handle = ColliderHandle::from_raw_parts(raw.0, raw.1);
+        for (a, b, intersecting) in physics.narrow_phase.intersection_pairs_with(handle) {
+            let other = if a == handle { b } else { a };
+            let Some(body) = physics.colliders.get(other).and_then(|c| c.parent()) else {
+                continue;
+            };
+            if intersecting {
+                affected
+                    .entry(body.into_raw_parts())
+                    .or_default()
+                    .entry(gravity.area.as_str())
+                    .or_insert(gravity);
+            }
+        }
+    }
+
+    let world_gravity = physics.gravity;
+    let dt = physics.integration_parameters.dt;
+    for (raw, areas) in affected {
+        let Some(body) = physics
+            .bodies
+            .get_mut(RigidBodyHandle::from_raw_parts(raw.0, raw.1))
+        else {
+            continue;
+        };
+        if !body.is_dynamic() || body.is_sleeping() {
+            continue;
+        }
+
+        let mut areas: Vec<_> = areas.into_values().collect();
+        areas.sort_by_key(|area| Reverse(area.priority)); // Stable, equal priorities stay sorted by cuid
+        let gravity = combined_gravity(world_gravity, &areas, body.center_of_mass());
+        let change = (gravity - world_gravity) * body.gravity_scale() * dt;
+        if change != Vector::zeros() {
+            body.set_linvel(body.linvel() + change, false);
+        }
+    }
+}
+
+/// Wakes the bodies inside the given area collider, e.g. after its gravity changed
+pub fn wake_bodies_inside(collider: ColliderHandle, physics: &mut PhysicsState) {
+    let bodies: Vec<RigidBodyHandle> = physics
+        .narrow_phase
+        .intersection_pairs_with(collider)
+        .filter(|(_, _, intersecting)| *intersecting)
+        .filter_map(|(a, b, _)| {
+            let other = if a == collider { b } else { a };
+            physics.colliders.get(other).and_then(|c| c.parent())
+        })
+        .collect();
+    for body in bodies {
+        physics.islands.wake_up(&mut physics.bodies, body, true);
+    }
+}
@@ -0,0 +1,137 @@
+use rapier3d::prelude::*;
+use std::hash::Hasher;
+use twox_hash::XxHash64;
+
+use super::lookup::HandleKind;
+use super::state::PhysicsState;
+
+/*
+    Stable 64-bit checksum of the simulation, cheap enough to compute and exchange every tick
+
+    Objects are visited in cuid order so the result does not depend on HashMap iteration order,
+    and floats are hashed by their bits so any divergence between peers changes the hash
+*/
+
+const SEED: u64 = 0;
+
+/// Hashes the position, velocity and sleep state of every body and the state of every joint
+pub fn hash_physics_state(physics: &PhysicsState) -> u64 {
+    let mut hasher = XxHash64::with_seed(SEED);
+
+    for (cuid, raw) in physics.lookup_table.sorted_handles(HandleKind::RigidBody) {
+        let handle = RigidBodyHandle::from_raw_parts(raw.0, raw.1);
+        if let Some(body) = physics.bodies.get(handle) {
+            hasher.write(cuid.as_bytes());
+            write_isometry(&mut hasher, body.position());
+            write_vector(&mut hasher, body.linvel());
+            write_vector(&mut hasher, body.angvel());
+            hasher.write_u8(body.is_sleeping() as u8);
+        }
+    }
+
+    for (cuid, raw) in physics
+        .lookup_table
+        .sorted_handles(HandleKind::ImpulseJoint)
+    {
+        let handle = ImpulseJointHandle::from_raw_parts(raw.0, raw.1);
+        if let Some(joint) = physics.impulse_joints.get(handle) {
+            hasher.write(cuid.as_bytes());
+            write_isometry(&mut hasher, &joint.data.local_frame1);
+            write_isometry(&mut hasher, &joint.data.local_frame2);
+            for impulse in joint.impulses.iter() {
+                hasher.write_u32(impulse.to_bits());
+            }
+        }
+    }
+
+    hasher.finish()
+}
+
+fn write_vector(hasher: &mut XxHash64, vector: &Vector<Real>) {
+    for value in vector.iter() {
+        hasher.write_u32(value.to_bits());
+    }
+}
+
+fn write_isometry(hasher: &mut XxHash64, isometry: &Isometry<Real>) {
+    write_vector(hasher, &isometry.translation.vector);
+    for value in isometry.rotation.coords.iter() {
+        hasher.write_u32(value.to_bits());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::command::Command;
+    use crate::world::descriptor::{BodyDescriptor, BodyKind, ColliderDescriptor};
+    use crate::world::state::{pack_snapshot, restore_snapshot};
+    use crate::World;
+
+    fn ball(cuid: &str, x: Real) -> Command {
+        Command::AddBody(
+            BodyDescriptor::new(cuid, BodyKind::Dynamic, Isometry::translation(x, 2.0, 0.0))
+                .with_collider(ColliderDescriptor::new(
+                    &format!("{}_shape", cuid),
+                    SharedShape::ball(0.5),
+                )),
+        )
+    }
+
+    /// Two balls added directly to the physics state, in the given order
+    fn balls(order: [&str; 2]) -> PhysicsState {
+        let mut physics = PhysicsState::new();
+        for cuid in order {
+            let x = if cuid == "a" { 0.0 } else { 3.0 };
+            ball(cuid, x).execute(&mut physics);
+        }
+        physics
+    }
+
+    fn ball_handle(physics: &PhysicsState, cuid: &str) -> RigidBodyHandle {
+        let raw = physics.lookup_table.get_rapier_handle(cuid).unwrap();
+        RigidBodyHandle::from_raw_parts(raw.0, raw.1)
+    }
+
+    #[test]
+    fn equal_after_restore() {
+        let mut world = World::new_empty();
+        world.queue_command(ball("a", 0.0));
+        world.queue_command(ball("b", 3.0));
+        world.step();
+        world.step();
+        let snapshot = pack_snapshot(&world).unwrap();
+        let expected = hash_physics_state(&world.physics);
+
+        let mut restored = World::new_empty();
+        restore_snapshot(&mut restored, snapshot).unwrap();
+        assert_eq!(hash_physics_state(&restored.physics), expected);
+    }
+
+    #[test]
+    fn equal_across_insertion_orders() {
+        let ab = balls(["a", "b"]);
+        let ba = balls(["b", "a"]);
+        assert_ne!(ball_handle(&ab, "a"), ball_handle(&ba, "a"));
+        assert_eq!(hash_physics_state(&ab), hash_physics_state(&ba));
+    }
+
+    #[test]
+    fn changes_with_every_body_field() {
+        let expected = hash_physics_state(&balls(["a", "b"]));
+        let changes: [fn(&mut RigidBody); 5] = [
+            |body| body.set_translation(vector![0.0, 2.0, 1.0e-3], false),
+            |body| body.set_rotation(Rotation::new(vector![0.0, 1.0e-3, 0.0]), false),
+            |body| body.set_linvel(vector![0.0, 0.0, 1.0e-3], false),
+            |body| body.set_angvel(vector![1.0e-3, 0.0, 0.0], false),
+            |body| body.sleep(),
+        ];
+
+        for (idx, change) in changes.into_iter().enumerate() {
+            let mut physics = balls(["a", "b"]);
+            let handle = ball_handle(&physics, "b");
+            change(&mut physics.bodies[handle]);
+            assert_ne!(hash_physics_state(&physics), expected, "change {}", idx);
+        }
+    }
+}
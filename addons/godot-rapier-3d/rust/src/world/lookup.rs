@@ -1,6 +1,5 @@
-use godot::builtin::GString;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 /*
 
   This module facilitates retrieving Rapier objects via UIDs
@@ -8,71 +7,103 @@ use std::collections::HashMap;
 
   GString = Godot UID
   (u32, u32) = Rapier handle raw parts
+  HandleKind = Which Rapier set the handle belongs to (raw parts are only unique within a set)
+
+  The maps are ordered so that snapshots of the same state are byte identical
 
 */
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HandleKind {
+    RigidBody,
+    Collider,
+    ImpulseJoint,
+}
+
+impl HandleKind {
+    pub const ALL: [HandleKind; 3] = [
+        HandleKind::RigidBody,
+        HandleKind::Collider,
+        HandleKind::ImpulseJoint,
+    ];
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct LookupTable {
-    pub godot_to_rapier: HashMap<String, (u32, u32)>,
-    pub rapier_to_godot: HashMap<(u32, u32), String>,
+    pub godot_to_rapier: BTreeMap<String, (u32, u32)>,
+    pub rapier_to_godot: BTreeMap<(HandleKind, (u32, u32)), String>,
     pub snapshot_colliders: Vec<(u32, u32)>,
 }
 
 impl LookupTable {
     pub fn new() -> Self {
         Self {
-            godot_to_rapier: HashMap::new(),
-            rapier_to_godot: HashMap::new(),
+            godot_to_rapier: BTreeMap::new(),
+            rapier_to_godot: BTreeMap::new(),
             snapshot_colliders: Vec::new(),
         }
     }
 
-    pub fn insert(&mut self, godot_uid: GString, rapier_handle: (u32, u32)) {
-        self.godot_to_rapier
-            .insert(godot_uid.to_string(), rapier_handle);
+    pub fn insert(&mut self, uid: &str, kind: HandleKind, rapier_handle: (u32, u32)) {
+        self.godot_to_rapier.insert(uid.to_string(), rapier_handle);
         self.rapier_to_godot
-            .insert(rapier_handle, godot_uid.to_string());
+            .insert((kind, rapier_handle), uid.to_string());
     }
 
     // Collision check
-    pub fn cuid_exists(&self, cuid: &GString) -> bool {
-        let g2r = self.godot_to_rapier.contains_key(cuid.to_string().as_str());
-        let rapier_handle = self.godot_to_rapier.get(cuid.to_string().as_str());
+    pub fn cuid_exists(&self, cuid: &str) -> bool {
+        let g2r = self.godot_to_rapier.contains_key(cuid);
+        let rapier_handle = self.godot_to_rapier.get(cuid);
         if let Some(rapier_handle) = rapier_handle {
-            let r2g = self.rapier_to_godot.contains_key(rapier_handle);
+            let r2g = HandleKind::ALL
+                .iter()
+                .any(|kind| self.rapier_to_godot.contains_key(&(*kind, *rapier_handle)));
             g2r || r2g
         } else {
             g2r
         }
     }
 
-    pub fn get_rapier_handle(&self, godot_uid: &GString) -> Option<&(u32, u32)> {
-        self.godot_to_rapier.get(godot_uid.to_string().as_str())
+    pub fn get_rapier_handle(&self, uid: &str) -> Option<(u32, u32)> {
+        self.godot_to_rapier.get(uid).copied()
     }
 
-    pub fn get_godot_uid(&self, rapier_handle: &(u32, u32)) -> Option<GString> {
-        match self.rapier_to_godot.get(rapier_handle) {
-            Some(uid) => Some(GString::from(uid)),
-            None => None,
-        }
+    pub fn get_godot_uid(&self, kind: HandleKind, rapier_handle: &(u32, u32)) -> Option<&str> {
+        self.rapier_to_godot
+            .get(&(kind, *rapier_handle))
+            .map(String::as_str)
     }
 
-    pub fn remove_by_uid(&mut self, godot_uid: &GString) -> Option<(u32, u32)> {
-        if let Some(rapier_handle) = self.godot_to_rapier.remove(godot_uid.to_string().as_str()) {
-            self.rapier_to_godot.remove(&rapier_handle);
-            Some(rapier_handle)
-        } else {
-            None
+    pub fn remove_by_uid(&mut self, uid: &str) -> Option<(u32, u32)> {
+        let rapier_handle = self.godot_to_rapier.remove(uid)?;
+        for kind in HandleKind::ALL {
+            if self.get_godot_uid(kind, &rapier_handle) == Some(uid) {
+                self.rapier_to_godot.remove(&(kind, rapier_handle));
+            }
         }
+        Some(rapier_handle)
     }
 
-    pub fn remove_by_handle(&mut self, rapier_handle: &(u32, u32)) -> Option<GString> {
-        if let Some(godot_uid) = self.rapier_to_godot.remove(rapier_handle) {
-            self.godot_to_rapier.remove(&godot_uid);
-            Some(GString::from(godot_uid))
-        } else {
-            None
-        }
+    pub fn remove_by_handle(
+        &mut self,
+        kind: HandleKind,
+        rapier_handle: &(u32, u32),
+    ) -> Option<String> {
+        let godot_uid = self.rapier_to_godot.remove(&(kind, *rapier_handle))?;
+        self.godot_to_rapier.remove(&godot_uid);
+        Some(godot_uid)
+    }
+
+    /// Returns every cuid registered for the given kind of handle with its handle, sorted by cuid
+    pub fn sorted_handles(&self, kind: HandleKind) -> Vec<(&String, (u32, u32))> {
+        let mut handles: Vec<(&String, (u32, u32))> = self
+            .rapier_to_godot
+            .iter()
+            .filter(|((handle_kind, _), _)| *handle_kind == kind)
+            .map(|((_, raw), cuid)| (cuid, *raw))
+            .collect();
+        handles.sort();
+        handles
     }
 
     pub fn insert_snapshot_collider(&mut self, raw_handle: (u32, u32)) {
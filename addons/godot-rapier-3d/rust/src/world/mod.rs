@@ -1,7 +1,20 @@
 mod buffer;
+pub mod character;
+pub mod command;
+pub mod delta;
+pub mod descriptor;
+pub mod diff;
+pub mod events;
+pub mod gravity;
+pub mod hash;
 pub mod lookup;
+pub mod objects;
+pub mod settings;
 pub mod state;
 pub mod world;
 
 // World contains all physics objects that exist within Rapier
 // This module is responsible for modifying the world, snapshotting and buffering state + interactions
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,191 @@
+use rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::command::OperationOrder;
+use super::descriptor::{BodyDescriptor, ColliderDescriptor};
+use super::lookup::HandleKind;
+use super::state::PhysicsState;
+use crate::utils::is_expensive_shape;
+
+/*
+    Adds, removes and pushes the physics objects described by descriptors, identified by their cuids.
+    Godot nodes go through these functions too, after being converted to descriptors
+*/
+
+/// Raw handles of a body and its colliders, in the order of the descriptor's colliders
+pub struct InsertedBody {
+    pub body: (u32, u32),
+    pub colliders: Vec<(u32, u32)>,
+}
+
+/// Inserts the body and its colliders, and registers them in the lookup table
+pub fn insert_body(descriptor: &BodyDescriptor, physics: &mut PhysicsState) -> InsertedBody {
+    let handle = physics.bodies.insert(descriptor.builder());
+    let colliders = descriptor
+        .colliders
+        .iter()
+        .map(|collider| insert_collider(collider, Some(handle), physics))
+        .collect();
+
+    let body = handle.into_raw_parts();
+    physics
+        .lookup_table
+        .insert(&descriptor.cuid, HandleKind::RigidBody, body);
+    InsertedBody { body, colliders }
+}
+
+/// Inserts the collider, attached to the parent body if there is one, and registers it in the lookup table.
+/// Colliders with expensive shapes are not added to snapshot_colliders
+pub fn insert_collider(
+    descriptor: &ColliderDescriptor,
+    parent: Option<RigidBodyHandle>,
+    physics: &mut PhysicsState,
+) -> (u32, u32) {
+    let built = descriptor.builder().build();
+    let handle = match parent {
+        Some(parent) => physics
+            .colliders
+            .insert_with_parent(built, parent, &mut physics.bodies),
+        None => physics.colliders.insert(built),
+    };
+
+    let raw_handle = handle.into_raw_parts();
+    let lookup_table = &mut physics.lookup_table;
+    lookup_table.insert(&descriptor.cuid, HandleKind::Collider, raw_handle);
+    if !is_expensive_shape(&*descriptor.shape) {
+        lookup_table.insert_snapshot_collider(raw_handle);
+    }
+    if let Some(gravity) = &descriptor.gravity {
+        physics
+            .gravity_overrides
+            .insert(raw_handle, gravity.clone());
+    }
+    raw_handle
+}
+
+/// Removes whatever Rapier object is registered under the given uid.
+/// Colliders attached to a removed body stay in the world until their own uid is removed
+pub fn remove_object(uid: &str, physics: &mut PhysicsState) {
+    let Some(raw_handle) = physics.lookup_table.get_rapier_handle(uid) else {
+        return;
+    };
+    let kind = HandleKind::ALL
+        .into_iter()
+        .find(|kind| physics.lookup_table.get_godot_uid(*kind, &raw_handle) == Some(uid));
+
+    match kind {
+        Some(HandleKind::RigidBody) => {
+            let handle = RigidBodyHandle::from_raw_parts(raw_handle.0, raw_handle.1);
+            // Rapier removes the joints attached to the body with it, forget their uids too
+            let joints: Vec<(u32, u32)> = physics
+                .impulse_joints
+                .attached_joints(handle)
+                .map(|(_, _, joint, _)| joint.into_raw_parts())
+                .collect();
+            for joint in joints {
+                let joint_uid = physics
+                    .lookup_table
+                    .get_godot_uid(HandleKind::ImpulseJoint, &joint)
+                    .map(str::to_string);
+                if let Some(joint_uid) = joint_uid {
+                    physics.lookup_table.remove_by_uid(&joint_uid);
+                }
+            }
+
+            physics.lookup_table.remove_by_uid(uid);
+            physics.character_platforms.remove(&raw_handle);
+            physics.bodies.remove(
+                handle,
+                &mut physics.islands,
+                &mut physics.colliders,
+                &mut physics.impulse_joints,
+                &mut physics.multibody_joints,
+                false,
+            );
+        }
+        Some(HandleKind::Collider) => remove_collider_if_exists(&raw_handle, physics),
+        Some(HandleKind::ImpulseJoint) => {
+            physics.lookup_table.remove_by_uid(uid);
+            physics.impulse_joints.remove(
+                ImpulseJointHandle::from_raw_parts(raw_handle.0, raw_handle.1),
+                true,
+            );
+        }
+        None => {
+            physics.lookup_table.remove_by_uid(uid);
+        }
+    }
+}
+
+/// Removes the given collider handle from all lookup tables and collider set
+pub fn remove_collider_if_exists(raw_handle: &(u32, u32), physics: &mut PhysicsState) {
+    physics
+        .lookup_table
+        .remove_by_handle(HandleKind::Collider, raw_handle);
+    physics.lookup_table.remove_snapshot_collider(raw_handle);
+    physics.gravity_overrides.remove(raw_handle);
+    let handle = ColliderHandle::from_raw_parts(raw_handle.0, raw_handle.1);
+    if physics.colliders.contains(handle) {
+        physics
+            .colliders
+            .remove(handle, &mut physics.islands, &mut physics.bodies, false);
+    }
+}
+
+/// A force, impulse or velocity change applied to a rigid body
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum BodyForce {
+    ResetForces,
+    SetLinearVelocity(Vector<Real>),
+    SetAngularVelocity(Vector<Real>),
+    ApplyImpulse(Vector<Real>),
+    ApplyImpulseAtPoint(Vector<Real>, Point<Real>),
+    ApplyTorqueImpulse(Vector<Real>),
+    AddForce(Vector<Real>),
+    AddTorque(Vector<Real>),
+}
+
+impl BodyForce {
+    /// Order in which the forces queued for a single body are applied
+    pub fn order(&self) -> OperationOrder {
+        match self {
+            BodyForce::ResetForces => OperationOrder::ResetForces,
+            BodyForce::SetLinearVelocity(_) => OperationOrder::SetLinearVelocity,
+            BodyForce::SetAngularVelocity(_) => OperationOrder::SetAngularVelocity,
+            BodyForce::ApplyImpulse(_) => OperationOrder::ApplyImpulse,
+            BodyForce::ApplyImpulseAtPoint(_, _) => OperationOrder::ApplyImpulseAtPoint,
+            BodyForce::ApplyTorqueImpulse(_) => OperationOrder::ApplyTorqueImpulse,
+            BodyForce::AddForce(_) => OperationOrder::AddForce,
+            BodyForce::AddTorque(_) => OperationOrder::AddTorque,
+        }
+    }
+}
+
+/// Applies the force to the rigid body registered under the given uid
+pub fn apply_force(uid: &str, force: &BodyForce, physics: &mut PhysicsState) {
+    let Some(raw) = physics.lookup_table.get_rapier_handle(uid) else {
+        return;
+    };
+    let Some(body) = physics
+        .bodies
+        .get_mut(RigidBodyHandle::from_raw_parts(raw.0, raw.1))
+    else {
+        return;
+    };
+
+    match *force {
+        BodyForce::ResetForces => {
+            body.reset_forces(true);
+            body.reset_torques(true);
+        }
+        BodyForce::SetLinearVelocity(velocity) => body.set_linvel(velocity, true),
+        BodyForce::SetAngularVelocity(velocity) => body.set_angvel(velocity, true),
+        BodyForce::ApplyImpulse(impulse) => body.apply_impulse(impulse, true),
+        BodyForce::ApplyImpulseAtPoint(impulse, point) => {
+            body.apply_impulse_at_point(impulse, point, true)
+        }
+        BodyForce::ApplyTorqueImpulse(torque) => body.apply_torque_impulse(torque, true),
+        BodyForce::AddForce(force) => body.add_force(force, true),
+        BodyForce::AddTorque(torque) => body.add_torque(torque, true),
+    }
+}
@@ -0,0 +1,80 @@
+use rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::num::NonZeroUsize;
+
+use super::state::PhysicsState;
+
+/*
+    Simulation settings, read from the project settings when the world is created.
+    Changing a parameter at runtime goes through the buffer as a command so that it is replayed when resimulating
+*/
+
+/// Settings a world is created with
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorldSettings {
+    pub buffer_length: usize, // Amount of ticks kept in the world buffer
+    pub parameters: Vec<WorldParameter>,
+}
+
+impl Default for WorldSettings {
+    fn default() -> Self {
+        Self {
+            buffer_length: 1000,
+            parameters: Vec::new(),
+        }
+    }
+}
+
+/// A single gravity or integration parameter value
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum WorldParameter {
+    Gravity(Vector<Real>),
+    TicksPerSecond(u32),
+    SolverIterations(usize),
+    AdditionalFrictionIterations(usize),
+    InternalPgsIterations(usize),
+    InternalStabilizationIterations(usize),
+    CcdSubsteps(usize),
+    ContactNaturalFrequency(Real),
+    ContactDampingRatio(Real),
+    JointNaturalFrequency(Real),
+    JointDampingRatio(Real),
+    WarmstartCoefficient(Real),
+    LengthUnit(Real),
+}
+
+impl WorldParameter {
+    pub fn apply(&self, physics: &mut PhysicsState) {
+        let params = &mut physics.integration_parameters;
+        match *self {
+            WorldParameter::Gravity(gravity) => physics.gravity = gravity,
+            WorldParameter::TicksPerSecond(ticks) => {
+                let dt = 1.0 / ticks.max(1) as Real;
+                params.dt = dt;
+                params.min_ccd_dt = dt / 100.0; // Same ratio as Rapier's defaults
+            }
+            WorldParameter::SolverIterations(iterations) => {
+                params.num_solver_iterations =
+                    NonZeroUsize::new(iterations).unwrap_or(NonZeroUsize::MIN)
+            }
+            WorldParameter::AdditionalFrictionIterations(iterations) => {
+                params.num_additional_friction_iterations = iterations
+            }
+            WorldParameter::InternalPgsIterations(iterations) => {
+                params.num_internal_pgs_iterations = iterations
+            }
+            WorldParameter::InternalStabilizationIterations(iterations) => {
+                params.num_internal_stabilization_iterations = iterations
+            }
+            WorldParameter::CcdSubsteps(substeps) => params.max_ccd_substeps = substeps,
+            WorldParameter::ContactNaturalFrequency(value) => {
+                params.contact_natural_frequency = value
+            }
+            WorldParameter::ContactDampingRatio(value) => params.contact_damping_ratio = value,
+            WorldParameter::JointNaturalFrequency(value) => params.joint_natural_frequency = value,
+            WorldParameter::JointDampingRatio(value) => params.joint_damping_ratio = value,
+            WorldParameter::WarmstartCoefficient(value) => params.warmstart_coefficient = value,
+            WorldParameter::LengthUnit(value) => params.length_unit = value,
+        }
+    }
+}
@@ -1,6 +1,11 @@
 use rapier3d::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
 
+use super::character::PlatformState;
+use super::gravity::GravityOverride;
+use super::lookup::HandleKind;
 use crate::{LookupTable, World};
 
 pub struct PhysicsState {
@@ -18,6 +23,8 @@ pub struct PhysicsState {
     pub gravity: Vector<Real>,
     pub hooks: Box<dyn PhysicsHooks>,
     pub lookup_table: LookupTable,
+    pub gravity_overrides: BTreeMap<(u32, u32), GravityOverride>, // Keyed by raw area collider handle
+    pub character_platforms: BTreeMap<(u32, u32), PlatformState>, // Keyed by raw character body handle
 }
 
 impl Default for PhysicsState {
@@ -43,6 +50,8 @@ impl PhysicsState {
             gravity: Vector::y() * -9.81,
             hooks: Box::new(()),
             lookup_table: LookupTable::new(),
+            gravity_overrides: BTreeMap::new(),
+            character_platforms: BTreeMap::new(),
         }
     }
 }
@@ -58,15 +67,84 @@ pub struct DeserializedPhysicsSnapshot {
     pub impulse_joints: ImpulseJointSet,
     pub multibody_joints: MultibodyJointSet,
     pub lookup_table: LookupTable,
+    pub gravity: Vector<Real>,
+    pub integration_parameters: IntegrationParameters,
+    pub gravity_overrides: BTreeMap<(u32, u32), GravityOverride>,
+    pub character_platforms: BTreeMap<(u32, u32), PlatformState>,
+}
+
+/*
+    Snapshots start with a fixed size header, followed by the bincode payload (optionally LZ4 compressed)
+
+    magic (4) | format version (u16) | rapier version (3 x u16) | flags (u16) | CRC32 of the payload (u32)
+
+    All integers are little endian. The CRC32 is computed over the payload as stored, i.e. after compression
+*/
+
+pub const SNAPSHOT_MAGIC: &[u8; 4] = b"GR3D";
+pub const SNAPSHOT_FORMAT_VERSION: u16 = 5;
+/// Version of the rapier3d dependency, snapshots are not compatible across Rapier versions
+pub const RAPIER_VERSION: [u16; 3] = [0, 23, 1];
+/// Flag set when the payload is LZ4 compressed
+pub const SNAPSHOT_FLAG_LZ4: u16 = 1;
+const HEADER_LEN: usize = 18;
+
+/// Reasons a snapshot can fail to unpack
+#[derive(Debug, Clone, PartialEq)]
+pub enum SnapshotError {
+    InvalidMagic,
+    UnsupportedVersion { version: u16 },
+    RapierVersionMismatch { version: [u16; 3] },
+    ChecksumMismatch,
+    InvalidSnapshot,
+}
+
+impl SnapshotError {
+    /// Stable identifier exposed to Godot
+    pub fn code(&self) -> &'static str {
+        match self {
+            SnapshotError::InvalidMagic => "INVALID_MAGIC",
+            SnapshotError::UnsupportedVersion { .. } => "UNSUPPORTED_VERSION",
+            SnapshotError::RapierVersionMismatch { .. } => "RAPIER_VERSION_MISMATCH",
+            SnapshotError::ChecksumMismatch => "CHECKSUM_MISMATCH",
+            SnapshotError::InvalidSnapshot => "INVALID_SNAPSHOT",
+        }
+    }
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::InvalidMagic => write!(f, "Data is not a GR3D snapshot"),
+            SnapshotError::UnsupportedVersion { version } => write!(
+                f,
+                "Snapshot format version {} is not supported (expected {})",
+                version, SNAPSHOT_FORMAT_VERSION
+            ),
+            SnapshotError::RapierVersionMismatch { version } => write!(
+                f,
+                "Snapshot was created with Rapier {} (expected {})",
+                format_version(version),
+                format_version(&RAPIER_VERSION)
+            ),
+            SnapshotError::ChecksumMismatch => {
+                write!(f, "Snapshot checksum does not match, the data is corrupted")
+            }
+            SnapshotError::InvalidSnapshot => write!(f, "Snapshot could not be unpacked"),
+        }
+    }
+}
+
+fn format_version(version: &[u16; 3]) -> String {
+    format!("{}.{}.{}", version[0], version[1], version[2])
 }
 
 pub fn pack_snapshot(world: &World) -> bincode::Result<Vec<u8>> {
-    // NOTE: only cheap colliders are serialized
-    let mut colliders = ColliderSet::new();
-    for raw_handle in &world.physics.lookup_table.snapshot_colliders {
-        let handle = ColliderHandle::from_raw_parts(raw_handle.0, raw_handle.1);
-        if let Some(collider) = world.physics.colliders.get(handle) {
-            colliders.insert(collider.clone());
+    // NOTE: expensive colliders are serialized as placeholders, see restore_expensive_colliders
+    let mut colliders = world.physics.colliders.clone();
+    for handle in expensive_colliders(&colliders, &world.physics.lookup_table) {
+        if let Some(collider) = colliders.get_mut(handle) {
+            collider.set_shape(SharedShape::ball(0.0));
         }
     }
 
@@ -76,46 +154,409 @@ pub fn pack_snapshot(world: &World) -> bincode::Result<Vec<u8>> {
         narrow_phase: world.physics.narrow_phase.clone(),
         island_manager: world.physics.islands.clone(),
         bodies: world.physics.bodies.clone(),
-        colliders: colliders,
+        colliders,
         impulse_joints: world.physics.impulse_joints.clone(),
         multibody_joints: world.physics.multibody_joints.clone(),
         lookup_table: world.physics.lookup_table.clone(),
+        gravity: world.physics.gravity,
+        integration_parameters: world.physics.integration_parameters,
+        gravity_overrides: world.physics.gravity_overrides.clone(),
+        character_platforms: world.physics.character_platforms.clone(),
     };
 
-    bincode::serialize(&output)
+    let payload = bincode::serialize(&output)?;
+    Ok(write_header(payload, world.compress_snapshots))
 }
 
-fn unpack_snapshot(bytes: Vec<u8>) -> Option<DeserializedPhysicsSnapshot> {
-    let deserialized: bincode::Result<DeserializedPhysicsSnapshot> = bincode::deserialize(&bytes);
-    match deserialized {
-        Ok(snapshot) => Some(snapshot),
-        Err(e) => {
-            log::error!("Failed to unpack snapshot: {:?}", e);
-            None
-        }
+/// Prepends the snapshot header to the payload, compressing the payload first if requested
+fn write_header(payload: Vec<u8>, compress: bool) -> Vec<u8> {
+    let (payload, flags) = match compress {
+        true => (lz4_flex::compress_prepend_size(&payload), SNAPSHOT_FLAG_LZ4),
+        false => (payload, 0),
+    };
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(SNAPSHOT_MAGIC);
+    bytes.extend_from_slice(&SNAPSHOT_FORMAT_VERSION.to_le_bytes());
+    for part in RAPIER_VERSION {
+        bytes.extend_from_slice(&part.to_le_bytes());
+    }
+    bytes.extend_from_slice(&flags.to_le_bytes());
+    bytes.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+    bytes.extend_from_slice(&payload);
+    bytes
+}
+
+/// Validates the snapshot header and returns the decompressed payload
+fn read_header(bytes: &[u8]) -> Result<Vec<u8>, SnapshotError> {
+    if bytes.len() < HEADER_LEN || &bytes[0..4] != SNAPSHOT_MAGIC {
+        return Err(SnapshotError::InvalidMagic);
+    }
+    let u16_at = |offset: usize| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+
+    let version = u16_at(4);
+    if version != SNAPSHOT_FORMAT_VERSION {
+        return Err(SnapshotError::UnsupportedVersion { version });
+    }
+    let rapier_version = [u16_at(6), u16_at(8), u16_at(10)];
+    if rapier_version != RAPIER_VERSION {
+        return Err(SnapshotError::RapierVersionMismatch {
+            version: rapier_version,
+        });
+    }
+    let flags = u16_at(12);
+    let checksum = u32::from_le_bytes([bytes[14], bytes[15], bytes[16], bytes[17]]);
+
+    let payload = &bytes[HEADER_LEN..];
+    if crc32fast::hash(payload) != checksum {
+        return Err(SnapshotError::ChecksumMismatch);
+    }
+
+    match flags & SNAPSHOT_FLAG_LZ4 {
+        0 => Ok(payload.to_vec()),
+        _ => lz4_flex::decompress_size_prepended(payload).map_err(|e| {
+            log::error!("Failed to decompress snapshot: {:?}", e);
+            SnapshotError::InvalidSnapshot
+        }),
     }
 }
 
-/// Overwrite the current state of the given world to the given snapshot state
-pub fn restore_snapshot(world: &mut World, bytes: Vec<u8>) {
-    if let Some(deserialized) = unpack_snapshot(bytes) {
-        world.state.timestep_id = deserialized.timestep_id;
-        world.physics.broad_phase = deserialized.broad_phase;
-        world.physics.narrow_phase = deserialized.narrow_phase;
-        world.physics.islands = deserialized.island_manager;
-        world.physics.bodies = deserialized.bodies;
-        world.physics.impulse_joints = deserialized.impulse_joints;
-        world.physics.multibody_joints = deserialized.multibody_joints;
-
-        // Carefully handle colliders to not overwrite expensive "eternal" ones
-        for (handle, collider) in deserialized.colliders.iter() {
-            if let Some(collider) = world.physics.colliders.get_mut(handle) {
-                *collider = collider.clone();
-            } else {
-                world.physics.colliders.insert(collider.clone());
+pub fn unpack_snapshot(bytes: Vec<u8>) -> Result<DeserializedPhysicsSnapshot, SnapshotError> {
+    let payload = read_header(&bytes)?;
+    bincode::deserialize(&payload).map_err(|e| {
+        log::error!("Failed to unpack snapshot: {:?}", e);
+        SnapshotError::InvalidSnapshot
+    })
+}
+
+/// Overwrite the current state of the given world to the given snapshot state.
+/// Returns the tick of the restored snapshot
+pub fn restore_snapshot(world: &mut World, bytes: Vec<u8>) -> Result<usize, SnapshotError> {
+    let deserialized = unpack_snapshot(bytes)?;
+    let tick = deserialized.timestep_id;
+    apply_snapshot(world, deserialized);
+    Ok(tick)
+}
+
+/// Overwrite the current state of the given world to an already unpacked snapshot
+pub fn apply_snapshot(world: &mut World, deserialized: DeserializedPhysicsSnapshot) {
+    world.state.timestep_id = deserialized.timestep_id;
+    world.physics.broad_phase = deserialized.broad_phase;
+    world.physics.narrow_phase = deserialized.narrow_phase;
+    world.physics.islands = deserialized.island_manager;
+    world.physics.bodies = deserialized.bodies;
+    world.physics.impulse_joints = deserialized.impulse_joints;
+    world.physics.multibody_joints = deserialized.multibody_joints;
+    world.physics.gravity = deserialized.gravity;
+    world.physics.integration_parameters = deserialized.integration_parameters;
+    world.physics.gravity_overrides = deserialized.gravity_overrides;
+    world.physics.character_platforms = deserialized.character_platforms;
+
+    // Colliders keep their handles and parents, the snapshot's set replaces the current one entirely
+    let live_colliders = std::mem::replace(&mut world.physics.colliders, deserialized.colliders);
+    let live_lookup = std::mem::replace(&mut world.physics.lookup_table, deserialized.lookup_table);
+    restore_expensive_colliders(&mut world.physics, &live_colliders, &live_lookup);
+}
+
+/// Colliders registered by nodes but excluded from snapshot_colliders because their shape is too expensive to serialize
+fn expensive_colliders(colliders: &ColliderSet, lookup_table: &LookupTable) -> Vec<ColliderHandle> {
+    let cheap: HashSet<&(u32, u32)> = lookup_table.snapshot_colliders.iter().collect();
+    colliders
+        .iter()
+        .map(|(handle, _)| handle)
+        .filter(|handle| {
+            let raw = handle.into_raw_parts();
+            !cheap.contains(&raw)
+                && lookup_table
+                    .rapier_to_godot
+                    .contains_key(&(HandleKind::Collider, raw))
+        })
+        .collect()
+}
+
+/// Expensive "eternal" colliders are only placeholders in snapshots. Swap the live colliders back in,
+/// or remove the placeholder if the live collider no longer exists (it can't be rebuilt without its node)
+fn restore_expensive_colliders(
+    physics: &mut PhysicsState,
+    live_colliders: &ColliderSet,
+    live_lookup: &LookupTable,
+) {
+    for handle in expensive_colliders(&physics.colliders, &physics.lookup_table) {
+        let key = (HandleKind::Collider, handle.into_raw_parts());
+        let cuid = physics.lookup_table.rapier_to_godot.get(&key);
+        let live = live_colliders
+            .get(handle)
+            .filter(|_| live_lookup.rapier_to_godot.get(&key) == cuid);
+
+        match live {
+            Some(live) => {
+                if let Some(collider) = physics.colliders.get_mut(handle) {
+                    *collider = live.clone();
+                }
+            }
+            None => {
+                log::warn!(
+                    "Expensive collider {:?} from the snapshot no longer exists and was not restored",
+                    cuid
+                );
+                physics
+                    .colliders
+                    .remove(handle, &mut physics.islands, &mut physics.bodies, false);
+                physics.lookup_table.remove_by_handle(key.0, &key.1);
+                physics.gravity_overrides.remove(&key.1);
             }
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn world_with_body(compress: bool) -> World {
+        let mut world = World::new_empty();
+        world.compress_snapshots = compress;
+        world
+            .physics
+            .bodies
+            .insert(RigidBodyBuilder::dynamic().translation(vector![0.0, 3.0, 0.0]));
+        world.step();
+        world
+    }
+
+    fn packed(compress: bool) -> Vec<u8> {
+        pack_snapshot(&world_with_body(compress)).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        for compress in [false, true] {
+            let snapshot = unpack_snapshot(packed(compress)).unwrap();
+            assert_eq!(snapshot.timestep_id, 1);
+            assert_eq!(snapshot.bodies.len(), 1);
+        }
+    }
+
+    #[test]
+    fn compression_shrinks_snapshot() {
+        assert!(packed(true).len() < packed(false).len());
+        assert_eq!(
+            u16::from_le_bytes([packed(true)[12], packed(true)[13]]),
+            SNAPSHOT_FLAG_LZ4
+        );
+    }
+
+    #[test]
+    fn restore_returns_tick() {
+        let mut world = World::new_empty();
+        assert_eq!(restore_snapshot(&mut world, packed(true)), Ok(1));
+        assert_eq!(world.physics.bodies.len(), 1);
+    }
+
+    #[test]
+    fn rejects_invalid_headers() {
+        assert_eq!(
+            unpack_snapshot(b"not a snapshot".to_vec()).err(),
+            Some(SnapshotError::InvalidMagic)
+        );
+
+        let mut bytes = packed(false);
+        bytes[4..6].copy_from_slice(&(SNAPSHOT_FORMAT_VERSION + 1).to_le_bytes());
+        assert_eq!(
+            unpack_snapshot(bytes).err(),
+            Some(SnapshotError::UnsupportedVersion {
+                version: SNAPSHOT_FORMAT_VERSION + 1
+            })
+        );
+
+        let mut bytes = packed(false);
+        bytes[10..12].copy_from_slice(&99u16.to_le_bytes());
+        assert_eq!(
+            unpack_snapshot(bytes).err(),
+            Some(SnapshotError::RapierVersionMismatch {
+                version: [RAPIER_VERSION[0], RAPIER_VERSION[1], 99]
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_corrupted_payload() {
+        let mut bytes = packed(true);
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        assert_eq!(
+            unpack_snapshot(bytes).err(),
+            Some(SnapshotError::ChecksumMismatch)
+        );
+    }
+
+    #[test]
+    fn rapier_version_matches_manifest() {
+        let manifest = include_str!("../../Cargo.toml");
+        let rapier = &manifest[manifest.find("[dependencies.rapier3d]").unwrap()..];
+        let expected = format!("version = \"{}\"", format_version(&RAPIER_VERSION));
+        assert!(rapier.lines().nth(1).unwrap().starts_with(&expected));
+    }
+
+    /// Registers a collider like a RapierCollisionShape3D would, cheap colliders are added to snapshot_colliders
+    fn add_collider(
+        world: &mut World,
+        cuid: &str,
+        shape: SharedShape,
+        parent: RigidBodyHandle,
+        cheap: bool,
+    ) -> ColliderHandle {
+        let physics = &mut world.physics;
+        let handle = physics.colliders.insert_with_parent(
+            ColliderBuilder::new(shape),
+            parent,
+            &mut physics.bodies,
+        );
+        let raw = handle.into_raw_parts();
+        let lookup_table = &mut physics.lookup_table;
+        lookup_table.godot_to_rapier.insert(cuid.to_string(), raw);
+        lookup_table
+            .rapier_to_godot
+            .insert((HandleKind::Collider, raw), cuid.to_string());
+        if cheap {
+            lookup_table.insert_snapshot_collider(raw);
+        }
+        handle
+    }
+
+    fn remove_collider(world: &mut World, handle: ColliderHandle) {
+        let physics = &mut world.physics;
+        physics
+            .colliders
+            .remove(handle, &mut physics.islands, &mut physics.bodies, true);
+        let raw = handle.into_raw_parts();
+        physics
+            .lookup_table
+            .remove_by_handle(HandleKind::Collider, &raw);
+        physics.lookup_table.remove_snapshot_collider(&raw);
+    }
+
+    /// A static terrain with an expensive collider and a falling box
+    fn terrain_world() -> (World, RigidBodyHandle, ColliderHandle, ColliderHandle) {
+        let mut world = World::new_empty();
+        let terrain = world.physics.bodies.insert(RigidBodyBuilder::fixed());
+        let ground = add_collider(
+            &mut world,
+            "terrain",
+            SharedShape::cuboid(20.0, 0.5, 20.0),
+            terrain,
+            false,
+        );
+        let body = world
+            .physics
+            .bodies
+            .insert(RigidBodyBuilder::dynamic().translation(vector![0.0, 2.0, 0.0]));
+        let cube = add_collider(
+            &mut world,
+            "cube",
+            SharedShape::cuboid(0.5, 0.5, 0.5),
+            body,
+            true,
+        );
+        world.step();
+        (world, body, ground, cube)
+    }
+
+    fn restore(world: &mut World, snapshot: &[u8]) {
+        restore_snapshot(world, snapshot.to_vec()).unwrap();
+    }
+
+    #[test]
+    fn restore_keeps_collider_handles_and_parents() {
+        let (mut world, body, _, cube) = terrain_world();
+        let snapshot = pack_snapshot(&world).unwrap();
+
+        // Replace the cube's collider, the new one takes its slot with a new generation
+        remove_collider(&mut world, cube);
+        let sphere = add_collider(&mut world, "sphere", SharedShape::ball(0.5), body, true);
+        assert_ne!(sphere, cube);
+        world.step();
+
+        restore(&mut world, &snapshot);
+        let physics = &world.physics;
+        assert!(physics.colliders.get(sphere).is_none());
+        assert_eq!(physics.colliders[cube].parent(), Some(body));
+        assert!(physics.bodies[body].colliders().contains(&cube));
+        assert!(physics.colliders[cube].shape().as_cuboid().is_some());
+        assert_eq!(
+            physics.lookup_table.godot_to_rapier.get("cube"),
+            Some(&cube.into_raw_parts())
+        );
+        assert!(!physics.lookup_table.godot_to_rapier.contains_key("sphere"));
+    }
+
+    #[test]
+    fn restore_removes_colliders_added_after_snapshot() {
+        let (mut world, body, _, _) = terrain_world();
+        let snapshot = pack_snapshot(&world).unwrap();
+        let extra = add_collider(&mut world, "extra", SharedShape::ball(0.2), body, true);
+        world.step();
+
+        restore(&mut world, &snapshot);
+        assert!(world.physics.colliders.get(extra).is_none());
+        assert_eq!(world.physics.colliders.len(), 2);
+        assert!(!world.physics.bodies[body].colliders().contains(&extra));
+    }
+
+    #[test]
+    fn expensive_colliders_are_not_serialized() {
+        let (world, _, ground, _) = terrain_world();
+        let snapshot = unpack_snapshot(pack_snapshot(&world).unwrap()).unwrap();
+        assert!(snapshot.colliders[ground].shape().as_ball().is_some());
+    }
+
+    #[test]
+    fn restore_keeps_live_expensive_colliders() {
+        let (mut world, _, ground, _) = terrain_world();
+        let snapshot = pack_snapshot(&world).unwrap();
+        world.step();
+
+        restore(&mut world, &snapshot);
+        let collider = &world.physics.colliders[ground];
+        assert_eq!(
+            collider.shape().as_cuboid().map(|c| c.half_extents),
+            Some(vector![20.0, 0.5, 20.0])
+        );
+        assert!(world.physics.bodies[collider.parent().unwrap()]
+            .colliders()
+            .contains(&ground));
+    }
+
+    #[test]
+    fn restore_drops_removed_expensive_colliders() {
+        let (mut world, _, ground, _) = terrain_world();
+        let snapshot = pack_snapshot(&world).unwrap();
+        remove_collider(&mut world, ground);
+
+        restore(&mut world, &snapshot);
+        assert!(world.physics.colliders.get(ground).is_none());
+        assert!(!world
+            .physics
+            .lookup_table
+            .godot_to_rapier
+            .contains_key("terrain"));
+    }
+
+    #[test]
+    fn resimulating_after_restore_is_deterministic() {
+        let (mut world, body, _, cube) = terrain_world();
+        let snapshot = pack_snapshot(&world).unwrap();
+        for _ in 0..60 {
+            world.step();
+        }
+        let expected = world.get_state_hash(None);
 
-        world.physics.lookup_table = deserialized.lookup_table;
+        remove_collider(&mut world, cube);
+        add_collider(&mut world, "sphere", SharedShape::ball(0.5), body, true);
+        restore(&mut world, &snapshot);
+        for _ in 0..60 {
+            world.step();
+        }
+        assert_eq!(world.get_state_hash(None), expected);
     }
 }
@@ -0,0 +1,1136 @@
+/*
+    Headless tests of the world, built from descriptors and driven by commands instead of Godot nodes and actions
+*/
+
+use rapier3d::control::{CharacterAutostep, CharacterLength, KinematicCharacterController};
+use rapier3d::prelude::*;
+
+use super::character::{
+    align_character, floating_controller, floor_normal, move_character, rotate_character,
+    CharacterMove, CharacterRotation, PlatformLeave, PlatformState,
+};
+use super::command::Command;
+use super::descriptor::{BodyDescriptor, BodyKind, ColliderDescriptor};
+use super::events::{touching_pairs, EventCollider};
+use super::gravity::{GravityMode, GravityOverride};
+use super::lookup::HandleKind;
+use super::objects::BodyForce;
+use super::settings::{WorldParameter, WorldSettings};
+use super::state::{pack_snapshot, restore_snapshot};
+use super::world::CorrectionError;
+use crate::World;
+
+fn floor() -> Command {
+    Command::AddBody(
+        BodyDescriptor::new(
+            "floor",
+            BodyKind::Fixed,
+            Isometry::translation(0.0, -0.5, 0.0),
+        )
+        .with_collider(ColliderDescriptor::new(
+            "floor_shape",
+            SharedShape::cuboid(50.0, 0.5, 50.0),
+        )),
+    )
+}
+
+fn cube(cuid: &str, x: Real, y: Real) -> Command {
+    Command::AddBody(
+        BodyDescriptor::new(cuid, BodyKind::Dynamic, Isometry::translation(x, y, 0.0))
+            .with_collider(ColliderDescriptor::new(
+                &format!("{}_shape", cuid),
+                SharedShape::cuboid(0.5, 0.5, 0.5),
+            )),
+    )
+}
+
+fn force(cuid: &str, force: BodyForce) -> Command {
+    Command::Force {
+        cuid: cuid.to_string(),
+        force,
+    }
+}
+
+/// A floor with a stack of cubes falling on it
+fn stack() -> World {
+    let mut world = World::new_empty();
+    world.queue_command(floor());
+    for idx in 0..4 {
+        world.queue_command(cube(
+            &format!("cube{}", idx),
+            idx as Real * 0.2,
+            1.0 + idx as Real * 1.1,
+        ));
+    }
+    world
+}
+
+fn step(world: &mut World, count: usize) {
+    for _ in 0..count {
+        world.step();
+    }
+}
+
+fn handle(world: &World, cuid: &str) -> RigidBodyHandle {
+    let raw = world.physics.lookup_table.get_rapier_handle(cuid).unwrap();
+    RigidBodyHandle::from_raw_parts(raw.0, raw.1)
+}
+
+fn body<'a>(world: &'a World, cuid: &str) -> &'a RigidBody {
+    &world.physics.bodies[handle(world, cuid)]
+}
+
+#[test]
+fn commands_add_bodies_and_colliders() {
+    let mut world = stack();
+    step(&mut world, 1);
+
+    assert_eq!(world.get_counts(), (5, 5, 0, 0));
+    let cube = body(&world, "cube0");
+    assert_eq!(cube.colliders().len(), 1);
+    let collider = world
+        .physics
+        .lookup_table
+        .get_rapier_handle("cube0_shape")
+        .unwrap();
+    assert_eq!(cube.colliders()[0].into_raw_parts(), collider);
+
+    step(&mut world, 120);
+    let top = body(&world, "cube3").translation().y;
+    assert!(
+        top > 3.0 && top < 3.6,
+        "cubes should rest on each other, top at {}",
+        top
+    );
+}
+
+#[test]
+fn commands_apply_forces() {
+    let mut world = stack();
+    step(&mut world, 1);
+    world.queue_command(force(
+        "cube3",
+        BodyForce::SetLinearVelocity(vector![0.0, 10.0, 0.0]),
+    ));
+    world.queue_command(force(
+        "cube2",
+        BodyForce::ApplyImpulse(vector![500.0, 0.0, 0.0]),
+    )); // Cubes weigh 100
+    step(&mut world, 1);
+
+    assert!(body(&world, "cube3").linvel().y > 9.0);
+    assert!(body(&world, "cube2").linvel().x > 4.0);
+}
+
+#[test]
+fn commands_remove_objects() {
+    let mut world = stack();
+    step(&mut world, 1);
+    world.queue_command(Command::Remove {
+        cuid: "cube1_shape".to_string(),
+    });
+    world.queue_command(Command::Remove {
+        cuid: "cube1".to_string(),
+    });
+    step(&mut world, 1);
+
+    assert_eq!(world.get_counts(), (4, 4, 0, 0));
+    assert!(world
+        .physics
+        .lookup_table
+        .get_rapier_handle("cube1")
+        .is_none());
+    assert!(world
+        .physics
+        .lookup_table
+        .get_rapier_handle("cube1_shape")
+        .is_none());
+}
+
+#[test]
+fn removing_a_body_forgets_its_joints() {
+    let mut world = stack();
+    step(&mut world, 1);
+    let joint = FixedJointBuilder::new().local_anchor2(point![0.0, 1.1, 0.0]);
+    let (cube0, cube1) = (handle(&world, "cube0"), handle(&world, "cube1"));
+    let joint = world
+        .physics
+        .impulse_joints
+        .insert(cube0, cube1, joint, true);
+    world
+        .physics
+        .lookup_table
+        .insert("joint", HandleKind::ImpulseJoint, joint.into_raw_parts());
+
+    world.queue_command(Command::Remove {
+        cuid: "cube0".to_string(),
+    });
+    step(&mut world, 1);
+
+    assert_eq!(world.physics.impulse_joints.len(), 0);
+    assert!(world
+        .physics
+        .lookup_table
+        .get_rapier_handle("joint")
+        .is_none());
+    assert!(world
+        .physics
+        .lookup_table
+        .get_godot_uid(HandleKind::ImpulseJoint, &joint.into_raw_parts())
+        .is_none());
+}
+
+/// Deterministic Fisher-Yates shuffle, seeded so failures can be reproduced
+fn shuffle<T>(items: &mut [T], seed: u64) {
+    let mut state = seed
+        .wrapping_mul(6364136223846793005)
+        .wrapping_add(1442695040888963407);
+    for idx in (1..items.len()).rev() {
+        state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        items.swap(idx, (state >> 33) as usize % (idx + 1));
+    }
+}
+
+#[test]
+fn queue_order_does_not_matter() {
+    // What a tick of actions turns into: adds, removals, parameters and every force,
+    // on several bodies, including the same operation twice on one body and a force on a body added this tick
+    let queued = vec![
+        Command::SetParameter(WorldParameter::Gravity(vector![0.0, -5.0, 0.0])),
+        cube("late", 2.0, 3.0),
+        force(
+            "late",
+            BodyForce::SetAngularVelocity(vector![0.0, 1.0, 0.0]),
+        ),
+        force("cube0", BodyForce::ApplyImpulse(vector![1.0, 0.0, 0.0])),
+        force("cube0", BodyForce::ApplyImpulse(vector![0.0, 0.0, -0.5])),
+        force(
+            "cube0",
+            BodyForce::SetLinearVelocity(vector![0.0, 2.0, 0.0]),
+        ),
+        force(
+            "cube1",
+            BodyForce::ApplyTorqueImpulse(vector![0.0, 0.3, 0.0]),
+        ),
+        force(
+            "cube1",
+            BodyForce::ApplyImpulseAtPoint(vector![0.0, 0.0, 1.0], point![0.0, 2.0, 0.0]),
+        ),
+        force("cube2", BodyForce::AddForce(vector![0.0, 0.0, 3.0])),
+        force("cube2", BodyForce::ResetForces),
+        force("cube2", BodyForce::AddTorque(vector![1.0, 0.0, 0.0])),
+        Command::Remove {
+            cuid: "cube3".to_string(),
+        },
+    ];
+
+    let run = |commands: &[Command]| {
+        let mut world = stack();
+        step(&mut world, 1);
+        for command in commands {
+            world.queue_command(command.clone());
+        }
+        step(&mut world, 60);
+        pack_snapshot(&world).unwrap()
+    };
+
+    let expected = run(&queued);
+    let mut reversed = queued.clone();
+    reversed.reverse();
+    assert!(run(&reversed) == expected, "reversed");
+    for seed in 0..8 {
+        let mut shuffled = queued.clone();
+        shuffle(&mut shuffled, seed);
+        assert!(run(&shuffled) == expected, "seed {}", seed);
+    }
+}
+
+#[test]
+fn restore_and_resimulate() {
+    let mut world = stack();
+    step(&mut world, 30);
+    let snapshot = pack_snapshot(&world).unwrap();
+    step(&mut world, 60);
+    let expected = world.get_state_hash(None);
+
+    assert_eq!(restore_snapshot(&mut world, snapshot), Ok(30));
+    step(&mut world, 60);
+    assert_eq!(world.get_state_hash(None), expected);
+}
+
+#[test]
+fn rollback_replays_buffered_commands() {
+    let mut world = stack();
+    step(&mut world, 10);
+    world.queue_command(force(
+        "cube3",
+        BodyForce::ApplyImpulse(vector![0.0, 8.0, 0.0]),
+    ));
+    step(&mut world, 5);
+    world.queue_command(cube("late", 3.0, 2.0));
+    step(&mut world, 25);
+    let expected = world.get_state_hash(None);
+
+    // Correcting a tick with its own snapshot resimulates to the exact same state
+    let snapshot = world.get_snapshot(Some(5)).unwrap();
+    assert_eq!(world.apply_correction(snapshot), Ok(35));
+    assert_eq!(world.state.timestep_id, 40);
+    assert_eq!(world.get_state_hash(None), expected);
+    assert!(world
+        .physics
+        .lookup_table
+        .get_rapier_handle("late")
+        .is_some());
+}
+
+#[test]
+fn rollback_applies_correction() {
+    let mut world = stack();
+    step(&mut world, 20);
+    let uncorrected = world.get_state_hash(None);
+
+    // An authoritative peer pushed cube0 at tick 10
+    let mut server = stack();
+    step(&mut server, 10);
+    server.queue_command(force(
+        "cube0",
+        BodyForce::SetLinearVelocity(vector![3.0, 0.0, 0.0]),
+    ));
+    step(&mut server, 1);
+    let correction = server.get_snapshot(None).unwrap();
+    step(&mut server, 9);
+
+    assert_eq!(world.apply_correction(correction), Ok(9));
+    assert_ne!(world.get_state_hash(None), uncorrected);
+    assert_eq!(world.get_state_hash(None), server.get_state_hash(None));
+}
+
+#[test]
+fn correction_rejects_future_and_unbuffered_ticks() {
+    let mut world = stack();
+    world.buffer.set_max_len(10);
+    step(&mut world, 30);
+    let expected = world.get_state_hash(None);
+
+    let mut future = stack();
+    step(&mut future, 40);
+    let correction = future.get_snapshot(None).unwrap();
+    let error = world.apply_correction(correction).unwrap_err();
+    assert_eq!(
+        error,
+        CorrectionError::FutureTick {
+            tick: 40,
+            current: 30
+        }
+    );
+    assert_eq!(error.code(), "FUTURE_TICK");
+
+    let mut past = stack();
+    step(&mut past, 5);
+    let correction = past.get_snapshot(None).unwrap();
+    let error = world.apply_correction(correction).unwrap_err();
+    assert_eq!(
+        error,
+        CorrectionError::OutsideBuffer {
+            tick: 5,
+            current: 30
+        }
+    );
+    assert_eq!(error.code(), "OUTSIDE_BUFFER");
+
+    // Rejected corrections leave the world untouched
+    assert_eq!(world.state.timestep_id, 30);
+    assert_eq!(world.get_state_hash(None), expected);
+}
+
+#[test]
+fn corrected_resimulation_matches_straight_run() {
+    // The server pushes cube0 at tick 10, both peers add a cube at tick 15
+    let run = |world: &mut World, push: bool| {
+        step(world, 10);
+        if push {
+            world.queue_command(force(
+                "cube0",
+                BodyForce::SetLinearVelocity(vector![3.0, 0.0, 0.0]),
+            ));
+        }
+        step(world, 5);
+        world.queue_command(cube("late", 3.0, 2.0));
+        step(world, 15);
+    };
+    let mut server = stack();
+    run(&mut server, true);
+    let mut client = stack();
+    run(&mut client, false);
+    assert_ne!(client.get_state_hash(None), server.get_state_hash(None));
+
+    let correction = server.get_snapshot(Some(11)).unwrap();
+    assert_eq!(client.apply_correction(correction), Ok(19));
+    // Every resimulated tick is buffered again with the state of the straight run
+    for tick in 12..=30 {
+        let resimulated = client.get_state_hash(Some(tick));
+        assert!(resimulated.is_some(), "tick {}", tick);
+        assert_eq!(
+            resimulated,
+            server.get_state_hash(Some(tick)),
+            "tick {}",
+            tick
+        );
+    }
+    assert_eq!(client.get_state_hash(None), server.get_state_hash(None));
+    assert_eq!(
+        pack_snapshot(&client).unwrap(),
+        pack_snapshot(&server).unwrap()
+    );
+}
+
+/// Cuids of the colliders touching each other, sorted
+fn touching(world: &World) -> Vec<(String, String)> {
+    let mut pairs: Vec<_> = touching_pairs(&world.physics)
+        .into_iter()
+        .map(|(a, b)| match a.collider_uid < b.collider_uid {
+            true => (a.collider_uid, b.collider_uid),
+            false => (b.collider_uid, a.collider_uid),
+        })
+        .collect();
+    pairs.sort();
+    pairs
+}
+
+#[test]
+fn correction_updates_touching_pairs() {
+    // A floating ball that the server pushes into an area at tick 5
+    let run = |push: bool| {
+        let mut world = World::new_empty();
+        let mut area = ColliderDescriptor::new("area", SharedShape::ball(2.0))
+            .with_position(Isometry::translation(4.0, 0.0, 0.0));
+        area.sensor = true;
+        world.queue_command(Command::AddSensor(area));
+        let mut ball =
+            BodyDescriptor::new("ball", BodyKind::Dynamic, Isometry::identity()).with_collider(
+                ColliderDescriptor::new("ball_shape", SharedShape::ball(0.5)),
+            );
+        ball.gravity_scale = 0.0;
+        world.queue_command(Command::AddBody(ball));
+        step(&mut world, 5);
+        if push {
+            world.queue_command(force(
+                "ball",
+                BodyForce::SetLinearVelocity(vector![10.0, 0.0, 0.0]),
+            ));
+        }
+        step(&mut world, 25);
+        world
+    };
+    let inside = vec![("area".to_string(), "ball_shape".to_string())];
+
+    let mut server = run(true);
+    let mut client = run(false);
+    assert_eq!(touching(&server), inside);
+    assert!(touching(&client).is_empty());
+
+    // The correction moves the ball into the area
+    let correction = server.buffer.get_physics_state(6).unwrap();
+    assert_eq!(client.apply_correction(correction), Ok(24));
+    assert_eq!(touching(&client), inside);
+
+    // And back out of it
+    let correction = run(false).buffer.get_physics_state(6).unwrap();
+    assert_eq!(server.apply_correction(correction), Ok(24));
+    assert!(touching(&server).is_empty());
+}
+
+#[test]
+fn world_settings_apply_on_creation() {
+    let settings = WorldSettings {
+        buffer_length: 10,
+        parameters: vec![
+            WorldParameter::TicksPerSecond(120),
+            WorldParameter::CcdSubsteps(4),
+        ],
+    };
+    let mut world = World::new(&settings);
+    assert_eq!(world.physics.integration_parameters.dt, 1.0 / 120.0);
+    assert_eq!(world.physics.integration_parameters.max_ccd_substeps, 4);
+
+    step(&mut world, 30);
+    assert!((world.state.time - 0.25).abs() < 1e-4);
+    assert!(world.buffer.buffer.len() <= 10);
+}
+
+#[test]
+fn parameter_commands_are_replayed_when_resimulating() {
+    let mut world = stack();
+    step(&mut world, 10);
+    let gravity = vector![0.0, 5.0, 0.0];
+    world.queue_command(Command::SetParameter(WorldParameter::Gravity(gravity)));
+    step(&mut world, 1);
+    world.queue_command(Command::SetParameter(WorldParameter::SolverIterations(1)));
+    step(&mut world, 19);
+    let expected = world.get_state_hash(None);
+
+    // Snapshots carry the parameters, resimulating from before the change replays it at the same tick
+    let snapshot = world.get_snapshot(Some(5)).unwrap();
+    assert_eq!(world.apply_correction(snapshot.clone()), Ok(25));
+    assert_eq!(world.get_state_hash(None), expected);
+    assert_eq!(world.physics.gravity, gravity);
+
+    let delta = world.get_delta_snapshot(5, 20).unwrap();
+    assert_eq!(world.apply_delta_snapshot(5, &delta), Ok(20));
+    assert_eq!(world.physics.gravity, gravity);
+    assert_eq!(
+        world
+            .physics
+            .integration_parameters
+            .num_solver_iterations
+            .get(),
+        1
+    );
+
+    assert_eq!(restore_snapshot(&mut world, snapshot), Ok(5));
+    assert_eq!(world.physics.gravity, vector![0.0, -9.81, 0.0]);
+    assert_eq!(
+        world
+            .physics
+            .integration_parameters
+            .num_solver_iterations
+            .get(),
+        4
+    );
+}
+
+fn gravity_area(cuid: &str, position: Vector<Real>, gravity: GravityOverride) -> Command {
+    let mut collider = ColliderDescriptor::new(cuid, SharedShape::ball(5.0))
+        .with_position(Isometry::translation(position.x, position.y, position.z));
+    collider.sensor = true;
+    collider.gravity = Some(gravity);
+    Command::AddSensor(collider)
+}
+
+fn directional(
+    area: &str,
+    mode: GravityMode,
+    direction: Vector<Real>,
+    priority: i32,
+) -> GravityOverride {
+    GravityOverride {
+        area: area.to_string(),
+        mode,
+        priority,
+        point: false,
+        center: Point::origin(),
+        direction,
+        strength: 9.81,
+    }
+}
+
+#[test]
+fn gravity_scale_applies_to_body() {
+    let mut world = World::new_empty();
+    let mut floating = BodyDescriptor::new(
+        "floating",
+        BodyKind::Dynamic,
+        Isometry::translation(0.0, 10.0, 0.0),
+    )
+    .with_collider(ColliderDescriptor::new(
+        "floating_shape",
+        SharedShape::ball(0.5),
+    ));
+    floating.gravity_scale = 0.0;
+    world.queue_command(Command::AddBody(floating));
+    world.queue_command(cube("falling", 3.0, 10.0));
+    step(&mut world, 30);
+
+    assert_eq!(body(&world, "floating").translation().y, 10.0);
+    assert!(body(&world, "falling").translation().y < 9.0);
+}
+
+#[test]
+fn area_gravity_replaces_or_adds_to_world_gravity() {
+    let mut world = World::new_empty();
+    world.queue_command(gravity_area(
+        "up",
+        vector![0.0, 10.0, 0.0],
+        directional("up", GravityMode::Replace, Vector::y(), 0),
+    ));
+    world.queue_command(gravity_area(
+        "down",
+        vector![20.0, 10.0, 0.0],
+        directional("down", GravityMode::Add, -Vector::y(), 0),
+    ));
+    world.queue_command(cube("in_up", 0.0, 10.0));
+    world.queue_command(cube("in_down", 20.0, 10.0));
+    world.queue_command(cube("outside", 40.0, 10.0));
+    step(&mut world, 30);
+
+    let fallen = |cuid: &str| 10.0 - body(&world, cuid).translation().y;
+    assert!(
+        fallen("in_up") < 0.0,
+        "replaced gravity should lift the cube"
+    );
+    let (doubled, normal) = (fallen("in_down"), fallen("outside"));
+    assert!(
+        doubled > normal * 1.8 && doubled < normal * 2.1,
+        "added gravity should double the fall, {} vs {}",
+        doubled,
+        normal
+    );
+}
+
+#[test]
+fn area_point_gravity_and_priority() {
+    let mut world = World::new_empty();
+    let mut point = directional("point", GravityMode::Replace, Vector::zeros(), 1);
+    point.point = true;
+    point.center = point![0.0, 10.0, 0.0];
+    // The collider is off the area's origin, the cube is between both and pulled towards the origin
+    world.queue_command(gravity_area("point", vector![4.0, 10.0, 0.0], point));
+    // Lower priority, ignored because the point area replaces gravity
+    world.queue_command(gravity_area(
+        "side",
+        vector![0.0, 10.0, 0.0],
+        directional("side", GravityMode::Add, Vector::z(), 0),
+    ));
+    world.queue_command(cube("orbiter", 3.0, 10.0));
+    step(&mut world, 20);
+
+    let position = body(&world, "orbiter").translation();
+    assert!(
+        position.x < 3.0,
+        "cube should be pulled towards the area origin"
+    );
+    // World gravity applies during the first step only, before the area detected the cube
+    assert!(
+        (position.y - 10.0).abs() < 0.1 && position.z.abs() < 0.01,
+        "cube should only move towards the center, at {:?}",
+        position
+    );
+}
+
+#[test]
+fn area_gravity_is_snapshotted() {
+    let mut world = World::new_empty();
+    world.queue_command(gravity_area(
+        "up",
+        vector![0.0, 10.0, 0.0],
+        directional("up", GravityMode::Replace, Vector::y(), 0),
+    ));
+    world.queue_command(cube("cube", 0.0, 10.0));
+    step(&mut world, 10);
+    let snapshot = pack_snapshot(&world).unwrap();
+    step(&mut world, 20);
+    let expected = world.get_state_hash(None);
+
+    world.queue_command(Command::Remove {
+        cuid: "up".to_string(),
+    });
+    step(&mut world, 1);
+    assert!(world.physics.gravity_overrides.is_empty());
+
+    assert_eq!(restore_snapshot(&mut world, snapshot), Ok(10));
+    assert_eq!(world.physics.gravity_overrides.len(), 1);
+    step(&mut world, 20);
+    assert_eq!(world.get_state_hash(None), expected);
+}
+
+/// A box character standing on the floor, with a 0.2 high step ahead of it
+fn character_and_step() -> World {
+    let mut world = World::new_empty();
+    world.queue_command(floor());
+    world.queue_command(Command::AddBody(
+        BodyDescriptor::new(
+            "step",
+            BodyKind::Fixed,
+            Isometry::translation(2.0, 0.1, 0.0),
+        )
+        .with_collider(ColliderDescriptor::new(
+            "step_shape",
+            SharedShape::cuboid(1.0, 0.1, 1.0),
+        )),
+    ));
+    world.queue_command(Command::AddBody(
+        BodyDescriptor::new(
+            "character",
+            BodyKind::KinematicPositionBased,
+            Isometry::translation(0.0, 0.85, 0.0),
+        )
+        .with_collider(ColliderDescriptor::new(
+            "character_shape",
+            SharedShape::cuboid(0.3, 0.8, 0.3),
+        )),
+    ));
+    step(&mut world, 1);
+    world
+}
+
+/// A character's controller and settings
+struct Character {
+    controller: KinematicCharacterController,
+    floating: bool,
+    on_leave: PlatformLeave,
+}
+
+impl Character {
+    fn new(controller: KinematicCharacterController) -> Self {
+        Self {
+            controller,
+            floating: false,
+            on_leave: PlatformLeave::AddVelocity,
+        }
+    }
+
+    fn floating() -> Self {
+        Self {
+            floating: true,
+            ..Self::new(floating_controller(KinematicCharacterController::default()))
+        }
+    }
+
+    /// Moves the character once and steps the world
+    fn move_and_step(&mut self, world: &mut World, movement: Vector<Real>) -> CharacterMove {
+        let result = move_character(
+            &self.controller,
+            self.floating,
+            self.on_leave,
+            handle(world, "character"),
+            movement,
+            &mut world.physics,
+        );
+        world.step();
+        result.unwrap()
+    }
+}
+
+/// Moves the character forward for the given amount of steps, returns whether it stepped up at any point
+fn walk(world: &mut World, character: &mut Character, steps: usize) -> bool {
+    let mut stepped_up = false;
+    for _ in 0..steps {
+        let movement = vector![0.05, -0.05, 0.0];
+        stepped_up |= character.move_and_step(world, movement).stepped_up;
+    }
+    stepped_up
+}
+
+#[test]
+fn character_autostep() {
+    let mut world = character_and_step();
+    let mut blocked = Character::new(KinematicCharacterController::default());
+    assert!(!walk(&mut world, &mut blocked, 40));
+    let position = body(&world, "character").translation();
+    assert!(
+        position.x < 1.0 && position.y < 0.9,
+        "character without autostep should stop at the step, at {:?}",
+        position
+    );
+
+    let mut climbing = Character::new(KinematicCharacterController {
+        autostep: Some(CharacterAutostep {
+            max_height: CharacterLength::Absolute(0.3),
+            min_width: CharacterLength::Absolute(0.2),
+            include_dynamic_bodies: false,
+        }),
+        ..KinematicCharacterController::default()
+    });
+    assert!(walk(&mut world, &mut climbing, 20));
+    let position = body(&world, "character").translation();
+    assert!(
+        position.x > 1.2 && position.y > 1.0,
+        "character should stand on the step, at {:?}",
+        position
+    );
+}
+
+#[test]
+fn floating_character_ignores_the_floor() {
+    let mut world = character_and_step();
+    let mut character = Character::floating();
+    for _ in 0..10 {
+        let result = character.move_and_step(&mut world, vector![0.05, 0.0, 0.0]);
+        assert!(!result.movement.grounded);
+    }
+    assert_eq!(
+        body(&world, "character").translation().y,
+        0.85,
+        "floating character should not snap to the ground"
+    );
+
+    // Flying into the step slides along its side instead of climbing it
+    for _ in 0..10 {
+        character.move_and_step(&mut world, vector![0.05, -0.05, 0.0]);
+    }
+    let position = body(&world, "character").translation();
+    assert!(
+        position.x < 0.7 && position.y < 0.85,
+        "character should be blocked by the step, at {:?}",
+        position
+    );
+
+    // A grounded character at the same height is on the floor
+    let mut world = character_and_step();
+    let mut character = Character::new(KinematicCharacterController::default());
+    let result = character.move_and_step(&mut world, vector![0.05, 0.0, 0.0]);
+    assert!(result.movement.grounded);
+}
+
+/// A character standing on a platform, floating high above the floor
+fn character_on_platform(kind: BodyKind) -> World {
+    let mut world = World::new_empty();
+    let mut platform = BodyDescriptor::new("platform", kind, Isometry::translation(0.0, 2.0, 0.0));
+    platform.gravity_scale = 0.0;
+    world.queue_command(Command::AddBody(platform.with_collider(
+        ColliderDescriptor::new("platform_shape", SharedShape::cuboid(2.0, 0.1, 2.0)),
+    )));
+    world.queue_command(Command::AddBody(
+        BodyDescriptor::new(
+            "character",
+            BodyKind::KinematicPositionBased,
+            Isometry::translation(0.0, 2.92, 0.0),
+        )
+        .with_collider(ColliderDescriptor::new(
+            "character_shape",
+            SharedShape::cuboid(0.3, 0.8, 0.3),
+        )),
+    ));
+    step(&mut world, 1);
+
+    let raw = world
+        .physics
+        .lookup_table
+        .get_rapier_handle("platform")
+        .unwrap();
+    let platform = &mut world.physics.bodies[RigidBodyHandle::from_raw_parts(raw.0, raw.1)];
+    platform.set_additional_mass(1.0e6, true); // Barely pushed by the character when dynamic
+    world
+}
+
+/// Moves the platform at the given velocity, then moves the character and steps the world
+fn ride(
+    world: &mut World,
+    character: &mut Character,
+    platform_velocity: Vector<Real>,
+    movement: Vector<Real>,
+) -> CharacterMove {
+    let dt = world.physics.integration_parameters.dt;
+    let platform_handle = handle(world, "platform");
+    let platform = &mut world.physics.bodies[platform_handle];
+    match platform.is_kinematic() {
+        true => {
+            let next = platform.translation() + platform_velocity * dt;
+            platform.set_next_kinematic_translation(next);
+        }
+        false => {
+            platform.set_linvel(platform_velocity, true);
+            platform.set_angvel(Vector::zeros(), true);
+        }
+    }
+    character.move_and_step(world, movement)
+}
+
+/// The platform state the world keeps for the character
+fn platform_of(world: &World) -> PlatformState {
+    let raw = handle(world, "character").into_raw_parts();
+    world.physics.character_platforms[&raw].clone()
+}
+
+#[test]
+fn character_is_carried_by_platform() {
+    let sideways = vector![1.2, 0.0, 0.0];
+    for kind in [BodyKind::Dynamic, BodyKind::KinematicPositionBased] {
+        let mut world = character_on_platform(kind);
+        let mut character = Character::new(KinematicCharacterController::default());
+        for _ in 0..30 {
+            ride(
+                &mut world,
+                &mut character,
+                sideways,
+                vector![0.0, -0.16, 0.0],
+            );
+        }
+
+        let platform = platform_of(&world);
+        assert!(platform.body.is_some());
+        assert!(
+            (platform.velocity.x - 1.2).abs() < 0.01,
+            "platform velocity should be known, got {:?}",
+            platform.velocity
+        );
+        // The velocity of a kinematic platform is only known once it moved, so the character can be a step or two behind
+        let offset =
+            body(&world, "platform").translation().x - body(&world, "character").translation().x;
+        assert!(
+            offset.abs() < 0.06,
+            "character should move with the platform, {} behind",
+            offset
+        );
+    }
+}
+
+#[test]
+fn platform_on_leave() {
+    // Descending, so that AddUpwardVelocity drops part of the platform's velocity
+    let descending = vector![1.2, -0.6, 0.0];
+    let airborne_motion = |on_leave: PlatformLeave| {
+        let mut world = character_on_platform(BodyKind::KinematicPositionBased);
+        let mut character = Character::new(KinematicCharacterController::default());
+        character.on_leave = on_leave;
+        for _ in 0..10 {
+            ride(
+                &mut world,
+                &mut character,
+                descending,
+                vector![0.0, -0.16, 0.0],
+            );
+        }
+
+        // Jump off, then hover
+        let jump = ride(
+            &mut world,
+            &mut character,
+            descending,
+            vector![0.0, 0.3, 0.0],
+        );
+        assert!(!jump.movement.grounded && platform_of(&world).body.is_none());
+        let start = *body(&world, "character").translation();
+        for _ in 0..10 {
+            ride(&mut world, &mut character, descending, Vector::zeros());
+        }
+        body(&world, "character").translation() - start
+    };
+
+    let kept = airborne_motion(PlatformLeave::AddVelocity);
+    assert!(
+        (kept - vector![0.2, -0.1, 0.0]).norm() < 0.01,
+        "character should keep the platform's velocity, moved {:?}",
+        kept
+    );
+    let upward = airborne_motion(PlatformLeave::AddUpwardVelocity);
+    assert!(
+        (upward - vector![kept.x, 0.0, 0.0]).norm() < 1.0e-4,
+        "character should only keep the horizontal velocity, moved {:?}",
+        upward
+    );
+    assert_eq!(airborne_motion(PlatformLeave::DoNothing), Vector::zeros());
+}
+
+#[test]
+fn platform_state_is_snapshotted() {
+    let sideways = vector![1.2, 0.0, 0.0];
+    let mut world = character_on_platform(BodyKind::KinematicPositionBased);
+    let mut character = Character::new(KinematicCharacterController::default());
+    for _ in 0..10 {
+        ride(
+            &mut world,
+            &mut character,
+            sideways,
+            vector![0.0, -0.16, 0.0],
+        );
+    }
+    let snapshot = pack_snapshot(&world).unwrap();
+
+    // Jump off the platform, then hover with the velocity it kept
+    let mut replay = |world: &mut World| {
+        ride(world, &mut character, sideways, vector![0.0, 0.3, 0.0]);
+        for _ in 0..10 {
+            ride(world, &mut character, sideways, Vector::zeros());
+        }
+        (*body(world, "character").translation(), platform_of(world))
+    };
+    let expected = replay(&mut world);
+    assert!(expected.1.inherited_velocity.x > 1.0);
+
+    restore_snapshot(&mut world, snapshot).unwrap();
+    assert!(platform_of(&world).body.is_some());
+    assert_eq!(replay(&mut world), expected);
+}
+
+#[test]
+fn character_rotation() {
+    let mut world = character_and_step();
+    let character_handle = handle(&world, "character");
+    let mut character = Character::new(KinematicCharacterController::default());
+    let eighth_turn = Rotation::from_axis_angle(&Vector::y_axis(), std::f32::consts::FRAC_PI_4);
+
+    // Rotations requested during a step add up, and are applied along with the movement
+    for _ in 0..2 {
+        let rotation = CharacterRotation::RotateBy(eighth_turn);
+        rotate_character(character_handle, rotation, &mut world.physics);
+    }
+    character.move_and_step(&mut world, vector![0.05, 0.0, 0.0]);
+    let character_body = body(&world, "character");
+    assert!((character_body.translation().x - 0.05).abs() < 1.0e-4);
+    let expected = eighth_turn * eighth_turn;
+    assert!(
+        character_body.rotation().angle_to(&expected) < 1.0e-4,
+        "character should have turned a quarter, got {:?}",
+        character_body.rotation()
+    );
+
+    // Facing ignores the vertical part of the direction and keeps the character upright
+    let facing = CharacterRotation::Face(vector![1.0, 0.5, 1.0]);
+    rotate_character(character_handle, facing, &mut world.physics);
+    step(&mut world, 1);
+    let rotation = body(&world, "character").rotation();
+    let forward = rotation * -Vector::z();
+    assert!((forward - vector![1.0, 0.0, 1.0].normalize()).norm() < 1.0e-4);
+    assert!((rotation * Vector::y() - Vector::y()).norm() < 1.0e-4);
+}
+
+#[test]
+fn character_aligns_to_floor() {
+    let mut world = World::new_empty();
+    let tilt = Rotation::from_axis_angle(&Vector::z_axis(), 0.3);
+    world.queue_command(Command::AddBody(
+        BodyDescriptor::new(
+            "slope",
+            BodyKind::Fixed,
+            Isometry::from_parts(Translation::identity(), tilt),
+        )
+        .with_collider(ColliderDescriptor::new(
+            "slope_shape",
+            SharedShape::cuboid(5.0, 0.1, 5.0),
+        )),
+    ));
+    world.queue_command(Command::AddBody(
+        BodyDescriptor::new(
+            "character",
+            BodyKind::KinematicPositionBased,
+            Isometry::translation(0.0, 1.5, 0.0),
+        )
+        .with_collider(ColliderDescriptor::new(
+            "character_shape",
+            SharedShape::cuboid(0.3, 0.8, 0.3),
+        )),
+    ));
+    step(&mut world, 1);
+
+    let character_handle = handle(&world, "character");
+    let character = Character::new(KinematicCharacterController::default());
+    let move_aligned = |world: &mut World, movement: Vector<Real>| {
+        let result = move_character(
+            &character.controller,
+            false,
+            character.on_leave,
+            character_handle,
+            movement,
+            &mut world.physics,
+        )
+        .unwrap();
+        align_character(
+            &character.controller,
+            &result,
+            character_handle,
+            &mut world.physics,
+        );
+        world.step();
+        result
+    };
+
+    for _ in 0..10 {
+        move_aligned(&mut world, vector![0.0, -0.2, 0.0]);
+    }
+    let up = body(&world, "character").rotation() * Vector::y();
+    let normal = tilt * Vector::y();
+    assert!(
+        up.angle(&normal) < 0.01,
+        "character should be aligned to the slope, up is {:?}",
+        up
+    );
+
+    // Airborne, the character straightens up again
+    let jump = move_aligned(&mut world, vector![0.0, 1.0, 0.0]);
+    assert!(!jump.movement.grounded);
+    let up = body(&world, "character").rotation() * Vector::y();
+    assert!(up.angle(&Vector::y()) < 1.0e-4);
+}
+
+#[test]
+fn snapshots_of_the_same_state_are_identical() {
+    let mut world = stack();
+    step(&mut world, 10);
+    let snapshot = pack_snapshot(&world).unwrap();
+
+    let mut expected = None;
+    for _ in 0..5 {
+        restore_snapshot(&mut world, snapshot.clone()).unwrap();
+        step(&mut world, 1);
+        let packed = pack_snapshot(&world).unwrap();
+        assert_eq!(expected.get_or_insert_with(|| packed.clone()), &packed);
+    }
+}
+
+#[test]
+fn character_floor_on_slope() {
+    // A 20 degree slope, and a capsule character above it
+    let angle: Real = 20.0_f32.to_radians();
+    let mut world = World::new_empty();
+    world.queue_command(Command::AddBody(
+        BodyDescriptor::new(
+            "slope",
+            BodyKind::Fixed,
+            Isometry::new(vector![0.0, -0.5, 0.0], vector![0.0, 0.0, angle]),
+        )
+        .with_collider(ColliderDescriptor::new(
+            "slope_shape",
+            SharedShape::cuboid(5.0, 0.5, 5.0),
+        )),
+    ));
+    world.queue_command(Command::AddBody(
+        BodyDescriptor::new(
+            "character",
+            BodyKind::KinematicPositionBased,
+            Isometry::translation(0.0, 1.5, 0.0),
+        )
+        .with_collider(ColliderDescriptor::new(
+            "character_shape",
+            SharedShape::capsule_y(0.5, 0.3),
+        )),
+    ));
+    step(&mut world, 1);
+
+    let mut character = Character::new(KinematicCharacterController::default());
+    let desired = vector![0.0, -0.1, 0.0];
+    let mut result = character.move_and_step(&mut world, desired);
+    for _ in 0..20 {
+        result = character.move_and_step(&mut world, desired);
+    }
+    assert!(result.movement.grounded);
+
+    let slope_normal = vector![-angle.sin(), angle.cos(), 0.0];
+    let normal = floor_normal(&character.controller, &result.collisions);
+    assert!(
+        (normal - slope_normal).norm() < 1.0e-2,
+        "floor normal should be the slope normal, got {:?}",
+        normal
+    );
+    let floor_angle = crate::utils::vector_to_godot(normal).angle_to(godot::builtin::Vector3::UP);
+    assert!(
+        (floor_angle - angle).abs() < 1.0e-2,
+        "floor angle {}",
+        floor_angle
+    );
+
+    // The slide collision reports the slope, where and when it was hit
+    assert!(!result.collisions.is_empty());
+    let hit = &result.collisions[0];
+    let collider = EventCollider::from_handle(hit.handle, &world.physics).unwrap();
+    assert_eq!(collider.collider_uid, "slope_shape");
+    assert_eq!(collider.body_uid.as_deref(), Some("slope"));
+    assert!((hit.hit.normal1.into_inner() - slope_normal).norm() < 1.0e-2);
+    let surface = point![0.0, -0.5, 0.0] + slope_normal * 0.5;
+    let surface_distance = (hit.hit.witness1 - surface).dot(&slope_normal);
+    assert!(
+        surface_distance.abs() < 1.0e-2,
+        "contact point should be on the slope surface, {} away",
+        surface_distance
+    );
+    assert!(hit.hit.time_of_impact >= 0.0);
+    assert!(
+        (hit.translation_applied + hit.translation_remaining - desired).norm() < 1.0e-3,
+        "applied and remaining translation should add up to the movement"
+    );
+
+    // Standing still, the floor is not hit and the normal falls back to the up direction
+    assert_eq!(floor_normal(&character.controller, &[]), Vector::y());
+}
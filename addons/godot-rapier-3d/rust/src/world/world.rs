@@ -1,6 +1,16 @@
 use super::buffer::WorldBuffer;
-use super::state::{pack_snapshot, PhysicsState};
+use super::command::Command;
+use super::delta::{apply_delta, create_delta, DeltaError};
+use super::events::{EventCollector, PhysicsEvent};
+use super::gravity::apply_gravity_overrides;
+use super::hash::hash_physics_state;
+use super::settings::WorldSettings;
+use super::state::{
+    apply_snapshot, pack_snapshot, unpack_snapshot, DeserializedPhysicsSnapshot, PhysicsState,
+    SnapshotError,
+};
 use rapier3d::dynamics::IntegrationParameters;
+use std::fmt;
 
 pub struct RunState {
     pub timestep_id: usize,
@@ -22,24 +32,75 @@ impl RunState {
     }
 }
 
+/// Reasons an authoritative correction can be rejected
+#[derive(Debug, Clone, PartialEq)]
+pub enum CorrectionError {
+    InvalidSnapshot(SnapshotError),
+    FutureTick { tick: usize, current: usize },
+    OutsideBuffer { tick: usize, current: usize },
+}
+
+impl CorrectionError {
+    /// Stable identifier exposed to Godot
+    pub fn code(&self) -> &'static str {
+        match self {
+            CorrectionError::InvalidSnapshot(e) => e.code(),
+            CorrectionError::FutureTick { .. } => "FUTURE_TICK",
+            CorrectionError::OutsideBuffer { .. } => "OUTSIDE_BUFFER",
+        }
+    }
+}
+
+impl fmt::Display for CorrectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CorrectionError::InvalidSnapshot(e) => e.fmt(f),
+            CorrectionError::FutureTick { tick, current } => write!(
+                f,
+                "Cannot correct future tick {} (current tick is {})",
+                tick, current
+            ),
+            CorrectionError::OutsideBuffer { tick, current } => write!(
+                f,
+                "Tick {} is no longer in the buffer (current tick is {})",
+                tick, current
+            ),
+        }
+    }
+}
+
 pub struct World {
     pub physics: PhysicsState,
     pub buffer: WorldBuffer,
     callbacks: Callbacks,
     pub state: RunState,
+    event_collector: EventCollector,
+    pending_events: Vec<PhysicsEvent>,
+    /// Whether snapshots (including the buffered ones) are LZ4 compressed
+    pub compress_snapshots: bool,
 }
 
 type Callbacks = Vec<Box<dyn FnMut(&mut PhysicsState, &RunState)>>; // Callbacks are called after stepping every step
 
 impl World {
     pub fn new_empty() -> Self {
-        let physics = PhysicsState::new();
+        Self::new(&WorldSettings::default())
+    }
+
+    pub fn new(settings: &WorldSettings) -> Self {
+        let mut physics = PhysicsState::new();
+        for parameter in &settings.parameters {
+            parameter.apply(&mut physics);
+        }
         let state = RunState::new();
         Self {
             physics,
-            buffer: WorldBuffer::default(),
+            buffer: WorldBuffer::new(settings.buffer_length),
             callbacks: Vec::new(),
             state,
+            event_collector: EventCollector::new(),
+            pending_events: Vec::new(),
+            compress_snapshots: false,
         }
     }
 
@@ -59,9 +120,15 @@ impl World {
         self.callbacks.push(Box::new(callback));
     }
 
+    /// Queues a command for the current timestep, it is executed at the start of the next step
+    pub fn queue_command(&mut self, command: Command) {
+        self.buffer.insert_command(command, self.state.timestep_id);
+    }
+
     pub fn step(&mut self) {
         self.buffer
             .execute_actions(self.state.timestep_id, &mut self.physics);
+        apply_gravity_overrides(&mut self.physics);
 
         self.physics.pipeline.step(
             &self.physics.gravity,
@@ -76,9 +143,12 @@ impl World {
             &mut self.physics.ccd_solver,
             Some(&mut self.physics.query_pipeline),
             &*self.physics.hooks,
-            &(),
+            &self.event_collector.handler,
         );
 
+        let events = self.event_collector.collect(&self.physics);
+        self.pending_events.extend(events);
+
         for f in &mut self.callbacks {
             f(&mut self.physics, &self.state);
         }
@@ -86,8 +156,99 @@ impl World {
         self.state.time += self.physics.integration_parameters.dt as f32;
         self.state.timestep_id += 1;
 
-        self.buffer
-            .on_world_stepped(self.state.timestep_id, self.get_current_snapshot());
+        let state_hash = hash_physics_state(&self.physics);
+        self.buffer.on_world_stepped(
+            self.state.timestep_id,
+            self.get_current_snapshot(),
+            state_hash,
+        );
+    }
+
+    /// Overwrites a past state of the world with the given snapshot and then resimulates
+    /// every buffered tick up to the current one, rewriting the buffered physics states.
+    /// Returns the number of resimulated steps
+    pub fn apply_correction(&mut self, snapshot: Vec<u8>) -> Result<usize, CorrectionError> {
+        let deserialized =
+            unpack_snapshot(snapshot.clone()).map_err(CorrectionError::InvalidSnapshot)?;
+        let tick = deserialized.timestep_id;
+        let current = self.state.timestep_id;
+
+        if tick > current {
+            return Err(CorrectionError::FutureTick { tick, current });
+        }
+        let steps_to_resim = current - tick;
+        if steps_to_resim > self.buffer.max_len
+            || (steps_to_resim > 0 && self.buffer.get_step(tick).is_none())
+        {
+            return Err(CorrectionError::OutsideBuffer { tick, current });
+        }
+
+        let time = self.state.time;
+        apply_snapshot(self, deserialized);
+        self.buffer.mark_stale_after(tick);
+        let state_hash = hash_physics_state(&self.physics);
+        self.buffer.set_physics_state(tick, snapshot, state_hash);
+
+        for _ in 0..steps_to_resim {
+            self.step();
+        }
+        self.state.time = time;
+
+        Ok(steps_to_resim)
+    }
+
+    /// Encodes the changes between two buffered ticks. The base tick is the baseline the receiver already has
+    pub fn get_delta_snapshot(&self, base_tick: usize, tick: usize) -> Result<Vec<u8>, DeltaError> {
+        let base = self.get_buffered_snapshot(base_tick)?;
+        let target = self.get_buffered_snapshot(tick)?;
+        create_delta(&base, &target).map_err(|e| {
+            log::error!("Failed to create delta snapshot: {:?}", e);
+            DeltaError::InvalidDelta
+        })
+    }
+
+    /// Rebuilds a snapshot from the buffered baseline and the given delta, and overwrites the current state with it.
+    /// Like a correction, the buffered states after the restored tick are marked stale.
+    /// Returns the tick of the restored snapshot
+    pub fn apply_delta_snapshot(
+        &mut self,
+        base_tick: usize,
+        delta: &[u8],
+    ) -> Result<usize, DeltaError> {
+        let base = self.get_buffered_snapshot(base_tick)?;
+        let restored = apply_delta(base, delta)?;
+        let tick = restored.timestep_id;
+        apply_snapshot(self, restored);
+        self.buffer.mark_stale_after(tick);
+        if let Some(snapshot) = self.get_current_snapshot() {
+            let state_hash = hash_physics_state(&self.physics);
+            self.buffer.set_physics_state(tick, snapshot, state_hash);
+        }
+        Ok(tick)
+    }
+
+    fn get_buffered_snapshot(
+        &self,
+        tick: usize,
+    ) -> Result<DeserializedPhysicsSnapshot, DeltaError> {
+        let bytes = self
+            .buffer
+            .get_physics_state(tick)
+            .ok_or(DeltaError::MissingTick { tick })?;
+        unpack_snapshot(bytes).map_err(|_| DeltaError::InvalidDelta)
+    }
+
+    /// Returns and clears all events collected since the last call
+    pub fn drain_events(&mut self) -> Vec<PhysicsEvent> {
+        std::mem::take(&mut self.pending_events)
+    }
+
+    /// Retrieve either the current or a buffered state checksum
+    pub fn get_state_hash(&self, timestep_id: Option<usize>) -> Option<u64> {
+        match timestep_id {
+            None => Some(hash_physics_state(&self.physics)),
+            Some(timestep_id) => self.buffer.get_state_hash(timestep_id),
+        }
     }
 
     /// Retrieve either the current or a buffered snapshot
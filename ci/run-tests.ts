@@ -26,6 +26,22 @@ async function main() {
   console.log("Running:", args.join(" ") + "\n");
   const proc = Bun.spawn(args, { stdout: "inherit" });
   await proc.exited;
+
+  // Exits with a non-zero code if shuffled actions produced different snapshots.
+  // Set SORTING_SEED to replay the shuffles of a failed run
+  const seed = process.env.SORTING_SEED ?? String(Date.now());
+  console.log(`Action sorting seed: ${seed} (SORTING_SEED=${seed} to reproduce)`);
+  const sortingArgs = [
+    binPath,
+    "--headless",
+    "--no-window",
+    "++",
+    "--test=action_sorting",
+    `--seed=${seed}`,
+  ];
+  console.log("Running:", sortingArgs.join(" ") + "\n");
+  const sortingProc = Bun.spawn(sortingArgs, { stdout: "inherit" });
+  if ((await sortingProc.exited) !== 0) process.exit(1);
 }
 
 async function extractMacOSBinary({ buildDir, tests }: any): Promise<string> {
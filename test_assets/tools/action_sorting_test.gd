@@ -0,0 +1,72 @@
+extends Node3D
+
+## Replays the same timestep several times from the same snapshot, queueing the same
+## actions in a different (shuffled) order on each run, and checks that every run
+## ends in an identical snapshot
+
+@export var runs = 5
+@export var total_steps = 120
+@export var quit_on_complete = true
+
+var cmd = preload("res://test_assets/tools/cmd.gd")
+
+func _ready():
+	GR3DRuntime.pause() # This test steps the world itself
+	await get_tree().physics_frame # Wait for the deferred AddNode actions
+	GR3D.step(1)
+
+	var seed_arg = cmd.get_cmdline_args().get("seed")
+	var rng = RandomNumberGenerator.new()
+	rng.seed = int(seed_arg) if seed_arg else 0
+
+	var tick = GR3D.get_tick()
+	var start = GR3D.save_snapshot()
+	var actions = get_actions()
+	var expected = PackedByteArray()
+	var failures = 0
+
+	print("Shuffling with seed ", rng.seed)
+	print("Replaying tick ", tick, " ", runs, " times with ", actions.size(), " shuffled actions")
+	for run in runs:
+		GR3D.restore_snapshot(start)
+		GR3D._clear_actions(tick)
+		for action in shuffle(actions, rng):
+			GR3D._ingest_action(action.node, action.operation, action.data)
+		GR3D.step(total_steps)
+
+		var result = GR3D.save_snapshot()
+		if run == 0: expected = result
+		elif result != expected:
+			push_error("Run ", run, " produced a different snapshot than run 0: ", GR3D.diff_snapshots(expected, result))
+			failures += 1
+
+	print("Action sorting test ", "passed" if failures == 0 else "failed")
+	if quit_on_complete:
+		get_tree().root.propagate_notification(NOTIFICATION_WM_CLOSE_REQUEST)
+		get_tree().quit(1 if failures > 0 else 0)
+
+## Several order dependent actions for every rigid body in the scene
+func get_actions() -> Array:
+	var actions = []
+	var bodies = find_children("*", "RapierRigidBody3D", true, false)
+	for idx in bodies.size():
+		var body = bodies[idx]
+		var offset = Vector3(idx, 0, -idx) * 0.1
+		actions.append({ "node": body, "operation": "ResetForces", "data": {} })
+		actions.append({ "node": body, "operation": "SetLinearVelocity", "data": { "velocity": Vector3(0, 1, 0) + offset } })
+		actions.append({ "node": body, "operation": "SetAngularVelocity", "data": { "velocity": Vector3(0.5, 0, 0) } })
+		actions.append({ "node": body, "operation": "ApplyImpulse", "data": { "impulse": Vector3(0.3, 2.1, -0.7) } })
+		actions.append({ "node": body, "operation": "ApplyImpulse", "data": { "impulse": Vector3(-0.1, 0.7, 0.2) + offset } })
+		actions.append({ "node": body, "operation": "ApplyImpulse", "data": { "impulse": Vector3(1e-4, 3e3, -1e-3) } })
+		actions.append({ "node": body, "operation": "ApplyTorqueImpulse", "data": { "torque": Vector3(0, 0.2, 0.1) } })
+		actions.append({ "node": body, "operation": "AddForce", "data": { "force": Vector3(5, 0, 0) - offset } })
+	return actions
+
+func shuffle(actions: Array, rng: RandomNumberGenerator) -> Array:
+	var shuffled = actions.duplicate()
+	for i in range(shuffled.size() - 1, 0, -1):
+		var j = rng.randi_range(0, i)
+		var tmp = shuffled[i]
+		shuffled[i] = shuffled[j]
+		shuffled[j] = tmp
+	return shuffled